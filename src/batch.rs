@@ -0,0 +1,377 @@
+//! Shrink several files, with the possibility to stop midway.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::runner::{check_deadline, run_polling};
+use crate::{
+    resolve_ghostscript, shrink_file_with, shrink_in_place, shrink_in_place_with, CommandOutput,
+    Runner, ShrinkError, ShrinkOptions, ShrinkOutcome, SystemRunner,
+};
+
+/// Flag requesting to stop a batch, shared between the batch and whoever can cancel it.
+///
+/// Clones share the same flag.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::CancellationToken;
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+/// handle.cancel();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Requests the cancellation; it cannot be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// [`Runner`] spawning the commands as child processes, like [`SystemRunner`](crate::SystemRunner),
+/// but killing them as soon as its token is cancelled.
+///
+/// A killed command results in an error of kind [`io::ErrorKind::Interrupted`].
+#[derive(Debug, Clone)]
+pub struct CancellableRunner {
+    pub token: CancellationToken,
+}
+
+impl CancellableRunner {
+    pub fn new(token: CancellationToken) -> CancellableRunner {
+        CancellableRunner { token }
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+}
+
+impl CancellableRunner {
+    fn check_cancelled(&self) -> Option<io::Error> {
+        if self.token.is_cancelled() {
+            Some(cancelled())
+        } else {
+            None
+        }
+    }
+}
+
+impl Runner for CancellableRunner {
+    fn run(&self, cmd: &mut Command) -> io::Result<CommandOutput> {
+        #[cfg(feature = "logging")]
+        trace!("CancellableRunner::run({:?})", cmd);
+        if let Some(e) = self.check_cancelled() {
+            return Err(e);
+        }
+        run_polling(cmd, || self.check_cancelled())
+    }
+
+    fn run_with_timeout(&self, cmd: &mut Command, timeout: Duration) -> io::Result<CommandOutput> {
+        #[cfg(feature = "logging")]
+        trace!(
+            "CancellableRunner::run_with_timeout({:?}, {:?})",
+            cmd,
+            timeout
+        );
+        if let Some(e) = self.check_cancelled() {
+            return Err(e);
+        }
+        let deadline = Instant::now() + timeout;
+        run_polling(cmd, || {
+            self.check_cancelled().or_else(|| check_deadline(deadline))
+        })
+    }
+}
+
+/// Shrinks each input into its output, in order, until `token` is cancelled.
+///
+/// The Ghostscript process running when the token is cancelled is killed, its partial output
+/// removed, and its result is [`ShrinkError::Cancelled`]; the remaining jobs are not started.
+/// Returns the results of the jobs which were started, in order.
+///
+/// If [`ShrinkOptions::ghostscript`] is not set, the executable is located once
+/// with [`resolve_ghostscript`].
+pub fn shrink_batch<I, P, Q>(
+    jobs: I,
+    options: &ShrinkOptions,
+    token: &CancellationToken,
+) -> Result<Vec<Result<ShrinkOutcome, ShrinkError>>, ShrinkError>
+where
+    I: IntoIterator<Item = (P, Q)>,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let runner = CancellableRunner::new(token.clone());
+    if options.ghostscript.is_some() {
+        Ok(shrink_batch_with(&runner, jobs, options, token))
+    } else {
+        let options = ShrinkOptions {
+            ghostscript: Some(resolve_ghostscript()?),
+            ..options.clone()
+        };
+        Ok(shrink_batch_with(&runner, jobs, &options, token))
+    }
+}
+
+/// Like [`shrink_batch`], but executes the commands with `runner`.
+///
+/// The `runner` should stop the running command when `token` is cancelled,
+/// like [`CancellableRunner`] does; otherwise the batch stops after it.
+pub fn shrink_batch_with<R, I, P, Q>(
+    runner: &R,
+    jobs: I,
+    options: &ShrinkOptions,
+    token: &CancellationToken,
+) -> Vec<Result<ShrinkOutcome, ShrinkError>>
+where
+    R: Runner + ?Sized,
+    I: IntoIterator<Item = (P, Q)>,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut results = Vec::new();
+    for (inpath, outpath) in jobs {
+        if token.is_cancelled() {
+            #[cfg(feature = "logging")]
+            trace!("shrink_batch_with: cancelled after {} files", results.len());
+            break;
+        }
+        let (inpath, outpath) = (inpath.as_ref(), outpath.as_ref());
+        let result = match shrink_file_with(runner, inpath, outpath, options) {
+            Err(ShrinkError::SpawnFailed { source, .. })
+                if source.kind() == io::ErrorKind::Interrupted && token.is_cancelled() =>
+            {
+                let _ = fs::remove_file(outpath);
+                Err(ShrinkError::Cancelled {
+                    path: inpath.into(),
+                })
+            }
+            result => result,
+        };
+        results.push(result);
+    }
+    results
+}
+
+/// Shrinks each of `paths` in place, in order, calling `progress` before each file.
+///
+/// The callback receives the path about to be shrunk, its index starting from `0`,
+/// and the number of paths, which is enough to drive a progress bar.
+/// A failure does not stop the batch: the results of all the files are returned, in order.
+///
+/// If [`ShrinkOptions::ghostscript`] is not set, the executable is located once
+/// with [`resolve_ghostscript`]; if that fails, every result reports why.
+pub fn shrink_all<P, F>(
+    paths: &[P],
+    options: &ShrinkOptions,
+    progress: F,
+) -> Vec<Result<ShrinkOutcome, ShrinkError>>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path, usize, usize),
+{
+    if options.ghostscript.is_some() {
+        return shrink_all_with(&SystemRunner, paths, options, progress);
+    }
+    match resolve_ghostscript() {
+        Ok(ghostscript) => {
+            let options = ShrinkOptions {
+                ghostscript: Some(ghostscript),
+                ..options.clone()
+            };
+            shrink_all_with(&SystemRunner, paths, &options, progress)
+        }
+        Err(_) => shrink_each(paths, progress, |path| shrink_in_place(path, options)),
+    }
+}
+
+/// Like [`shrink_all`], but executes the commands with `runner`.
+pub fn shrink_all_with<R, P, F>(
+    runner: &R,
+    paths: &[P],
+    options: &ShrinkOptions,
+    progress: F,
+) -> Vec<Result<ShrinkOutcome, ShrinkError>>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+    F: FnMut(&Path, usize, usize),
+{
+    shrink_each(paths, progress, |path| {
+        shrink_in_place_with(runner, path, options)
+    })
+}
+
+fn shrink_each<P, F, S>(
+    paths: &[P],
+    mut progress: F,
+    mut shrink: S,
+) -> Vec<Result<ShrinkOutcome, ShrinkError>>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path, usize, usize),
+    S: FnMut(&Path) -> Result<ShrinkOutcome, ShrinkError>,
+{
+    let total = paths.len();
+    paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let path = path.as_ref();
+            progress(path, index, total);
+            shrink(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{output_file, pdf_bytes, MockRunner, TempDir};
+    use std::thread;
+
+    #[test]
+    fn test_cancel_between_files() {
+        let dir = TempDir::new();
+        let jobs: Vec<_> = (0..4)
+            .map(|i| {
+                let input = dir.pdf(&format!("{}.pdf", i), 100);
+                (input, dir.path().join(format!("{}.shrunk.pdf", i)))
+            })
+            .collect();
+
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        let runner = MockRunner::new(move |argv| {
+            let out = output_file(argv).unwrap();
+            fs::write(&out, pdf_bytes(50))?;
+            // the user cancels while the second file is being processed
+            if out.ends_with("1.shrunk.pdf") {
+                handle.cancel();
+            }
+            Ok(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+        });
+
+        let results = shrink_batch_with(&runner, jobs.clone(), &ShrinkOptions::default(), &token);
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Ok(ShrinkOutcome::Shrunk(_)))));
+        assert_eq!(runner.calls().len(), 2);
+        assert!(!jobs[2].1.exists());
+        assert!(!jobs[3].1.exists());
+    }
+
+    #[test]
+    fn test_cancel_running_file() {
+        let dir = TempDir::new();
+        let input = dir.pdf("a.pdf", 100);
+        let output = dir.path().join("a.shrunk.pdf");
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        // Ghostscript is killed after writing part of the output
+        let runner = MockRunner::new(move |argv| {
+            fs::write(output_file(argv).unwrap(), b"%PDF-1.4\n")?;
+            handle.cancel();
+            Err(cancelled())
+        });
+        let jobs = [(&input, &output), (&input, &output)];
+        let results = shrink_batch_with(&runner, jobs, &ShrinkOptions::default(), &token);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(&results[0], Err(ShrinkError::Cancelled { path }) if *path == input));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_cancelled_before_start() {
+        let dir = TempDir::new();
+        let input = dir.pdf("a.pdf", 100);
+        let token = CancellationToken::new();
+        token.cancel();
+        let runner = MockRunner::gs(50);
+        let jobs = [(&input, dir.path().join("a.shrunk.pdf"))];
+        assert!(shrink_batch_with(&runner, jobs, &ShrinkOptions::default(), &token).is_empty());
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_shrink_all_progress() {
+        let dir = TempDir::new();
+        let paths = [
+            dir.pdf("a.pdf", 100),
+            dir.path().join("missing.pdf"),
+            dir.pdf("b.pdf", 100),
+        ];
+        let mut calls = Vec::new();
+        let results = shrink_all_with(
+            &MockRunner::gs(50),
+            &paths,
+            &ShrinkOptions::default(),
+            |path, index, total| calls.push((path.to_path_buf(), index, total)),
+        );
+        assert_eq!(
+            calls,
+            [
+                (paths[0].clone(), 0, 3),
+                (paths[1].clone(), 1, 3),
+                (paths[2].clone(), 2, 3)
+            ]
+        );
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], Ok(ShrinkOutcome::Shrunk(_))));
+        assert!(results[1].is_err());
+        assert!(matches!(results[2], Ok(ShrinkOutcome::Shrunk(_))));
+        assert_eq!(fs::metadata(&paths[2]).unwrap().len(), 50);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_running_command() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            handle.cancel();
+        });
+        let start = Instant::now();
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let err = CancellableRunner::new(token).run(&mut cmd).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let output = CancellableRunner::new(CancellationToken::new())
+            .run(&mut cmd)
+            .unwrap();
+        assert_eq!(output.code, Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+}
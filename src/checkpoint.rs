@@ -0,0 +1,180 @@
+//! Record of the inputs already shrunk, to resume an interrupted batch.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::ShrinkError;
+
+/// Inputs completed by previous runs, read from a file with one path per line,
+/// to which the inputs completed by this run are appended.
+///
+/// The paths are compared as they are written, without resolving them.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::Checkpoint;
+/// # let dir = std::env::temp_dir().join(format!("pdfshrink-doc-checkpoint-{}", std::process::id()));
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// let path = dir.join("progress.txt");
+/// # let _ = std::fs::remove_file(&path);
+/// let mut checkpoint = Checkpoint::open(&path)?;
+/// checkpoint.record("a.pdf")?;
+///
+/// // after a restart
+/// let checkpoint = Checkpoint::open(&path)?;
+/// assert!(checkpoint.contains("a.pdf"));
+/// assert!(!checkpoint.contains("b.pdf"));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// # Ok::<(), pdfshrink::ShrinkError>(())
+/// ```
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    done: HashSet<PathBuf>,
+    file: File,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint `path`, creating it if it does not exist.
+    pub fn open<P>(path: P) -> Result<Checkpoint, ShrinkError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let io_error = |source| ShrinkError::Io {
+            path: path.into(),
+            source,
+        };
+        let done = match std::fs::read_to_string(path) {
+            Ok(text) => text
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(io_error(e)),
+        };
+        #[cfg(feature = "logging")]
+        trace!("Checkpoint::open({:?}): {} inputs done", path, done.len());
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(io_error)?;
+        Ok(Checkpoint {
+            path: path.into(),
+            done,
+            file,
+        })
+    }
+
+    fn io_error(&self, source: io::Error) -> ShrinkError {
+        ShrinkError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Path of the checkpoint file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether `input` was completed.
+    pub fn contains<P>(&self, input: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        self.done.contains(input.as_ref())
+    }
+
+    /// Number of completed inputs.
+    pub fn len(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.done.is_empty()
+    }
+
+    /// Marks `input` as completed, appending it to the file right away so that
+    /// it survives an interruption.
+    ///
+    /// Paths containing a newline, or which are not valid UTF-8, cannot be recorded.
+    pub fn record<P>(&mut self, input: P) -> Result<(), ShrinkError>
+    where
+        P: AsRef<Path>,
+    {
+        let input = input.as_ref();
+        if self.done.contains(input) {
+            return Ok(());
+        }
+        let line = match input.to_str() {
+            Some(s) if !s.contains('\n') && !s.is_empty() => s,
+            _ => {
+                let message = format!("cannot record {:?}", input);
+                return Err(self.io_error(io::Error::new(io::ErrorKind::InvalidInput, message)));
+            }
+        };
+        writeln!(self.file, "{}", line)
+            .and_then(|()| self.file.flush())
+            .map_err(|source| self.io_error(source))?;
+        self.done.insert(input.into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_record_and_resume() {
+        let dir = TempDir::new();
+        let path = dir.path().join("checkpoint.txt");
+
+        let mut checkpoint = Checkpoint::open(&path).unwrap();
+        assert!(checkpoint.is_empty());
+        checkpoint.record("a.pdf").unwrap();
+        checkpoint.record("dir/b c.pdf").unwrap();
+        // recorded once
+        checkpoint.record("a.pdf").unwrap();
+        assert!(checkpoint.contains("a.pdf"));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a.pdf\ndir/b c.pdf\n"
+        );
+        // the process is interrupted
+        drop(checkpoint);
+
+        let mut checkpoint = Checkpoint::open(&path).unwrap();
+        assert_eq!(checkpoint.len(), 2);
+        assert!(checkpoint.contains("a.pdf"));
+        assert!(checkpoint.contains(Path::new("dir/b c.pdf")));
+        assert!(!checkpoint.contains("c.pdf"));
+        checkpoint.record("c.pdf").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "a.pdf\ndir/b c.pdf\nc.pdf\n"
+        );
+    }
+
+    #[test]
+    fn test_record_invalid_path() {
+        let dir = TempDir::new();
+        let mut checkpoint = Checkpoint::open(dir.path().join("checkpoint.txt")).unwrap();
+        assert!(matches!(
+            checkpoint.record("a\nb.pdf"),
+            Err(ShrinkError::Io { .. })
+        ));
+        assert!(checkpoint.is_empty());
+        assert!(Checkpoint::open(dir.path().join("missing/checkpoint.txt")).is_err());
+    }
+}
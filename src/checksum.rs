@@ -0,0 +1,227 @@
+//! Digests written next to the outputs, to check their integrity after distributing them.
+
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::ShrinkError;
+
+/// Hash function computing the digest written by [`write_checksum`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Md5,
+}
+
+impl ChecksumAlgorithm {
+    /// Extension appended to the name of a file to obtain its checksum file.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Md5 => "md5",
+        }
+    }
+
+    /// Hexadecimal digest of `bytes`.
+    pub fn digest(self, bytes: &[u8]) -> String {
+        let digest = match self {
+            ChecksumAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+            ChecksumAlgorithm::Md5 => Md5::digest(bytes).to_vec(),
+        };
+        digest
+            .iter()
+            .fold(String::with_capacity(2 * digest.len()), |mut hex, byte| {
+                let _ = write!(hex, "{:02x}", byte);
+                hex
+            })
+    }
+}
+
+impl std::str::FromStr for ChecksumAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ChecksumAlgorithm, String> {
+        match s {
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            "md5" => Ok(ChecksumAlgorithm::Md5),
+            _ => Err(format!(
+                "invalid checksum algorithm {:?}, expected sha256 or md5",
+                s
+            )),
+        }
+    }
+}
+
+/// Path of the checksum file of `path`, named after it with the extension of `algorithm`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{checksum_path, ChecksumAlgorithm};
+/// assert_eq!(
+///     checksum_path("dir/a.shrunk.pdf", ChecksumAlgorithm::Sha256),
+///     std::path::Path::new("dir/a.shrunk.pdf.sha256")
+/// );
+/// ```
+pub fn checksum_path<P>(path: P, algorithm: ChecksumAlgorithm) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut name = OsString::from(path.as_ref());
+    name.push(".");
+    name.push(algorithm.extension());
+    name.into()
+}
+
+/// Writes the digest of the file `path` to its [`checksum_path`], and returns the latter.
+///
+/// The line has the format of `sha256sum` and `md5sum`, the digest followed by the
+/// file name, so that the file can be verified with `sha256sum -c` from its directory.
+pub fn write_checksum<P>(path: P, algorithm: ChecksumAlgorithm) -> Result<PathBuf, ShrinkError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    #[cfg(feature = "logging")]
+    trace!("write_checksum({:?}, {:?})", path, algorithm);
+    let bytes = fs::read(path).map_err(|source| ShrinkError::Io {
+        path: path.into(),
+        source,
+    })?;
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    let line = format!(
+        "{}  {}\n",
+        algorithm.digest(&bytes),
+        Path::new(name).display()
+    );
+    let checksum = checksum_path(path, algorithm);
+    fs::write(&checksum, line).map_err(|source| ShrinkError::Io {
+        path: checksum.clone(),
+        source,
+    })?;
+    Ok(checksum)
+}
+
+/// Moves the file `path` into `dir`, naming it after its digest as `<digest>.pdf`.
+///
+/// If a file with that name already exists, it has the same content and `path` is
+/// removed instead. Returns the path named after the digest, and whether `path` was
+/// moved there rather than found to be a duplicate.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pdfshrink::{store_by_content, ChecksumAlgorithm};
+/// let (stored, new) = store_by_content("a.shrunk.pdf", "store", ChecksumAlgorithm::Sha256)?;
+/// if !new {
+///     println!("already stored as {}", stored.display());
+/// }
+/// # Ok::<(), pdfshrink::ShrinkError>(())
+/// ```
+pub fn store_by_content<P, Q>(
+    path: P,
+    dir: Q,
+    algorithm: ChecksumAlgorithm,
+) -> Result<(PathBuf, bool), ShrinkError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let finalize_error = |source| ShrinkError::FinalizeOutput {
+        path: path.into(),
+        source,
+    };
+    let bytes = fs::read(path).map_err(finalize_error)?;
+    let stored = dir
+        .as_ref()
+        .join(format!("{}.pdf", algorithm.digest(&bytes)));
+    let new = !stored.exists();
+    if new {
+        fs::rename(path, &stored).map_err(finalize_error)?;
+    } else {
+        fs::remove_file(path).map_err(finalize_error)?;
+    }
+    #[cfg(feature = "logging")]
+    trace!("store_by_content({:?}) = ({:?}, {})", path, stored, new);
+    Ok((stored, new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            ChecksumAlgorithm::Sha256.digest(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            ChecksumAlgorithm::Md5.digest(b"abc"),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!("md5".parse(), Ok(ChecksumAlgorithm::Md5));
+        assert!("sha1".parse::<ChecksumAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_write_checksum() {
+        let dir = TempDir::new();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiny.pdf");
+        let output = dir.path().join("tiny.shrunk.pdf");
+        fs::copy(&fixture, &output).unwrap();
+
+        let checksum = write_checksum(&output, ChecksumAlgorithm::Sha256).unwrap();
+        assert_eq!(checksum, dir.path().join("tiny.shrunk.pdf.sha256"));
+        assert_eq!(
+            fs::read_to_string(&checksum).unwrap(),
+            "68183657a4d0087a7271d2cfb2579db089749f6d1017d7c78f00c1c6d72b1d9f  tiny.shrunk.pdf\n"
+        );
+
+        let checksum = write_checksum(&output, ChecksumAlgorithm::Md5).unwrap();
+        assert_eq!(
+            fs::read_to_string(checksum).unwrap(),
+            "3ccba167e807bd20187e55eecee5bffd  tiny.shrunk.pdf\n"
+        );
+
+        assert!(matches!(
+            write_checksum(dir.path().join("missing.pdf"), ChecksumAlgorithm::Md5),
+            Err(ShrinkError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_store_by_content() {
+        let dir = TempDir::new();
+        let store = dir.path().join("store");
+        fs::create_dir(&store).unwrap();
+        let a = dir.pdf("a.pdf", 10);
+        let b = dir.pdf("b.pdf", 10);
+        let c = dir.pdf("c.pdf", 20);
+
+        let (stored, new) = store_by_content(&a, &store, ChecksumAlgorithm::Md5).unwrap();
+        assert!(new);
+        let digest = ChecksumAlgorithm::Md5.digest(&crate::testing::pdf_bytes(10));
+        assert_eq!(stored, store.join(format!("{}.pdf", digest)));
+        assert!(stored.is_file() && !a.exists());
+
+        assert_eq!(
+            store_by_content(&b, &store, ChecksumAlgorithm::Md5).unwrap(),
+            (stored.clone(), false)
+        );
+        assert!(!b.exists());
+
+        let (other, new) = store_by_content(&c, &store, ChecksumAlgorithm::Md5).unwrap();
+        assert!(new && other != stored);
+        assert_eq!(fs::read_dir(&store).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,202 @@
+//! Troubleshooting information about the environment.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{which, Runner, ShrinkOptions};
+
+/// Ghostscript devices relevant to this crate.
+pub const RELEVANT_DEVICES: &[&str] = &["pdfwrite", "ps2write", "nullpage"];
+
+/// Facts about the environment, see [`probe_environment`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Environment {
+    /// The Ghostscript executable, or the error message if it was not found.
+    pub ghostscript: Result<PathBuf, String>,
+    /// Output of `gs --version`.
+    pub ghostscript_version: Option<String>,
+    /// Devices listed by `gs -h`.
+    pub devices: Vec<String>,
+    /// The `pdfimages` executable from Poppler, used to list the embedded images,
+    /// see [`probe_images`](crate::probe_images).
+    pub pdfimages: Option<PathBuf>,
+    pub temp_dir: PathBuf,
+    /// Number of CPUs available to the process.
+    pub cpus: Option<usize>,
+}
+
+/// Parses the device list printed by `gs -h`.
+fn parse_devices(help: &str) -> Vec<String> {
+    help.lines()
+        .skip_while(|line| !line.starts_with("Available devices:"))
+        .skip(1)
+        .take_while(|line| line.starts_with(char::is_whitespace))
+        .flat_map(str::split_whitespace)
+        .map(String::from)
+        .collect()
+}
+
+/// Collects the [`Environment`], querying `ghostscript` through `runner`.
+pub fn probe_environment<R>(runner: &R, ghostscript: Result<PathBuf, String>) -> Environment
+where
+    R: Runner + ?Sized,
+{
+    let query = |gs: &Path, arg: &str| {
+        let output = runner.run(Command::new(gs).arg(arg)).ok()?;
+        if output.success() {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            None
+        }
+    };
+    let (ghostscript_version, devices) = match &ghostscript {
+        Ok(gs) => (
+            query(gs, "--version").map(|v| v.trim().into()),
+            query(gs, "-h").map_or_else(Vec::new, |help| parse_devices(&help)),
+        ),
+        Err(_) => (None, vec![]),
+    };
+    Environment {
+        ghostscript,
+        ghostscript_version,
+        devices,
+        pdfimages: which("pdfimages"),
+        temp_dir: std::env::temp_dir(),
+        cpus: std::thread::available_parallelism().ok().map(usize::from),
+    }
+}
+
+/// Formats the troubleshooting report printed by `pdfshrink --doctor`.
+pub fn doctor_report(env: &Environment, options: &ShrinkOptions) -> String {
+    let mut report = String::new();
+    let unknown = || String::from("unknown");
+
+    writeln!(report, "Ghostscript:").unwrap();
+    match &env.ghostscript {
+        Ok(path) => writeln!(report, "    path: {}", path.display()).unwrap(),
+        Err(e) => writeln!(report, "    path: {}", e).unwrap(),
+    }
+    let version = env.ghostscript_version.clone().unwrap_or_else(unknown);
+    writeln!(report, "    version: {}", version).unwrap();
+
+    writeln!(report, "Devices:").unwrap();
+    for device in RELEVANT_DEVICES {
+        let available = env.devices.iter().any(|d| d == device);
+        let status = if available { "available" } else { "missing" };
+        writeln!(report, "    {}: {}", device, status).unwrap();
+    }
+
+    writeln!(report, "Tools:").unwrap();
+    match &env.pdfimages {
+        Some(path) => writeln!(report, "    pdfimages: {}", path.display()).unwrap(),
+        None => writeln!(
+            report,
+            "    pdfimages: missing (needed to list the images, install Poppler)"
+        )
+        .unwrap(),
+    }
+
+    writeln!(report, "System:").unwrap();
+    writeln!(
+        report,
+        "    temporary directory: {}",
+        env.temp_dir.display()
+    )
+    .unwrap();
+    let cpus = env.cpus.map_or_else(unknown, |n| n.to_string());
+    writeln!(report, "    CPUs: {}", cpus).unwrap();
+
+    writeln!(report, "Options:").unwrap();
+    writeln!(report, "    gs arguments: {}", options.settings_string()).unwrap();
+    for line in format!("{:#?}", options).lines() {
+        writeln!(report, "    {}", line).unwrap();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockRunner;
+    use crate::CommandOutput;
+
+    const GS_HELP: &str = "\
+GPL Ghostscript 10.02.1 (2023-11-01)
+Usage: gs [switches] [file1.ps file2.ps ...]
+Available devices:
+   alc1900 bbox nullpage pdfwrite
+   png16m txtwrite
+Search path:
+   /usr/share/ghostscript/10.02.1/Resource/Init
+";
+
+    fn mock_gs() -> MockRunner {
+        MockRunner::new(|argv| {
+            let stdout = match argv[1].to_str() {
+                Some("--version") => "10.02.1\n",
+                Some("-h") => GS_HELP,
+                _ => "",
+            };
+            Ok(CommandOutput {
+                code: Some(0),
+                stdout: stdout.into(),
+                ..Default::default()
+            })
+        })
+    }
+
+    #[test]
+    fn test_parse_devices() {
+        assert_eq!(
+            parse_devices(GS_HELP),
+            ["alc1900", "bbox", "nullpage", "pdfwrite", "png16m", "txtwrite"]
+        );
+        assert!(parse_devices("").is_empty());
+    }
+
+    #[test]
+    fn test_doctor_report() {
+        let runner = mock_gs();
+        let env = probe_environment(&runner, Ok("/usr/bin/gs".into()));
+        assert_eq!(runner.calls().len(), 2);
+        assert_eq!(env.ghostscript_version.as_deref(), Some("10.02.1"));
+
+        let report = doctor_report(&env, &ShrinkOptions::default());
+        for section in &["Ghostscript:", "Devices:", "Tools:", "System:", "Options:"] {
+            assert!(report.contains(section), "missing {}", section);
+        }
+        assert!(report.contains("    path: /usr/bin/gs\n"));
+        assert!(report.contains("    version: 10.02.1\n"));
+        assert!(report.contains("    pdfwrite: available\n"));
+        assert!(report.contains("    ps2write: missing\n"));
+        assert!(report.contains("    temporary directory: "));
+        assert!(report.contains("-dPDFSETTINGS=/ebook"));
+    }
+
+    #[test]
+    fn test_doctor_report_without_gs() {
+        let runner = mock_gs();
+        let env = probe_environment(&runner, Err("Ghostscript not found".into()));
+        assert!(runner.calls().is_empty());
+
+        let report = doctor_report(&env, &ShrinkOptions::default());
+        assert!(report.contains("    path: Ghostscript not found\n"));
+        assert!(report.contains("    version: unknown\n"));
+        assert!(report.contains("    pdfwrite: missing\n"));
+
+        let env = Environment {
+            pdfimages: None,
+            ..env
+        };
+        let report = doctor_report(&env, &ShrinkOptions::default());
+        assert!(report
+            .contains("    pdfimages: missing (needed to list the images, install Poppler)\n"));
+        let env = Environment {
+            pdfimages: Some("/usr/bin/pdfimages".into()),
+            ..env
+        };
+        let report = doctor_report(&env, &ShrinkOptions::default());
+        assert!(report.contains("    pdfimages: /usr/bin/pdfimages\n"));
+    }
+}
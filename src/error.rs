@@ -0,0 +1,274 @@
+//! Errors reported while shrinking a file.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Error occurred while shrinking a file.
+#[derive(Debug)]
+pub enum ShrinkError {
+    /// The Ghostscript executable could not be found.
+    GhostscriptNotFound { tried: Vec<String> },
+    /// An I/O operation on `path` failed.
+    Io { path: PathBuf, source: io::Error },
+    /// No output can be named after the input `path`, for instance because it has
+    /// no `.pdf` extension.
+    InvalidInputPath { path: PathBuf },
+    /// The input `path` could not be read.
+    ReadInput { path: PathBuf, source: io::Error },
+    /// The directory `path` of the output could not be created.
+    CreateOutputDir { path: PathBuf, source: io::Error },
+    /// The output `path` cannot be written, because of the permissions of the file
+    /// or of its directory.
+    OutputNotWritable { path: PathBuf },
+    /// The program (Ghostscript) could not be started.
+    SpawnFailed { program: PathBuf, source: io::Error },
+    /// The output `path` written by Ghostscript could not be inspected or adjusted.
+    FinalizeOutput { path: PathBuf, source: io::Error },
+    /// Ghostscript terminated unsuccessfully.
+    NonZeroExit { code: Option<i32>, stderr: String },
+    /// Ghostscript was killed because shrinking the input `path` took longer than `timeout`,
+    /// see [`ShrinkOptions::timeout`](crate::ShrinkOptions::timeout).
+    Timeout { path: PathBuf, timeout: Duration },
+    /// Ghostscript terminated successfully, but `path` is not a valid PDF.
+    InvalidOutput { path: PathBuf },
+    /// The version printed by Ghostscript, `output`, could not be parsed.
+    InvalidVersion { output: String },
+    /// The [`compatibility_level`](crate::ShrinkOptions::compatibility_level) `level` is not
+    /// one of the [`COMPATIBILITY_LEVELS`](crate::COMPATIBILITY_LEVELS).
+    InvalidCompatibilityLevel { level: String },
+    /// The post-processing command could not be run or terminated unsuccessfully.
+    PostProcessFailed {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// The validation command rejected the output or could not be run.
+    ValidationFailed {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// The sidecar file `path` overriding the options of an input is invalid.
+    InvalidSidecar { path: PathBuf, message: String },
+    /// The file `path` defining [presets](crate::Presets) is invalid.
+    InvalidPresets { path: PathBuf, message: String },
+    /// The output would overwrite the input `path` while Ghostscript reads it.
+    OutputIsInput { path: PathBuf },
+    /// The batch was cancelled while shrinking the input `path`; its partial output was removed.
+    Cancelled { path: PathBuf },
+}
+
+impl ShrinkError {
+    /// Short kebab-case name of the variant, such as `"non-zero-exit"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ShrinkError::GhostscriptNotFound { .. } => "ghostscript-not-found",
+            ShrinkError::Io { .. } => "io",
+            ShrinkError::InvalidInputPath { .. } => "invalid-input-path",
+            ShrinkError::ReadInput { .. } => "read-input",
+            ShrinkError::CreateOutputDir { .. } => "create-output-dir",
+            ShrinkError::OutputNotWritable { .. } => "output-not-writable",
+            ShrinkError::SpawnFailed { .. } => "spawn-failed",
+            ShrinkError::FinalizeOutput { .. } => "finalize-output",
+            ShrinkError::NonZeroExit { .. } => "non-zero-exit",
+            ShrinkError::Timeout { .. } => "timeout",
+            ShrinkError::InvalidOutput { .. } => "invalid-output",
+            ShrinkError::InvalidVersion { .. } => "invalid-version",
+            ShrinkError::InvalidCompatibilityLevel { .. } => "invalid-compatibility-level",
+            ShrinkError::PostProcessFailed { .. } => "post-process-failed",
+            ShrinkError::ValidationFailed { .. } => "validation-failed",
+            ShrinkError::InvalidSidecar { .. } => "invalid-sidecar",
+            ShrinkError::InvalidPresets { .. } => "invalid-presets",
+            ShrinkError::OutputIsInput { .. } => "output-is-input",
+            ShrinkError::Cancelled { .. } => "cancelled",
+        }
+    }
+
+    /// JSON object describing the error which occurred processing `input`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::ShrinkError;
+    /// let e = ShrinkError::InvalidOutput { path: "out.pdf".into() };
+    /// let json = e.to_json("in.pdf".as_ref());
+    /// assert_eq!(json["kind"], "invalid-output");
+    /// assert_eq!(json["input"], "in.pdf");
+    /// assert_eq!(json["error"], e.to_string());
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, input: &std::path::Path) -> serde_json::Value {
+        serde_json::json!({
+            "error": self.to_string(),
+            "input": input.to_string_lossy(),
+            "kind": self.kind(),
+        })
+    }
+}
+
+impl fmt::Display for ShrinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShrinkError::GhostscriptNotFound { tried } => {
+                write!(f, "Ghostscript not found (tried {})", tried.join(", "))
+            }
+            ShrinkError::Io { path, source } => write!(f, "cannot access {:?}: {}", path, source),
+            ShrinkError::InvalidInputPath { path } => {
+                write!(f, "cannot name an output after the input {:?}", path)
+            }
+            ShrinkError::ReadInput { path, source } => {
+                write!(f, "cannot read the input {:?}: {}", path, source)
+            }
+            ShrinkError::CreateOutputDir { path, source } => {
+                write!(
+                    f,
+                    "cannot create the output directory {:?}: {}",
+                    path, source
+                )
+            }
+            ShrinkError::OutputNotWritable { path } => {
+                write!(f, "the output {:?} is not writable", path)
+            }
+            ShrinkError::SpawnFailed { program, source } => {
+                write!(f, "failed to execute {:?}: {}", program, source)
+            }
+            ShrinkError::FinalizeOutput { path, source } => {
+                write!(f, "cannot finalize the output {:?}: {}", path, source)
+            }
+            ShrinkError::NonZeroExit {
+                code: Some(code), ..
+            } => {
+                write!(f, "Ghostscript exited with code {}", code)
+            }
+            ShrinkError::NonZeroExit { code: None, .. } => {
+                write!(f, "Ghostscript was terminated by a signal")
+            }
+            ShrinkError::Timeout { path, timeout } => write!(
+                f,
+                "Ghostscript did not finish shrinking {:?} within {:?}",
+                path, timeout
+            ),
+            ShrinkError::InvalidOutput { path } => {
+                write!(f, "Ghostscript did not write a valid PDF to {:?}", path)
+            }
+            ShrinkError::InvalidVersion { output } => {
+                write!(f, "cannot parse the Ghostscript version {:?}", output)
+            }
+            ShrinkError::InvalidCompatibilityLevel { level } => {
+                write!(f, "unknown PDF compatibility level {:?}", level)
+            }
+            ShrinkError::PostProcessFailed {
+                command,
+                code: Some(code),
+                ..
+            } => write!(f, "`{}` exited with code {}", command, code),
+            ShrinkError::PostProcessFailed {
+                command, stderr, ..
+            } => {
+                write!(f, "`{}` failed: {}", command, stderr)
+            }
+            ShrinkError::ValidationFailed {
+                command,
+                code: Some(code),
+                ..
+            } => write!(
+                f,
+                "the output failed validation: `{}` exited with code {}",
+                command, code
+            ),
+            ShrinkError::ValidationFailed {
+                command, stderr, ..
+            } => {
+                write!(
+                    f,
+                    "the output could not be validated: `{}` failed: {}",
+                    command, stderr
+                )
+            }
+            ShrinkError::InvalidSidecar { path, message } => {
+                write!(f, "invalid sidecar {:?}: {}", path, message)
+            }
+            ShrinkError::InvalidPresets { path, message } => {
+                write!(f, "invalid presets file {:?}: {}", path, message)
+            }
+            ShrinkError::OutputIsInput { path } => {
+                write!(f, "the output would overwrite the input {:?}", path)
+            }
+            ShrinkError::Cancelled { path } => write!(f, "cancelled while shrinking {:?}", path),
+        }
+    }
+}
+
+impl std::error::Error for ShrinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ShrinkError::Io { source, .. }
+            | ShrinkError::ReadInput { source, .. }
+            | ShrinkError::CreateOutputDir { source, .. }
+            | ShrinkError::SpawnFailed { source, .. }
+            | ShrinkError::FinalizeOutput { source, .. } => Some(source),
+            ShrinkError::GhostscriptNotFound { .. }
+            | ShrinkError::NonZeroExit { .. }
+            | ShrinkError::Timeout { .. }
+            | ShrinkError::InvalidOutput { .. }
+            | ShrinkError::InvalidVersion { .. }
+            | ShrinkError::InvalidCompatibilityLevel { .. }
+            | ShrinkError::InvalidInputPath { .. }
+            | ShrinkError::OutputNotWritable { .. }
+            | ShrinkError::PostProcessFailed { .. }
+            | ShrinkError::ValidationFailed { .. }
+            | ShrinkError::InvalidSidecar { .. }
+            | ShrinkError::InvalidPresets { .. }
+            | ShrinkError::OutputIsInput { .. }
+            | ShrinkError::Cancelled { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let e = ShrinkError::NonZeroExit {
+            code: Some(1),
+            stderr: String::new(),
+        };
+        assert_eq!(e.kind(), "non-zero-exit");
+        let e = ShrinkError::SpawnFailed {
+            program: "gs".into(),
+            source: io::ErrorKind::NotFound.into(),
+        };
+        assert_eq!(e.kind(), "spawn-failed");
+        let e = ShrinkError::InvalidInputPath {
+            path: "notes.txt".into(),
+        };
+        assert_eq!(e.kind(), "invalid-input-path");
+        assert_eq!(
+            e.to_string(),
+            "cannot name an output after the input \"notes.txt\""
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_to_json() {
+        let e = ShrinkError::NonZeroExit {
+            code: Some(1),
+            stderr: "Unrecoverable error".into(),
+        };
+        let line = e.to_json("dir/my file.pdf".as_ref()).to_string();
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "error": "Ghostscript exited with code 1",
+                "input": "dir/my file.pdf",
+                "kind": "non-zero-exit",
+            })
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Explain what shrinking did to the images of a file.
+//!
+//! Ghostscript does not report which images it downsamples, so the images of the
+//! input and of the output, as listed by [`probe_images`](crate::probe_images),
+//! are compared instead. This requires the program `pdfimages` from
+//! [Poppler](https://poppler.freedesktop.org/).
+
+use std::fmt;
+
+use crate::ImageInfo;
+
+/// What became of an image of the input in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageChange {
+    /// The output image has fewer pixels.
+    Downsampled,
+    /// The output image has at least as many pixels.
+    Kept,
+    /// The output has no corresponding image, for instance because it was merged
+    /// with others or drawn as vector graphics.
+    Dropped,
+}
+
+/// An image of the input, paired with the corresponding image of the output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub input: ImageInfo,
+    pub output: Option<ImageInfo>,
+}
+
+impl ImageDiff {
+    pub fn change(&self) -> ImageChange {
+        let pixels = |image: &ImageInfo| u64::from(image.width) * u64::from(image.height);
+        match &self.output {
+            Some(output) if pixels(output) < pixels(&self.input) => ImageChange::Downsampled,
+            Some(_) => ImageChange::Kept,
+            None => ImageChange::Dropped,
+        }
+    }
+}
+
+fn describe(image: &ImageInfo) -> String {
+    format!(
+        "{}x{} {} {} ppi",
+        image.width,
+        image.height,
+        image.color,
+        image.ppi()
+    )
+}
+
+/// One line of a diff-style report, such as
+/// `page 1: 2480x3508 gray 300 ppi -> 1240x1754 gray 150 ppi (downsampled)`.
+impl fmt::Display for ImageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "page {}: {} -> ", self.input.page, describe(&self.input))?;
+        match (&self.output, self.change()) {
+            (Some(output), ImageChange::Downsampled) => {
+                write!(f, "{} (downsampled)", describe(output))
+            }
+            (Some(output), _) => write!(f, "{} (kept)", describe(output)),
+            (None, _) => write!(f, "none (dropped)"),
+        }
+    }
+}
+
+/// Pairs the `input` images with the `output` ones, in the order they are drawn on each page.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{diff_images, parse_image_list, ImageChange};
+/// let input = parse_image_list(
+///     "   1     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  528K 6.2%",
+/// );
+/// let output = parse_image_list(
+///     "   1     0 image    1240  1754  gray    1   8  jpeg   no         9  0   150   150  120K 5.7%",
+/// );
+/// let diffs = diff_images(&input, &output);
+/// assert_eq!(diffs[0].change(), ImageChange::Downsampled);
+/// assert_eq!(
+///     diffs[0].to_string(),
+///     "page 1: 2480x3508 gray 300 ppi -> 1240x1754 gray 150 ppi (downsampled)"
+/// );
+/// ```
+pub fn diff_images(input: &[ImageInfo], output: &[ImageInfo]) -> Vec<ImageDiff> {
+    let mut output_pages = output.iter().peekable();
+    let mut diffs = Vec::with_capacity(input.len());
+    let mut on_page: Vec<&ImageInfo> = Vec::new();
+    let mut page = None;
+    for image in input {
+        if page != Some(image.page) {
+            page = Some(image.page);
+            // the images of the same page, in reverse order to pop them
+            on_page.clear();
+            while output_pages.peek().is_some_and(|o| o.page < image.page) {
+                output_pages.next();
+            }
+            while let Some(o) = output_pages.next_if(|o| o.page == image.page) {
+                on_page.push(o);
+            }
+            on_page.reverse();
+        }
+        diffs.push(ImageDiff {
+            input: image.clone(),
+            output: on_page.pop().cloned(),
+        });
+    }
+    diffs
+}
+
+/// Numbers of images of each [`ImageChange`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageChanges {
+    pub downsampled: usize,
+    pub kept: usize,
+    pub dropped: usize,
+}
+
+impl ImageChanges {
+    pub fn count(diffs: &[ImageDiff]) -> ImageChanges {
+        let mut changes = ImageChanges::default();
+        for diff in diffs {
+            match diff.change() {
+                ImageChange::Downsampled => changes.downsampled += 1,
+                ImageChange::Kept => changes.kept += 1,
+                ImageChange::Dropped => changes.dropped += 1,
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for ImageChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} downsampled, {} kept, {} dropped",
+            self.downsampled, self.kept, self.dropped
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_image_list;
+
+    const INPUT: &str = "\
+page   num  type   width height color comp bpc  enc interp  object ID x-ppi y-ppi size ratio
+--------------------------------------------------------------------------------------------
+   1     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  528K 6.2%
+   1     1 smask    2480  3508  gray    1   8  image  no         7  0   300   300 1024B 0.0%
+   1     2 image     200   100  rgb     3   8  image  no         8  0    72    72 5000B 8.3%
+   2     3 image    4960  7016  gray    1   1  ccitt  no        12  0   600   600  120K 2.8%
+   3     4 image     640   480  rgb     3   8  jpeg   no        15  0   96    96   30K 3.3%
+";
+
+    const OUTPUT: &str = "\
+page   num  type   width height color comp bpc  enc interp  object ID x-ppi y-ppi size ratio
+--------------------------------------------------------------------------------------------
+   1     0 image    1240  1754  gray    1   8  jpeg   no         9  0   150   150  120K 5.7%
+   1     1 image     200   100  rgb     3   8  jpeg   no        10  0    72    72 2000B 3.3%
+   2     2 image    4960  7016  gray    1   1  ccitt  no        14  0   600   600  120K 2.8%
+";
+
+    #[test]
+    fn test_diff_images() {
+        let diffs = diff_images(&parse_image_list(INPUT), &parse_image_list(OUTPUT));
+        let changes: Vec<ImageChange> = diffs.iter().map(ImageDiff::change).collect();
+        assert_eq!(
+            changes,
+            [
+                ImageChange::Downsampled,
+                ImageChange::Kept,
+                ImageChange::Kept,
+                ImageChange::Dropped
+            ]
+        );
+        assert_eq!(
+            diffs[1].to_string(),
+            "page 1: 200x100 rgb 72 ppi -> 200x100 rgb 72 ppi (kept)"
+        );
+        assert_eq!(
+            diffs[3].to_string(),
+            "page 3: 640x480 rgb 96 ppi -> none (dropped)"
+        );
+
+        let changes = ImageChanges::count(&diffs);
+        assert_eq!(
+            changes,
+            ImageChanges {
+                downsampled: 1,
+                kept: 2,
+                dropped: 1
+            }
+        );
+        assert_eq!(changes.to_string(), "1 downsampled, 2 kept, 1 dropped");
+        assert!(diff_images(&[], &parse_image_list(OUTPUT)).is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+//! Locate the Ghostscript executable.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::{Runner, ShrinkError, SystemRunner};
+
+/// Names and paths tried, in order, when looking for Ghostscript.
+///
+/// Bare names are searched in the `PATH`, absolute paths are checked directly.
+pub const GHOSTSCRIPT_CANDIDATES: &[&str] = &[
+    "gs",
+    "ghostscript",
+    #[cfg(windows)]
+    "gswin64c",
+    #[cfg(windows)]
+    "gswin32c",
+    #[cfg(target_os = "macos")]
+    "/opt/homebrew/bin/gs",
+    #[cfg(unix)]
+    "/usr/local/bin/gs",
+    #[cfg(unix)]
+    "/usr/bin/gs",
+];
+
+/// Searches the program `name` in the directories listed in the `PATH`.
+///
+/// On Windows the extensions in `PATHEXT` are tried as well.
+pub fn which<P>(name: P) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let name = name.as_ref();
+    if name.components().count() > 1 {
+        return Some(name.to_path_buf()).filter(|p| p.is_file());
+    }
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| String::from(".EXE;.CMD;.BAT;.COM"))
+            .split(';')
+            .map(String::from)
+            .collect()
+    } else {
+        vec![]
+    };
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        let path = dir.join(name);
+        if path.is_file() {
+            return Some(path);
+        }
+        extensions.iter().find_map(|ext| {
+            let mut file = name.as_os_str().to_os_string();
+            file.push(ext);
+            Some(dir.join(file)).filter(|p| p.is_file())
+        })
+    })
+}
+
+/// Executable of the newest Ghostscript installed under `root`, in the layout of
+/// the Windows installer: `root\gs\gs10.02.1\bin\gswin64c.exe`.
+#[cfg(any(windows, test))]
+fn newest_installed(root: &Path, executables: &[&str]) -> Option<PathBuf> {
+    let version = |dir: &Path| -> Vec<u32> {
+        dir.file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("gs"))
+            .map_or_else(Vec::new, |v| {
+                v.split('.').map(|n| n.parse().unwrap_or(0)).collect()
+            })
+    };
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(root.join("gs"))
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    dirs.sort_by_key(|dir| std::cmp::Reverse(version(dir)));
+    dirs.iter().find_map(|dir| {
+        executables
+            .iter()
+            .map(|exe| dir.join("bin").join(exe))
+            .find(|path| path.is_file())
+    })
+}
+
+/// Finds the Ghostscript executable of the platform.
+///
+/// On Windows, where it is never called `gs`, this searches `gswin64c` and `gswin32c`
+/// in the `PATH` and then in the install directories under `Program Files`.
+/// Elsewhere it searches `gs` in the `PATH`.
+///
+/// Unlike [`resolve_ghostscript`], this does not try the other [`GHOSTSCRIPT_CANDIDATES`].
+pub fn find_ghostscript() -> Option<PathBuf> {
+    #[cfg(windows)]
+    {
+        const EXECUTABLES: &[&str] = &["gswin64c.exe", "gswin32c.exe"];
+        which("gswin64c").or_else(|| which("gswin32c")).or_else(|| {
+            ["ProgramFiles", "ProgramFiles(x86)", "ProgramW6432"]
+                .iter()
+                .filter_map(env::var_os)
+                .chain(Some(r"C:\Program Files".into()))
+                .find_map(|root| newest_installed(Path::new(&root), EXECUTABLES))
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        which("gs")
+    }
+}
+
+/// Finds Ghostscript among `candidates`, using `lookup` to resolve each of them.
+///
+/// This is the logic of [`resolve_ghostscript`], with a pluggable lookup.
+pub fn resolve_ghostscript_with<F>(candidates: &[&str], lookup: F) -> Result<PathBuf, ShrinkError>
+where
+    F: Fn(&str) -> Option<PathBuf>,
+{
+    candidates
+        .iter()
+        .find_map(|name| lookup(name))
+        .ok_or_else(|| ShrinkError::GhostscriptNotFound {
+            tried: candidates.iter().map(|&name| name.into()).collect(),
+        })
+}
+
+/// Finds the Ghostscript executable, trying [`GHOSTSCRIPT_CANDIDATES`] in order
+/// and then [`find_ghostscript`].
+///
+/// The result is computed once and cached for the rest of the run.
+pub fn resolve_ghostscript() -> Result<PathBuf, ShrinkError> {
+    static RESOLVED: OnceLock<Option<PathBuf>> = OnceLock::new();
+    let resolved = RESOLVED.get_or_init(|| {
+        let result = resolve_ghostscript_with(GHOSTSCRIPT_CANDIDATES, |name| which(name))
+            .ok()
+            .or_else(find_ghostscript);
+        #[cfg(feature = "logging")]
+        trace!("resolve_ghostscript() = {:?}", result);
+        result
+    });
+    match resolved {
+        Some(path) => Ok(path.clone()),
+        None => resolve_ghostscript_with(GHOSTSCRIPT_CANDIDATES, |_| None),
+    }
+}
+
+/// Parses the major and minor version printed by `gs --version`, such as `10.02.1`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_ghostscript_version;
+/// assert_eq!(parse_ghostscript_version("10.02.1\n"), Some((10, 2)));
+/// assert_eq!(parse_ghostscript_version("9.56"), Some((9, 56)));
+/// assert_eq!(parse_ghostscript_version("unknown"), None);
+/// ```
+pub fn parse_ghostscript_version(s: &str) -> Option<(u32, u32)> {
+    let mut numbers = s.trim().split('.');
+    let major = numbers.next()?.parse().ok()?;
+    let minor = numbers.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Major and minor version of Ghostscript, found with [`resolve_ghostscript`].
+///
+/// Returns an error if Ghostscript cannot be found or run, or if its version cannot be parsed.
+pub fn ghostscript_version() -> Result<(u32, u32), ShrinkError> {
+    ghostscript_version_with(&SystemRunner, resolve_ghostscript()?)
+}
+
+/// Like [`ghostscript_version`], but runs `ghostscript --version` through `runner`.
+pub fn ghostscript_version_with<R, P>(runner: &R, ghostscript: P) -> Result<(u32, u32), ShrinkError>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+{
+    let ghostscript = ghostscript.as_ref();
+    let output = runner
+        .run(std::process::Command::new(ghostscript).arg("--version"))
+        .map_err(|source| ShrinkError::SpawnFailed {
+            program: ghostscript.into(),
+            source,
+        })?;
+    if !output.success() {
+        return Err(ShrinkError::NonZeroExit {
+            code: output.code,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version =
+        parse_ghostscript_version(&stdout).ok_or_else(|| ShrinkError::InvalidVersion {
+            output: stdout.trim().into(),
+        })?;
+    #[cfg(feature = "logging")]
+    trace!(
+        "ghostscript_version_with({:?}) = {:?}",
+        ghostscript,
+        version
+    );
+    Ok(version)
+}
+
+/// Explanation printed when Ghostscript cannot be found.
+pub const INSTALL_GUIDANCE: &str = "\
+Ghostscript is required to shrink PDF files, please install it:
+    Debian/Ubuntu: sudo apt install ghostscript
+    Fedora:        sudo dnf install ghostscript
+    macOS:         brew install ghostscript
+    Windows:       https://www.ghostscript.com/releases/gsdnld.html
+and make sure that it is in the PATH.";
+
+/// Checks once, before processing any file, that Ghostscript is available.
+///
+/// `resolve` is usually [`resolve_ghostscript`]. On failure the error is
+/// returned together with the [`INSTALL_GUIDANCE`], ready to be shown to the user.
+pub fn require_ghostscript<F>(resolve: F) -> Result<PathBuf, String>
+where
+    F: FnOnce() -> Result<PathBuf, ShrinkError>,
+{
+    resolve().map_err(|e| format!("{}\n{}", e, INSTALL_GUIDANCE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolution_order() {
+        let candidates = ["gs", "ghostscript", "gswin64c"];
+
+        let found = resolve_ghostscript_with(&candidates, |name| match name {
+            "ghostscript" | "gswin64c" => Some(PathBuf::from("/bin").join(name)),
+            _ => None,
+        });
+        assert_eq!(found.unwrap(), Path::new("/bin/ghostscript"));
+
+        let found = resolve_ghostscript_with(&candidates, |name| Some(name.into()));
+        assert_eq!(found.unwrap(), Path::new("gs"));
+
+        match resolve_ghostscript_with(&candidates, |_| None) {
+            Err(ShrinkError::GhostscriptNotFound { tried }) => assert_eq!(tried, candidates),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_which() {
+        assert_eq!(which("pdfshrink-surely-missing-program"), None);
+        assert_eq!(which("/pdfshrink/surely/missing/program"), None);
+    }
+
+    #[test]
+    fn test_newest_installed() {
+        let dir = crate::testing::TempDir::new();
+        let exes = ["gswin64c.exe", "gswin32c.exe"];
+        assert_eq!(newest_installed(dir.path(), &exes), None);
+        for (version, exe) in &[
+            ("gs9.56.1", "gswin64c.exe"),
+            ("gs10.02.1", "gswin32c.exe"),
+            ("gs10.03.0", "README"),
+        ] {
+            let bin = dir.path().join("gs").join(version).join("bin");
+            std::fs::create_dir_all(&bin).unwrap();
+            std::fs::write(bin.join(exe), b"").unwrap();
+        }
+        // 10.03.0 has no executable, 10.02.1 is newer than 9.56.1
+        assert_eq!(
+            newest_installed(dir.path(), &exes).unwrap(),
+            dir.path().join("gs/gs10.02.1/bin/gswin32c.exe")
+        );
+        assert_eq!(
+            newest_installed(dir.path(), &exes[..1]).unwrap(),
+            dir.path().join("gs/gs9.56.1/bin/gswin64c.exe")
+        );
+    }
+
+    #[test]
+    fn test_ghostscript_version() {
+        use crate::testing::MockRunner;
+        use crate::CommandOutput;
+
+        let runner = MockRunner::new(|_| {
+            Ok(CommandOutput {
+                code: Some(0),
+                stdout: b"10.02.1\n".to_vec(),
+                ..Default::default()
+            })
+        });
+        assert_eq!(ghostscript_version_with(&runner, "gs").unwrap(), (10, 2));
+        assert_eq!(runner.calls(), [["gs", "--version"]]);
+
+        let runner = MockRunner::new(|_| {
+            Ok(CommandOutput {
+                code: Some(0),
+                stdout: b"GPL Ghostscript\n".to_vec(),
+                ..Default::default()
+            })
+        });
+        assert!(matches!(
+            ghostscript_version_with(&runner, "gs"),
+            Err(ShrinkError::InvalidVersion { output }) if output == "GPL Ghostscript"
+        ));
+
+        let runner = MockRunner::failing(1, "unknown switch");
+        assert!(matches!(
+            ghostscript_version_with(&runner, "gs"),
+            Err(ShrinkError::NonZeroExit { code: Some(1), .. })
+        ));
+        let missing = ghostscript_version_with(&SystemRunner, "/pdfshrink/surely/missing/gs");
+        assert!(matches!(missing, Err(ShrinkError::SpawnFailed { .. })));
+    }
+
+    #[test]
+    fn test_require_ghostscript() {
+        let found = require_ghostscript(|| Ok("/usr/bin/gs".into()));
+        assert_eq!(found.unwrap(), Path::new("/usr/bin/gs"));
+
+        let missing = require_ghostscript(|| resolve_ghostscript_with(&["gs"], |_| None));
+        let message = missing.unwrap_err();
+        assert!(message.starts_with("Ghostscript not found (tried gs)\n"));
+        assert!(message.ends_with(INSTALL_GUIDANCE));
+    }
+}
@@ -0,0 +1,167 @@
+//! User supplied commands run around the shrinking.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+/// Splits a command line into words.
+///
+/// Words are separated by whitespace; single quotes, double quotes and
+/// backslashes group and escape characters like in a POSIX shell.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::split_words;
+/// assert_eq!(
+///     split_words(r#"qpdf --check "my file.pdf" it\'s"#),
+///     Ok(vec!["qpdf".into(), "--check".into(), "my file.pdf".into(), "it's".into()])
+/// );
+/// assert!(split_words("'unterminated").is_err());
+/// ```
+pub fn split_words(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated quote in {:?}", s)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ '"') | Some(c @ '\\') => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("unterminated quote in {:?}", s)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated quote in {:?}", s)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash in {:?}", s)),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Command template run on a file, such as `ocrmypdf {output} {output}`.
+///
+/// Every occurrence of the placeholder is replaced by the path of the file;
+/// if the template contains no placeholder, the path is appended as the last argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    words: Vec<String>,
+}
+
+impl Hook {
+    /// Parses a command template, see [`split_words`].
+    pub fn parse(template: &str) -> Result<Hook, String> {
+        let words = split_words(template)?;
+        if words.is_empty() {
+            Err(String::from("empty command"))
+        } else {
+            Ok(Hook { words })
+        }
+    }
+
+    /// The command template, as a single line.
+    pub fn template(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// Command running the hook on `path`, substituting `placeholder`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::Hook;
+    /// let hook = Hook::parse("qpdf --check").unwrap();
+    /// let cmd = hook.command("{output}", "out.pdf");
+    /// assert_eq!(cmd.get_program(), "qpdf");
+    /// assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["--check", "out.pdf"]);
+    /// ```
+    pub fn command<P>(&self, placeholder: &str, path: P) -> Command
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut argv: Vec<OsString> = self
+            .words
+            .iter()
+            .map(|word| {
+                if word == placeholder {
+                    path.as_os_str().to_os_string()
+                } else {
+                    word.replace(placeholder, &path.to_string_lossy()).into()
+                }
+            })
+            .collect();
+        if !self.words.iter().any(|word| word.contains(placeholder)) {
+            argv.push(path.into());
+        }
+        let mut cmd = Command::new(&argv[0]);
+        cmd.args(&argv[1..]);
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(cmd: &Command) -> Vec<&std::ffi::OsStr> {
+        std::iter::once(cmd.get_program())
+            .chain(cmd.get_args())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words(""), Ok(vec![]));
+        assert_eq!(split_words("  a  b "), Ok(vec!["a".into(), "b".into()]));
+        assert_eq!(split_words("''"), Ok(vec!["".into()]));
+        assert_eq!(split_words(r#"a"b c"d"#), Ok(vec!["ab cd".into()]));
+        assert_eq!(split_words(r#""\"\\\x""#), Ok(vec![r#""\\x"#.into()]));
+        assert_eq!(split_words(r"a\ b"), Ok(vec!["a b".into()]));
+        assert!(split_words("\"open").is_err());
+        assert!(split_words("a\\").is_err());
+    }
+
+    #[test]
+    fn test_hook_command() {
+        let hook = Hook::parse("ocrmypdf {output} {output}").unwrap();
+        let cmd = hook.command("{output}", "dir/a b.pdf");
+        assert_eq!(argv(&cmd), ["ocrmypdf", "dir/a b.pdf", "dir/a b.pdf"]);
+
+        let hook = Hook::parse("cp {output} {output}.bak").unwrap();
+        let cmd = hook.command("{output}", "a.pdf");
+        assert_eq!(argv(&cmd), ["cp", "a.pdf", "a.pdf.bak"]);
+
+        let hook = Hook::parse("qpdf --check").unwrap();
+        let cmd = hook.command("{output}", "a.pdf");
+        assert_eq!(argv(&cmd), ["qpdf", "--check", "a.pdf"]);
+
+        assert!(Hook::parse("   ").is_err());
+    }
+}
@@ -5,13 +5,102 @@
 //! to reduce the file size.
 //!
 //! Ghostscript need to be already installed on your system.
+//!
+//! # Thread safety
+//!
+//! The options, outcomes and errors of this crate ([`ShrinkOptions`],
+//! [`ShrinkOutcome`], [`ShrinkReport`], [`ShrinkError`], ...) are `Send` and `Sync`,
+//! so that a single configuration can be shared by several threads shrinking
+//! different files at the same time.
 
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
 #[cfg(feature = "logging")]
 use log::trace;
 
+mod batch;
+mod checkpoint;
+#[cfg(feature = "checksum")]
+mod checksum;
+mod doctor;
+mod error;
+mod explain;
+mod ghostscript;
+mod hook;
+mod marker;
+mod options;
+mod parse;
+mod pattern;
+mod plan;
+mod preset;
+mod probe;
+mod runner;
+mod shrink;
+#[cfg(feature = "sidecar")]
+mod sidecar;
+mod summary;
+mod walk;
+mod watermark;
+
+#[cfg(test)]
+mod testing;
+
+pub use batch::{
+    shrink_all, shrink_all_with, shrink_batch, shrink_batch_with, CancellableRunner,
+    CancellationToken,
+};
+pub use checkpoint::Checkpoint;
+#[cfg(feature = "checksum")]
+pub use checksum::{checksum_path, store_by_content, write_checksum, ChecksumAlgorithm};
+pub use doctor::{doctor_report, probe_environment, Environment, RELEVANT_DEVICES};
+pub use error::ShrinkError;
+pub use explain::{diff_images, ImageChange, ImageChanges, ImageDiff};
+pub use ghostscript::{
+    find_ghostscript, ghostscript_version, ghostscript_version_with, parse_ghostscript_version,
+    require_ghostscript, resolve_ghostscript, resolve_ghostscript_with, which,
+    GHOSTSCRIPT_CANDIDATES, INSTALL_GUIDANCE,
+};
+pub use hook::{split_words, Hook};
+pub use marker::{
+    marker_pdfmark, read_marker, read_settings_hash, settings_hash, MARKER_KEY, SETTINGS_HASH_KEY,
+};
+pub use options::{
+    AutoRotate, PdfSettings, ResolutionConflict, ShrinkOptions, COMPATIBILITY_LEVELS,
+    STRIPPED_METADATA,
+};
+pub use parse::{
+    parse_dpi, parse_dpi_table, parse_dpi_table_over, parse_duration, parse_mode, parse_size,
+};
+pub use pattern::expand_braces;
+#[cfg(feature = "patterns")]
+pub use pattern::{glob_inputs, glob_inputs_with, EmptyGlob};
+pub use plan::{sample, sort_inputs, Order, Plan, PlannedJob};
+pub use preset::Presets;
+pub use probe::{
+    dpi_stats, image_probe_command, last_page_for_percent, page_count, page_count_command,
+    page_count_with, parse_image_list, probe_images, probe_images_with, ps_string,
+    respect_scan_dpi, scaled_dpi, scan_dpi, DpiStats, ImageInfo,
+};
+pub use runner::{
+    command_line_string, command_to_shell_string, CommandOutput, Runner, SystemRunner,
+};
+pub use shrink::{
+    check_skip, classify_exit, ensure_parent_dir, ensure_parent_dir_like, in_place_temp_path,
+    in_place_temp_path_with, is_pdf, shrink_file, shrink_file_with, shrink_in_place,
+    shrink_in_place_with, shrink_open_file, shrink_open_file_with, ExitClass, ShrinkOutcome,
+    ShrinkReport, SkipReason, DEFAULT_TEMP_SUFFIX,
+};
+#[cfg(feature = "sidecar")]
+pub use sidecar::{sidecar_path, with_sidecar, SIDECAR_EXTENSION};
+pub use summary::{
+    format_bytes, histogram_bucket, BatchSummary, SummaryFormat, HISTOGRAM_LABELS,
+    SUMMARY_CSV_HEADER,
+};
+pub use walk::collect_pdfs;
+pub use watermark::Watermark;
+
 /// Replaces a `.pdf` extension with `.cmp.pdf`.
 ///
 /// If there is no extension, or the extension is not `.pdf`, returns `None`.
@@ -74,6 +163,94 @@ where
     result
 }
 
+/// Collapses a repeated `.pdf` extension, turning `name.pdf.pdf` into `name.pdf`.
+///
+/// Other paths are returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::normalize_pdf_stem;
+/// assert_eq!(normalize_pdf_stem("dir/name.pdf.pdf"), std::path::Path::new("dir/name.pdf"));
+/// assert_eq!(normalize_pdf_stem("dir/name.tar.pdf"), std::path::Path::new("dir/name.tar.pdf"));
+/// ```
+pub fn normalize_pdf_stem<P>(path: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut path = path.as_ref().to_path_buf();
+    while path.extension() == Some("pdf".as_ref())
+        && Path::new(path.file_stem().unwrap_or_default()).extension() == Some("pdf".as_ref())
+    {
+        path.set_extension("");
+    }
+    path
+}
+
+/// Detects a `suffix` ending with the `pdf` extension, such as `shrunk.pdf`,
+/// which would give outputs named like `name.shrunk.pdf.pdf`.
+///
+/// Returns the suffix without the redundant extension, or `None` if it is not redundant.
+/// The result is empty if the suffix is just `pdf` or `.pdf`.
+///
+/// [`pdf_with_suffix`] and [`pdf_with_dash_suffix`] use the suffix literally,
+/// so it is up to the caller to decide what to do with a redundant one.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::redundant_pdf_suffix;
+/// assert_eq!(redundant_pdf_suffix("shrunk.pdf"), Some("shrunk"));
+/// assert_eq!(redundant_pdf_suffix("shrunk"), None);
+/// ```
+pub fn redundant_pdf_suffix(suffix: &str) -> Option<&str> {
+    if suffix == "pdf" {
+        Some("")
+    } else {
+        suffix.strip_suffix(".pdf")
+    }
+}
+
+/// Appends `-<suffix>` to the stem of a file with a `.pdf` extension.
+///
+/// This is the dash-joined alternative to [`pdf_with_suffix`].
+/// If there is no extension, or the extension is not `.pdf`, returns `None`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::pdf_with_dash_suffix;
+/// let before = "some dir/subdir/name.pdf";
+/// let after = "some dir/subdir/name-shrunk.pdf";
+/// assert_eq!(pdf_with_dash_suffix(before, "shrunk"), Some(after.into()));
+/// ```
+pub fn pdf_with_dash_suffix<P, Q>(inpath: P, suffix: Q) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+    Q: AsRef<std::ffi::OsStr>,
+{
+    let inpath = inpath.as_ref();
+    let suffix = suffix.as_ref();
+    let result = match (inpath.file_stem(), inpath.extension()) {
+        (Some(stem), Some(ext)) if ext == "pdf" => {
+            let mut name = stem.to_os_string();
+            name.push("-");
+            name.push(suffix);
+            name.push(".pdf");
+            Some(inpath.with_file_name(name))
+        }
+        _ => None,
+    };
+    #[cfg(feature = "logging")]
+    trace!(
+        "pdf_with_dash_suffix({:?}, {:?}) = {:?}",
+        inpath,
+        suffix,
+        result
+    );
+    result
+}
+
 /// Moves the file `inpath` into the subdirectory `subdir`.
 ///
 /// If there is no extension, or the extension is not `.pdf`, returns `None`.
@@ -139,9 +316,32 @@ where
     result
 }
 
+/// Whether `path` is not valid Unicode, so that converting it to a string loses characters.
+///
+/// The paths are passed to Ghostscript unchanged, with the wide API on Windows,
+/// but Ghostscript handles the file names as UTF-8 internally: such a file
+/// (on Windows, a name containing an unpaired surrogate) may not be processed correctly.
+/// The same happens to a path substituted inside a word of a [`Hook`].
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::path_is_lossy;
+/// assert!(!path_is_lossy("dir/ünïcödé.pdf"));
+/// ```
+pub fn path_is_lossy<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref().to_str().is_none()
+}
+
 /// Ghostscript command to shrink `inpath` and write to `outpath`.
 ///
 /// This command requires Ghostscript installed as a program `gs`.
+///
+/// It is equivalent to [`ShrinkOptions::build_command`] with the default options,
+/// and to [`gs_command_with_settings`] with [`PdfSettings::Ebook`].
 pub fn gs_command<P, Q>(inpath: P, outpath: Q) -> Command
 where
     P: AsRef<Path>,
@@ -149,34 +349,153 @@ where
 {
     #[cfg(feature = "logging")]
     trace!("gs_command({:?}, {:?})", inpath.as_ref(), outpath.as_ref());
-    let mut cmd = Command::new("gs");
-    cmd.args(
-        [
-            "-q",
-            "-dBATCH",
-            "-dSAFER",
-            "-dNOPAUSE",
-            "-sDEVICE=pdfwrite",
-            "-dCompatibilityLevel=1.4",
-            "-dPDFSETTINGS=/ebook",
-            "-dAutoRotatePages=/None",
-            "-dColorImageDownsampleType=/Bicubic",
-            "-dColorImageResolution=135",
-            "-dGrayImageDownsampleType=/Bicubic",
-            "-dGrayImageResolution=135",
-            "-dMonoImageDownsampleType=/Bicubic",
-            "-dMonoImageResolution=135",
-        ]
-        .iter(),
-    )
-    .arg(format!(
-        "-sOutputFile={}",
-        outpath.as_ref().to_string_lossy().to_string()
-    ))
-    .arg(inpath.as_ref().to_string_lossy().to_string());
+    gs_command_with_settings(inpath, outpath, PdfSettings::Ebook)
+}
+
+/// Like [`gs_command`], but with the given predefined `settings`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{gs_command_with_settings, PdfSettings};
+/// let cmd = gs_command_with_settings("in.pdf", "out.pdf", PdfSettings::Screen);
+/// assert!(cmd.get_args().any(|arg| arg == "-dPDFSETTINGS=/screen"));
+/// ```
+pub fn gs_command_with_settings<P, Q>(inpath: P, outpath: Q, settings: PdfSettings) -> Command
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let options = ShrinkOptions {
+        pdf_settings: settings,
+        ..Default::default()
+    };
+    let (program, args) = gs_argv(inpath, outpath, &options);
+    let mut cmd = Command::new(program);
+    cmd.args(args);
     cmd
 }
 
+/// Like [`gs_command`], but running the Ghostscript executable `binary`
+/// instead of the program `gs`.
+///
+/// `binary` can be a path, such as `/opt/gs/bin/gs`, or a name searched in the `PATH`,
+/// such as `gswin64c`; see [`resolve_ghostscript`] to find it automatically.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::gs_command_with_binary;
+/// let cmd = gs_command_with_binary("/opt/gs/bin/gs", "in.pdf", "out.pdf");
+/// assert_eq!(cmd.get_program(), "/opt/gs/bin/gs");
+/// ```
+pub fn gs_command_with_binary<B, P, Q>(binary: B, inpath: P, outpath: Q) -> Command
+where
+    B: AsRef<Path>,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    ShrinkOptions::default()
+        .ghostscript(binary.as_ref())
+        .build_command(inpath, outpath)
+}
+
+/// Highest image resolution accepted by [`gs_command_with_resolutions`], in dots per inch.
+pub const MAX_RESOLUTION: u32 = 10_000;
+
+/// Like [`gs_command`], but downsampling all the images to `dpi` dots per inch.
+///
+/// Returns an error if `dpi` is zero or larger than [`MAX_RESOLUTION`].
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::gs_command_with_resolution;
+/// let cmd = gs_command_with_resolution("in.pdf", "out.pdf", 300).unwrap();
+/// assert!(cmd.get_args().any(|arg| arg == "-dMonoImageResolution=300"));
+/// assert!(gs_command_with_resolution("in.pdf", "out.pdf", 0).is_err());
+/// ```
+pub fn gs_command_with_resolution<P, Q>(inpath: P, outpath: Q, dpi: u32) -> Result<Command, String>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    gs_command_with_resolutions(inpath, outpath, dpi, dpi, dpi)
+}
+
+/// Like [`gs_command`], but downsampling the color, gray and mono images
+/// to different resolutions, in dots per inch.
+///
+/// To override only some of them, set the fields of [`ShrinkOptions`] and use
+/// [`ShrinkOptions::build_command`].
+///
+/// Returns an error if a resolution is zero or larger than [`MAX_RESOLUTION`].
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::gs_command_with_resolutions;
+/// // color photos at 100 dpi, text scanned in black and white at 300 dpi
+/// let cmd = gs_command_with_resolutions("in.pdf", "out.pdf", 100, 100, 300).unwrap();
+/// assert!(cmd.get_args().any(|arg| arg == "-dColorImageResolution=100"));
+/// assert!(cmd.get_args().any(|arg| arg == "-dMonoImageResolution=300"));
+/// ```
+pub fn gs_command_with_resolutions<P, Q>(
+    inpath: P,
+    outpath: Q,
+    color_dpi: u32,
+    gray_dpi: u32,
+    mono_dpi: u32,
+) -> Result<Command, String>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    for &(kind, dpi) in &[("color", color_dpi), ("gray", gray_dpi), ("mono", mono_dpi)] {
+        if dpi == 0 || dpi > MAX_RESOLUTION {
+            return Err(format!(
+                "invalid {} resolution {} dpi, expected 1 to {}",
+                kind, dpi, MAX_RESOLUTION
+            ));
+        }
+    }
+    let options = ShrinkOptions {
+        color_resolution: color_dpi,
+        gray_resolution: gray_dpi,
+        mono_resolution: mono_dpi,
+        ..Default::default()
+    };
+    Ok(options.build_command(inpath, outpath))
+}
+
+/// Program and arguments of the Ghostscript command shrinking `inpath` into `outpath`.
+///
+/// This is the command of [`ShrinkOptions::build_command`], for executors which
+/// do not spawn a [`Command`] themselves, such as job schedulers.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{gs_argv, ShrinkOptions};
+/// let (program, args) = gs_argv("in.pdf", "out.pdf", &ShrinkOptions::default());
+/// assert_eq!(program, "gs");
+/// assert_eq!(args.last().unwrap(), "in.pdf");
+/// ```
+pub fn gs_argv<P, Q>(inpath: P, outpath: Q, options: &ShrinkOptions) -> (OsString, Vec<OsString>)
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let program = options
+        .ghostscript
+        .as_deref()
+        .unwrap_or_else(|| "gs".as_ref());
+    (
+        program.as_os_str().to_os_string(),
+        options.to_gs_args(inpath, outpath),
+    )
+}
+
 /// Command to simulate [`gs_command`].
 ///
 /// Please see its documentation to know what it should do.
@@ -190,15 +509,113 @@ where
     P: AsRef<Path>,
     Q: AsRef<Path>,
 {
-    #[cfg(target_os = "windows")]
+    #[cfg(feature = "logging")]
     trace!(
         "dry_run_command({:?}, {:?})",
         inpath.as_ref(),
         outpath.as_ref()
     );
-    let mut cmd = Command::new("args");
-    cmd.args(
-        [
+    ShrinkOptions::default().build_dry_run_command(inpath, outpath)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<ShrinkOptions>();
+        assert_send_sync::<Hook>();
+        assert_send_sync::<Watermark>();
+        assert_send_sync::<ShrinkOutcome>();
+        assert_send_sync::<ShrinkReport>();
+        assert_send_sync::<ExitClass>();
+        assert_send_sync::<SkipReason>();
+        assert_send_sync::<ShrinkError>();
+        assert_send_sync::<CommandOutput>();
+        assert_send_sync::<SystemRunner>();
+        assert_send_sync::<Environment>();
+        assert_send_sync::<Plan>();
+        assert_send_sync::<BatchSummary>();
+        assert_send_sync::<CancellationToken>();
+        assert_send_sync::<Presets>();
+        assert_send_sync::<PdfSettings>();
+        assert_send_sync::<CancellableRunner>();
+    }
+
+    #[test]
+    fn test_gs_command_with_resolution() {
+        let cmd = gs_command_with_resolution("in.pdf", "out.pdf", 600).unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        for kind in &["Color", "Gray", "Mono"] {
+            let arg = format!("-d{}ImageResolution=600", kind);
+            assert!(args.contains(&arg.as_ref()), "{}", arg);
+        }
+        assert_eq!(cmd.get_program(), "gs");
+        assert!(gs_command_with_resolution("in.pdf", "out.pdf", 0).is_err());
+        assert!(gs_command_with_resolution("in.pdf", "out.pdf", MAX_RESOLUTION).is_ok());
+        assert!(gs_command_with_resolution("in.pdf", "out.pdf", MAX_RESOLUTION + 1).is_err());
+    }
+
+    #[test]
+    fn test_path_is_lossy() {
+        assert!(!path_is_lossy("name.pdf"));
+        assert!(!path_is_lossy("日本語/ファイル.pdf"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_path_is_lossy_windows() {
+        use std::os::windows::ffi::OsStringExt;
+        // an unpaired surrogate, which NTFS allows in file names
+        let name = std::ffi::OsString::from_wide(&[0x61, 0xD800, 0x2E, 0x70, 0x64, 0x66]);
+        assert!(path_is_lossy(&name));
+        assert!(path_is_lossy(Path::new(r"C:\docs").join(&name)));
+        let name = std::ffi::OsString::from_wide(&[0xD83D, 0xDE00, 0x2E, 0x70, 0x64, 0x66]);
+        assert!(!path_is_lossy(name));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_is_lossy_unix() {
+        use std::os::unix::ffi::OsStrExt;
+        assert!(path_is_lossy(std::ffi::OsStr::from_bytes(b"caf\xe9.pdf")));
+    }
+
+    #[test]
+    fn test_gs_command_with_binary() {
+        let cmd = gs_command_with_binary("gswin64c", "in.pdf", "out.pdf");
+        assert_eq!(cmd.get_program(), "gswin64c");
+        assert!(cmd
+            .get_args()
+            .eq(gs_command("in.pdf", "out.pdf").get_args()));
+    }
+
+    #[test]
+    fn test_gs_command_with_resolutions() {
+        let cmd = gs_command_with_resolutions("in.pdf", "out.pdf", 100, 150, 300).unwrap();
+        let args: Vec<_> = cmd.get_args().collect();
+        for arg in &[
+            "-dColorImageResolution=100",
+            "-dGrayImageResolution=150",
+            "-dMonoImageResolution=300",
+        ] {
+            assert!(args.contains(&arg.as_ref()), "{}", arg);
+        }
+        let err = gs_command_with_resolutions("in.pdf", "out.pdf", 100, 0, 300).unwrap_err();
+        assert!(err.contains("gray"), "{}", err);
+        assert!(
+            gs_command_with_resolutions("in.pdf", "out.pdf", 100, 100, MAX_RESOLUTION + 1).is_err()
+        );
+    }
+
+    #[test]
+    fn test_gs_argv() {
+        let (program, args) = gs_argv("in.pdf", "out.pdf", &ShrinkOptions::default());
+        assert_eq!(program, "gs");
+        let expected = [
             "-q",
             "-dBATCH",
             "-dSAFER",
@@ -213,20 +630,21 @@ where
             "-dGrayImageResolution=135",
             "-dMonoImageDownsampleType=/Bicubic",
             "-dMonoImageResolution=135",
-        ]
-        .iter(),
-    )
-    .arg(format!(
-        "-sOutputFile={}",
-        outpath.as_ref().to_string_lossy().to_string()
-    ))
-    .arg(inpath.as_ref().to_string_lossy().to_string());
-    cmd
-}
+            "-sOutputFile=out.pdf",
+            "in.pdf",
+        ];
+        assert_eq!(args, expected);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let cmd = gs_command("in.pdf", "out.pdf");
+        assert_eq!(cmd.get_program(), program);
+        assert!(cmd.get_args().eq(&args));
+
+        let options = ShrinkOptions {
+            ghostscript: Some("/opt/gs/bin/gs".into()),
+            ..Default::default()
+        };
+        assert_eq!(gs_argv("in.pdf", "out.pdf", &options).0, "/opt/gs/bin/gs");
+    }
 
     #[test]
     fn test_pdf_to_cmp_pdf() {
@@ -287,6 +705,89 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normalize_pdf_stem() {
+        for &(before, after) in &[
+            ("name.pdf", "name.pdf"),
+            ("dir/name.pdf", "dir/name.pdf"),
+            ("name.pdf.pdf", "name.pdf"),
+            ("dir.pdf/name.pdf.pdf", "dir.pdf/name.pdf"),
+            ("name.pdf.pdf.pdf", "name.pdf"),
+            ("name.tar.pdf", "name.tar.pdf"),
+            ("name.pdf.txt", "name.pdf.txt"),
+            ("name.txt.txt", "name.txt.txt"),
+            ("name", "name"),
+            (".pdf.pdf", ".pdf.pdf"), // the stem .pdf is a hidden name
+        ] {
+            assert_eq!(normalize_pdf_stem(before), Path::new(after), "{}", before);
+        }
+        assert_eq!(
+            pdf_with_suffix(normalize_pdf_stem("name.pdf.pdf"), "shrunk"),
+            Some("name.shrunk.pdf".into())
+        );
+    }
+
+    #[test]
+    fn test_redundant_pdf_suffix() {
+        assert_eq!(redundant_pdf_suffix("pdf"), Some(""));
+        assert_eq!(redundant_pdf_suffix(".pdf"), Some(""));
+        assert_eq!(redundant_pdf_suffix("shrunk.pdf"), Some("shrunk"));
+        assert_eq!(redundant_pdf_suffix("a.b.pdf"), Some("a.b"));
+        for s in &["shrunk", "pdfs", "shrunkpdf", "pdf.shrunk", ""] {
+            assert_eq!(redundant_pdf_suffix(s), None, "{:?}", s);
+        }
+        // used literally by the path helpers
+        assert_eq!(
+            pdf_with_suffix("a.pdf", "shrunk.pdf"),
+            Some("a.shrunk.pdf.pdf".into())
+        );
+    }
+
+    #[test]
+    fn test_suffix_forms() {
+        type F = fn(&str, &str) -> Option<PathBuf>;
+        let dotted: F = |p, s| pdf_with_suffix(p, s);
+        let dashed: F = |p, s| pdf_with_dash_suffix(p, s);
+        let table: &[(F, &str, &str, Option<&str>)] = &[
+            (dotted, "name.pdf", "shrunk", Some("name.shrunk.pdf")),
+            (dashed, "name.pdf", "shrunk", Some("name-shrunk.pdf")),
+            (dotted, "dir/a b.pdf", "small", Some("dir/a b.small.pdf")),
+            (dashed, "dir/a b.pdf", "small", Some("dir/a b-small.pdf")),
+            (
+                dotted,
+                "dotted.name.pdf",
+                "shrunk",
+                Some("dotted.name.shrunk.pdf"),
+            ),
+            (
+                dashed,
+                "dotted.name.pdf",
+                "shrunk",
+                Some("dotted.name-shrunk.pdf"),
+            ),
+            (
+                dotted,
+                "../.hidden.pdf",
+                "shrunk",
+                Some("../.hidden.shrunk.pdf"),
+            ),
+            (
+                dashed,
+                "../.hidden.pdf",
+                "shrunk",
+                Some("../.hidden-shrunk.pdf"),
+            ),
+            (dotted, ".pdf", "shrunk", None),
+            (dashed, ".pdf", "shrunk", None),
+            (dotted, "name.ext", "shrunk", None),
+            (dashed, "name.ext", "shrunk", None),
+            (dashed, "name", "shrunk", None),
+        ];
+        for &(f, before, suffix, after) in table {
+            assert_eq!(f(before, suffix), after.map(PathBuf::from), "{}", before);
+        }
+    }
+
     #[test]
     fn test_pdf_into_subdir() {
         use pdf_into_subdir as f;
@@ -1,25 +1,37 @@
 #[macro_use]
 extern crate clap;
 use clap::{AppSettings, Arg, ArgGroup};
+use std::ffi::OsString;
+use std::time::{Duration, Instant};
 
 use pdfshrink::*;
 
-use log::{debug, info, warn};
+use log::{debug, error, info, warn};
 
 fn main() {
     let app = app_from_crate!()
         .setting(AppSettings::UnifiedHelpMessage)
         .setting(AppSettings::ColoredHelp)
         .help_message("Print help information") // Imperative form
-        .version_message("Print version information") // Imperative form
-        .after_help("The options --inplace, --rename and --subdir are mutually exclusive.")
+        .setting(AppSettings::DisableVersion)
+        .after_help(
+            "The options --inplace, --rename and --subdir are mutually exclusive.\n\
+             An argument @FILE is replaced with the arguments read from FILE.\n\
+             An INPUT which does not exist is expanded like a shell pattern, e.g. 'scans/{a,b}*.pdf'.",
+        )
         .arg(
             Arg::with_name("input")
                 .multiple(true)
-                .required(true)
+                .required_unless_one(&["doctor", "run-plan", "input-list-from-stdin", "version"])
                 .value_name("INPUT")
                 .help("Input PDF files to shrink"),
         )
+        .arg(
+            Arg::with_name("version")
+                .long("version")
+                .short("V")
+                .help("Print version information, including the one of Ghostscript"),
+        )
         .arg(
             Arg::with_name("verbose")
                 .long("verbose")
@@ -31,14 +43,64 @@ fn main() {
             Arg::with_name("inplace")
                 .long("inplace")
                 .short("i")
+                .conflicts_with_all(&["save-plan", "compare"])
                 .help("Replace the original file"),
         )
+        .arg(
+            Arg::with_name("temp-suffix")
+                .long("temp-suffix")
+                .value_name("STR")
+                .requires("inplace")
+                .validator(validate_suffix)
+                .help("Name the temporary files of --inplace .NAME.pdfSTR [default: .pdfshrink.tmp]"),
+        )
+        .arg(
+            Arg::with_name("no-follow-input-symlinks")
+                .long("no-follow-input-symlinks")
+                .requires("inplace")
+                .help("Skip the inputs which are symbolic links, instead of replacing their targets"),
+        )
+        .arg(
+            Arg::with_name("always-replace")
+                .long("always-replace")
+                .requires("inplace")
+                .help("Replace the original file even if the shrunk one is larger"),
+        )
         .arg(
             Arg::with_name("rename")
                 .long("rename")
                 .short("r")
                 .help("Save the output to a renamed file: *.pdf -> *.shrunk.pdf (defaut)"),
         )
+        .arg(
+            Arg::with_name("suffix")
+                .long("suffix")
+                .value_name("SUFFIX")
+                .validator(validate_suffix)
+                .help("Suffix of the renamed outputs (default: shrunk)"),
+        )
+        .arg(
+            Arg::with_name("keep-pdf-suffix")
+                .long("keep-pdf-suffix")
+                .requires("suffix")
+                .help("Use a suffix ending with .pdf as is, instead of removing the repetition"),
+        )
+        .arg(
+            Arg::with_name("collapse-double-ext")
+                .long("collapse-double-ext")
+                .help("Name the output of name.pdf.pdf as if the input were name.pdf"),
+        )
+        .arg(
+            Arg::with_name("out-suffix-as-ext")
+                .long("out-suffix-as-ext")
+                .help("Join the suffix to the extension: *.pdf -> *.shrunk.pdf (default)"),
+        )
+        .arg(
+            Arg::with_name("out-suffix-before-ext")
+                .long("out-suffix-before-ext")
+                .conflicts_with("out-suffix-as-ext")
+                .help("Join the suffix to the stem with a dash: *.pdf -> *-shrunk.pdf"),
+        )
         .arg(
             Arg::with_name("subdir")
                 .long("subdir")
@@ -46,27 +108,770 @@ fn main() {
                 .value_name("SUBDIR")
                 .help("Save the output in a subdirectory"),
         )
+        .arg(
+            Arg::with_name("first-percent")
+                .long("first-percent")
+                .value_name("P")
+                .validator(|p| match p.parse::<f64>() {
+                    Ok(p) if p > 0.0 && p <= 100.0 => Ok(()),
+                    _ => Err(String::from("must be a percentage in (0, 100]")),
+                })
+                .help("Only shrink the first P% of the pages"),
+        )
+        .arg(
+            Arg::with_name("dpi-scale")
+                .long("dpi-scale")
+                .value_name("FACTOR")
+                .validator(|f| match f.parse::<f64>() {
+                    Ok(f) if f > 0.0 => Ok(()),
+                    _ => Err(String::from("must be a positive number")),
+                })
+                .help("Downsample the images to FACTOR times their median resolution (requires pdfimages)"),
+        )
+        .arg(
+            Arg::with_name("min-dpi")
+                .long("min-dpi")
+                .value_name("DPI")
+                .requires("dpi-scale")
+                .validator(|dpi| parse_dpi(&dpi).map(drop))
+                .help("Lower bound of the resolution computed by --dpi-scale (default: 72)"),
+        )
+        .arg(
+            Arg::with_name("quality")
+                .long("quality")
+                .value_name("SETTINGS")
+                .possible_values(&["screen", "ebook", "printer", "prepress", "default"])
+                .help("Predefined Ghostscript settings refined by the other options (default: ebook)"),
+        )
+        .arg(
+            Arg::with_name("pdf-version")
+                .long("pdf-version")
+                .value_name("VERSION")
+                .possible_values(&COMPATIBILITY_LEVELS)
+                .help("Version of the PDF format of the outputs (default: 1.4)"),
+        )
+        .arg(
+            Arg::with_name("preset")
+                .long("preset")
+                .value_name("NAME")
+                .conflicts_with("dpi-scale")
+                .help("Start from the options for a use: email, web, archive, print or from --preset-file"),
+        )
+        .arg(
+            Arg::with_name("preset-file")
+                .long("preset-file")
+                .value_name("FILE")
+                .requires("preset")
+                .help("Read more presets from the TOML file FILE"),
+        )
+        .arg(
+            Arg::with_name("dpi")
+                .long("dpi")
+                .value_name("TABLE")
+                .conflicts_with("dpi-scale")
+                .validator(|table| parse_dpi_table(&table).map(drop))
+                .help("Resolution of the images, e.g. 150dpi or by type color=150,gray=150,mono=300"),
+        )
+        .arg(
+            Arg::with_name("no-downsample-color")
+                .long("no-downsample-color")
+                .help("Keep the resolution of the color images"),
+        )
+        .arg(
+            Arg::with_name("no-downsample-gray")
+                .long("no-downsample-gray")
+                .help("Keep the resolution of the grayscale images"),
+        )
+        .arg(
+            Arg::with_name("no-downsample-mono")
+                .long("no-downsample-mono")
+                .help("Keep the resolution of the monochrome images, such as scanned text"),
+        )
+        .arg(
+            Arg::with_name("respect-scan-dpi")
+                .long("respect-scan-dpi")
+                .help("Never downsample scanned monochrome images below their scan resolution"),
+        )
+        .arg(
+            Arg::with_name("device-dpi")
+                .long("device-dpi")
+                .value_name("DPI")
+                .validator(|dpi| parse_dpi(&dpi).map(drop))
+                .help("Rendering resolution of the output device (not of the images)"),
+        )
+        .arg(
+            Arg::with_name("flatten-forms")
+                .long("flatten-forms")
+                .help("Render the form fields into the pages so they can no longer be edited"),
+        )
+        .arg(
+            Arg::with_name("grayscale")
+                .long("grayscale")
+                .help("Convert all the colors to gray, irreversibly"),
+        )
+        .arg(
+            Arg::with_name("fast-web-view")
+                .long("fast-web-view")
+                .help("Linearize the output, so that browsers show it while downloading"),
+        )
+        .arg(
+            Arg::with_name("strip-metadata")
+                .long("strip-metadata")
+                .help("Clear the title, author and other metadata of the document"),
+        )
+        .arg(
+            Arg::with_name("drop-bookmarks")
+                .long("drop-bookmarks")
+                .help("Remove the document outline (bookmarks)"),
+        )
+        .arg(
+            Arg::with_name("no-new-pdf")
+                .long("no-new-pdf")
+                .help("Read the inputs with the legacy PDF interpreter of Ghostscript (before 10.02)"),
+        )
+        .arg(
+            Arg::with_name("include-path")
+                .long("include-path")
+                .value_name("DIR")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Add DIR to the Ghostscript resource search path (repeatable)"),
+        )
+        .arg(
+            Arg::with_name("gs-arg")
+                .long("gs-arg")
+                .value_name("ARG")
+                .multiple(true)
+                .number_of_values(1)
+                .allow_hyphen_values(true)
+                .help("Pass ARG to Ghostscript verbatim, without validating it (repeatable)"),
+        )
+        .arg(
+            Arg::with_name("font-path")
+                .long("font-path")
+                .value_name("DIR")
+                .help("Directory searched by Ghostscript for fonts"),
+        )
+        .arg(
+            Arg::with_name("watermark")
+                .long("watermark")
+                .value_name("TEXT")
+                .help("Draw TEXT diagonally across every page"),
+        )
+        .arg(
+            Arg::with_name("watermark-size")
+                .long("watermark-size")
+                .value_name("PT")
+                .requires("watermark")
+                .validator(|pt| match pt.parse::<u32>() {
+                    Ok(pt) if pt > 0 => Ok(()),
+                    _ => Err(String::from("must be a positive integer")),
+                })
+                .help("Font size of the watermark, in points (default: 72)"),
+        )
+        .arg(
+            Arg::with_name("watermark-opacity")
+                .long("watermark-opacity")
+                .value_name("PERCENT")
+                .requires("watermark")
+                .validator(|p| match p.parse::<u8>() {
+                    Ok(p) if p <= 100 => Ok(()),
+                    _ => Err(String::from("must be a percentage in [0, 100]")),
+                })
+                .help("Opacity of the watermark, in percent (default: 30)"),
+        )
+        .arg(
+            Arg::with_name("min-size")
+                .long("min-size")
+                .value_name("SIZE")
+                .validator(|s| parse_size(&s).map(drop))
+                .help("Skip files smaller than SIZE bytes (accepts K, M, G suffixes)"),
+        )
+        .arg(
+            Arg::with_name("max-size")
+                .long("max-size")
+                .value_name("SIZE")
+                .validator(|s| parse_size(&s).map(drop))
+                .help("Skip files larger than SIZE bytes (accepts K, M, G suffixes)"),
+        )
+        .arg(
+            Arg::with_name("skip-shrunk")
+                .long("skip-shrunk")
+                .help("Skip files named *.shrunk.pdf, produced by a previous run"),
+        )
+        .arg(
+            Arg::with_name("no-clobber")
+                .long("no-clobber")
+                .help("Skip files whose output already exists"),
+        )
+        .arg(
+            Arg::with_name("update")
+                .long("update")
+                .short("u")
+                .help("Skip files whose output is newer than the input"),
+        )
+        .arg(
+            Arg::with_name("keep-smaller")
+                .long("keep-smaller")
+                .help("Discard outputs larger than their input"),
+        )
+        .arg(
+            Arg::with_name("fail-on-growth")
+                .long("fail-on-growth")
+                .help("Exit with code 3 if any output is larger than its input"),
+        )
+        .arg(
+            Arg::with_name("link-unchanged")
+                .long("link-unchanged")
+                .requires("keep-smaller")
+                .help("Hardlink the input as output when the shrunk file is discarded"),
+        )
+        .arg(
+            Arg::with_name("output-mode")
+                .long("output-mode")
+                .value_name("OCTAL")
+                .validator(|mode| parse_mode(&mode).map(drop))
+                .help("Set the permissions of the outputs, e.g. 664 (Unix only)"),
+        )
+        .arg(
+            Arg::with_name("preserve-dir-mode")
+                .long("preserve-dir-mode")
+                .help("Create the output directories with the permissions of the input's (Unix only)"),
+        )
+        .arg(
+            Arg::with_name("mark")
+                .long("mark")
+                .help("Record the settings in the outputs and skip inputs already shrunk with the same settings"),
+        )
+        .arg(
+            Arg::with_name("upgrade")
+                .long("upgrade")
+                .help("Shrink again only the inputs marked by a previous run with different settings (implies --mark)"),
+        )
+        .arg(
+            Arg::with_name("pre-process")
+                .long("pre-process")
+                .value_name("CMD")
+                .validator(|cmd| Hook::parse(&cmd).map(drop))
+                .help("Run CMD on each input before shrinking it, replacing {input} with its path"),
+        )
+        .arg(
+            Arg::with_name("post-process")
+                .long("post-process")
+                .value_name("CMD")
+                .validator(|cmd| Hook::parse(&cmd).map(drop))
+                .help(
+                    "Run CMD on each output after shrinking it, replacing {output} with its path",
+                ),
+        )
+        .arg(
+            Arg::with_name("validate-with")
+                .long("validate-with")
+                .value_name("CMD")
+                .validator(|cmd| Hook::parse(&cmd).map(drop))
+                .help("Run CMD on each output to validate it, e.g. 'qpdf --check', and fail if it fails"),
+        )
+        .arg(
+            Arg::with_name("fallback-copy")
+                .long("fallback-copy")
+                .requires("validate-with")
+                .help("Copy the input in place of an output which fails validation"),
+        )
+        .arg(
+            Arg::with_name("input-list-from-stdin")
+                .long("input-list-from-stdin")
+                .help("Also shrink the files listed in the standard input, one per line"),
+        )
+        .arg(
+            Arg::with_name("input-list-mode")
+                .long("input-list-mode")
+                .value_name("MODE")
+                .possible_values(&["auto", "buffer", "stream"])
+                .requires("input-list-from-stdin")
+                .help(
+                    "Read the whole list before processing it, so that the total is known, \
+                     or process the files as they are listed (default: auto)",
+                ),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("ORDER")
+                .possible_values(&["name", "size-asc", "size-desc"])
+                .help("Process the files sorted by name or size (default: as given)"),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("N")
+                .validator(|n| n.parse::<usize>().map(drop).map_err(|e| e.to_string()))
+                .help("Only process the first N files which pass the filters"),
+        )
+        .arg(
+            Arg::with_name("sample-random")
+                .long("sample-random")
+                .requires("sample")
+                .help("Choose the --sample files at random instead of the first ones"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("SEED")
+                .requires("sample-random")
+                .validator(|n| n.parse::<u64>().map(drop).map_err(|e| e.to_string()))
+                .help("Seed for --sample-random (default: based on the clock)"),
+        )
+        .arg(
+            Arg::with_name("save-plan")
+                .long("save-plan")
+                .value_name("PATH")
+                .help("Write the planned commands to PATH as JSON instead of running them"),
+        )
+        .arg(
+            Arg::with_name("run-plan")
+                .long("run-plan")
+                .value_name("PATH")
+                .conflicts_with("save-plan")
+                .help("Run the commands of a plan saved with --save-plan"),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .value_name("PATH")
+                .conflicts_with("compare")
+                .help("Skip the inputs listed in PATH and append the ones shrunk, to resume a batch"),
+        )
+        .arg(
+            Arg::with_name("recursive")
+                .long("recursive")
+                .short("R")
+                .help("Shrink the PDF files in the input directories and their subdirectories"),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .value_name("N")
+                .requires("recursive")
+                .validator(|n| n.parse::<usize>().map(drop).map_err(|e| e.to_string()))
+                .help("Descend at most N levels below the input directories (1: no subdirectories)"),
+        )
+        .arg(
+            Arg::with_name("no-glob")
+                .long("no-glob")
+                .help("Take the inputs literally, without expanding their braces and wildcards"),
+        )
+        .arg(
+            Arg::with_name("glob-strict")
+                .conflicts_with("no-glob")
+                .long("glob-strict")
+                .help("Fail if an input pattern matches no file, instead of skipping it"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .long("jobs")
+                .short("j")
+                .value_name("N")
+                .validator(|n| match n.parse::<usize>() {
+                    Ok(0) => Err(String::from("at least one job is needed")),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                })
+                .help("Run up to N Ghostscript processes at once (default: number of CPUs)"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("DURATION")
+                .validator(|d| match parse_duration(&d) {
+                    Ok(d) if d.is_zero() => Err(String::from("the timeout must be positive")),
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(e),
+                })
+                .help("Stop Ghostscript if it takes longer than DURATION on a file, e.g. 90 or 5m"),
+        )
+        .arg(
+            Arg::with_name("max-runtime")
+                .long("max-runtime")
+                .value_name("DURATION")
+                .validator(|d| parse_duration(&d).map(drop))
+                .help("Do not start shrinking more files after DURATION, e.g. 2h or 1h30m"),
+        )
+        .arg(
+            Arg::with_name("kill-at-max-runtime")
+                .long("kill-at-max-runtime")
+                .requires("max-runtime")
+                .help("Stop Ghostscript on the files still running after --max-runtime"),
+        )
+        .arg(
+            Arg::with_name("checksum")
+                .long("checksum")
+                .value_name("ALGORITHM")
+                .possible_values(&["sha256", "md5"])
+                .help("Write the digest of each output next to it, e.g. out.pdf.sha256"),
+        )
+        .arg(
+            Arg::with_name("content-address")
+                .long("content-address")
+                .value_name("DIR")
+                .help("Store each output in DIR named after its digest, skipping duplicates"),
+        )
+        .arg(
+            Arg::with_name("gs")
+                .long("gs")
+                .value_name("PATH")
+                .help("Use the Ghostscript executable PATH instead of searching for it"),
+        )
+        .arg(
+            Arg::with_name("keep-going-on-missing-gs")
+                .long("keep-going-on-missing-gs")
+                .help("Process the files even if Ghostscript is not found"),
+        )
+        .arg(
+            Arg::with_name("doctor")
+                .long("doctor")
+                .help("Print troubleshooting information about the environment and exit"),
+        )
+        .arg(
+            Arg::with_name("print-output-paths")
+                .long("print-output-paths")
+                .conflicts_with("json")
+                .help("Print the path of each output to stdout, one per line"),
+        )
+        .arg(
+            Arg::with_name("null")
+                .long("null")
+                .short("0")
+                .requires("print-output-paths")
+                .help("Terminate the paths printed by --print-output-paths with NUL"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .value_name("WHEN")
+                .possible_values(&["always", "never", "auto"])
+                .help("Color the log: always, never or auto (default, honoring NO_COLOR)"),
+        )
+        .arg(
+            Arg::with_name("log")
+                .long("log")
+                .value_name("SINK")
+                .help("Write the log to stderr (default), stdout or appending to the file SINK"),
+        )
         .arg(
             Arg::with_name("debug")
                 .long("debug")
                 .hidden(true)
                 .help("Debug the command line"),
         )
+        .arg(
+            Arg::with_name("dpi-report")
+                .long("dpi-report")
+                .help("Print the resolution of the embedded images and exit (requires pdfimages)"),
+        )
+        .arg(
+            Arg::with_name("summary-format")
+                .long("summary-format")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json", "csv"])
+                .default_value("text")
+                .help("Print the final summary as text in the log, or as JSON or CSV on stdout"),
+        )
+        .arg(
+            Arg::with_name("histogram")
+                .long("histogram")
+                .help("Print a histogram of how much the files shrank at the end"),
+        )
+        .arg(
+            Arg::with_name("explain")
+                .long("explain")
+                .conflicts_with("inplace")
+                .help("Show which images were downsampled, comparing the images of the input and of the output (requires pdfimages)"),
+        )
+        .arg(
+            Arg::with_name("no-batch")
+                .long("no-batch")
+                .hidden(true)
+                .help("Omit -dBATCH and -dNOPAUSE to debug Ghostscript interactively (hangs otherwise)"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .conflicts_with("print-output-paths")
+                .help("Print a JSON object for each shrunk file, with the Ghostscript command, and for each error on stderr"),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .help("Print the sizes of the inputs and of their existing outputs and exit"),
+        )
+        .arg(
+            Arg::with_name("pairs")
+                .long("pairs")
+                .requires("compare")
+                .help("With --compare, read the inputs as pairs of an input and its output"),
+        )
+        .arg(
+            Arg::with_name("prepare")
+                .long("prepare")
+                .requires("dry-run")
+                .help("With --dry-run, create the output directories"),
+        )
         .arg(
             Arg::with_name("dry-run")
                 .long("dry-run")
                 .short("n")
                 .help("Do not actually run the commands, just show them"),
         )
-        .group(ArgGroup::with_name("output").args(&["inplace", "rename", "subdir"]));
+        .group(ArgGroup::with_name("output").args(&[
+            "inplace",
+            "rename",
+            "subdir",
+            "content-address",
+        ]));
 
-    let matches = app.get_matches();
+    let args = expand_arg_files(std::env::args_os()).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    });
+    let matches = app.get_matches_from(args);
 
     let debug = matches.is_present("debug");
     let dry_run = matches.is_present("dry-run");
+    let threads = matches.value_of("jobs").map_or_else(
+        || std::thread::available_parallelism().map_or(1, usize::from),
+        |n| n.parse().expect("invalid number of jobs"),
+    );
     let verbose = matches.is_present("verbose");
+    let prepare = matches.is_present("prepare");
+    let print_output_paths = matches.is_present("print-output-paths");
+    let null = matches.is_present("null");
+    let fail_on_growth = matches.is_present("fail-on-growth");
+    let explain = matches.is_present("explain");
+    let budget = matches.value_of("max-runtime").map(|d| Budget {
+        start: Instant::now(),
+        max: parse_duration(d).expect("invalid duration"),
+    });
+    let kill_at_max_runtime = matches.is_present("kill-at-max-runtime");
+
+    let sink = matches
+        .value_of_os("log")
+        .map_or(LogSink::Stderr, LogSink::parse);
+    // stdout is reserved for the paths
+    if print_output_paths {
+        let other = if matches.value_of("summary-format") != Some("text") {
+            Some("--summary-format")
+        } else if matches!(sink, LogSink::Stdout) {
+            Some("--log stdout")
+        } else {
+            None
+        };
+        if let Some(other) = other {
+            clap::Error::with_description(
+                &format!(
+                    "The argument '{}' cannot be used with '--print-output-paths'",
+                    other
+                ),
+                clap::ErrorKind::ArgumentConflict,
+            )
+            .exit();
+        }
+    }
+    let log_file = sink.open().unwrap_or_else(|e| {
+        eprintln!("Cannot open the log file: {}", e);
+        std::process::exit(1);
+    });
+    let color = matches.value_of("color").unwrap_or("auto");
+    let is_tty = sink.is_terminal();
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let style = if log_file.is_some() {
+        env_logger::WriteStyle::Never
+    } else {
+        write_style(color, is_tty, no_color)
+    };
+    set_up_env_logger(verbose, style, sink.target(), log_file);
+
+    // the executable given with --gs, or the one found in the usual places
+    let gs = matches.value_of_os("gs").map(std::path::PathBuf::from);
+    let resolve = || match &gs {
+        Some(gs) => which(gs).ok_or_else(|| ShrinkError::GhostscriptNotFound {
+            tried: vec![gs.to_string_lossy().into_owned()],
+        }),
+        None => resolve_ghostscript(),
+    };
+
+    // clap would only print the version of the crate
+    if matches.is_present("version") {
+        let gs_version = resolve().and_then(|gs| ghostscript_version_with(&SystemRunner, gs));
+        print!("{}", version_text(gs_version));
+        return;
+    }
+
+    let suffix = matches.value_of("suffix").unwrap_or("shrunk");
+    let suffix = match redundant_pdf_suffix(suffix) {
+        Some(_) if matches.is_present("keep-pdf-suffix") => {
+            warn!("The suffix {:?} repeats the extension .pdf", suffix);
+            suffix
+        }
+        Some("") => {
+            error!(
+                "The suffix {:?} would only repeat the extension .pdf",
+                suffix
+            );
+            std::process::exit(1);
+        }
+        Some(stripped) => {
+            warn!(
+                "Using the suffix {:?} instead of {:?}, which repeats the extension .pdf",
+                stripped, suffix
+            );
+            stripped
+        }
+        None => suffix,
+    };
+
+    let dpi_scale = matches
+        .value_of("dpi-scale")
+        .map(|f| f.parse::<f64>().expect("invalid factor"));
+    let min_dpi: u32 = matches
+        .value_of("min-dpi")
+        .map_or(72, |dpi| parse_dpi(dpi).expect("invalid resolution"));
+
+    let respect_scan = matches.is_present("respect-scan-dpi");
+
+    let first_percent = matches
+        .value_of("first-percent")
+        .map(|p| p.parse::<f64>().expect("invalid percentage"));
 
-    set_up_env_logger(verbose);
+    // the options given explicitly override the ones of the preset
+    let defaults = match matches.value_of("preset") {
+        Some(name) => {
+            let mut presets = Presets::builtin();
+            if let Some(path) = matches.value_of("preset-file") {
+                if let Err(e) = presets.load(path) {
+                    error!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            match presets.get(name) {
+                Some(options) => options.clone(),
+                None => {
+                    error!(
+                        "Unknown preset {:?}, expected one of {}",
+                        name,
+                        presets.names().join(", ")
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => ShrinkOptions::default(),
+    };
+
+    // an empty table gives the default resolutions
+    let (color_resolution, gray_resolution, mono_resolution) = parse_dpi_table_over(
+        matches.value_of("dpi").unwrap_or(""),
+        (
+            defaults.color_resolution,
+            defaults.gray_resolution,
+            defaults.mono_resolution,
+        ),
+    )
+    .expect("invalid resolution table");
+
+    let base_options = ShrinkOptions {
+        ghostscript: resolve().ok().or_else(|| gs.clone()),
+        pdf_settings: matches
+            .value_of("quality")
+            .map_or(defaults.pdf_settings, |q| {
+                q.parse().expect("invalid settings")
+            }),
+        compatibility_level: matches
+            .value_of("pdf-version")
+            .map_or_else(|| defaults.compatibility_level.clone(), Into::into),
+        color_resolution,
+        gray_resolution,
+        mono_resolution,
+        downsample_color: defaults.downsample_color && !matches.is_present("no-downsample-color"),
+        downsample_gray: defaults.downsample_gray && !matches.is_present("no-downsample-gray"),
+        downsample_mono: defaults.downsample_mono && !matches.is_present("no-downsample-mono"),
+        device_resolution: matches
+            .value_of("device-dpi")
+            .map(|dpi| parse_dpi(dpi).expect("invalid resolution"))
+            .or(defaults.device_resolution),
+        flatten_forms: defaults.flatten_forms || matches.is_present("flatten-forms"),
+        grayscale: defaults.grayscale || matches.is_present("grayscale"),
+        fast_web_view: defaults.fast_web_view || matches.is_present("fast-web-view"),
+        preserve_bookmarks: defaults.preserve_bookmarks && !matches.is_present("drop-bookmarks"),
+        full_rewrite: defaults.full_rewrite && !matches.is_present("no-new-pdf"),
+        min_size: matches
+            .value_of("min-size")
+            .map(|s| parse_size(s).expect("invalid size")),
+        max_size: matches
+            .value_of("max-size")
+            .map(|s| parse_size(s).expect("invalid size")),
+        shrunk_suffix: if matches.is_present("skip-shrunk") {
+            Some(suffix.into())
+        } else {
+            None
+        },
+        no_clobber: matches.is_present("no-clobber"),
+        update: matches.is_present("update"),
+        keep_smaller: defaults.keep_smaller || matches.is_present("keep-smaller"),
+        link_unchanged: matches.is_present("link-unchanged"),
+        preserve_dir_mode: matches.is_present("preserve-dir-mode"),
+        output_mode: matches
+            .value_of("output-mode")
+            .map(|mode| parse_mode(mode).expect("invalid file mode")),
+        marker: matches.is_present("mark") || matches.is_present("upgrade"),
+        upgrade: matches.is_present("upgrade"),
+        strip_metadata: defaults.strip_metadata || matches.is_present("strip-metadata"),
+        pre_process: matches
+            .value_of("pre-process")
+            .map(|cmd| Hook::parse(cmd).expect("invalid command")),
+        post_process: matches
+            .value_of("post-process")
+            .map(|cmd| Hook::parse(cmd).expect("invalid command")),
+        validate: matches
+            .value_of("validate-with")
+            .map(|cmd| Hook::parse(cmd).expect("invalid command")),
+        fallback_copy: matches.is_present("fallback-copy"),
+        temp_suffix: matches
+            .value_of_os("temp-suffix")
+            .map_or_else(|| DEFAULT_TEMP_SUFFIX.into(), Into::into),
+        always_replace: matches.is_present("always-replace"),
+        timeout: matches
+            .value_of("timeout")
+            .map(|d| parse_duration(d).expect("invalid timeout"))
+            .or(defaults.timeout),
+        follow_input_symlinks: !matches.is_present("no-follow-input-symlinks"),
+        include_paths: matches
+            .values_of_os("include-path")
+            .map_or_else(Vec::new, |paths| paths.map(Into::into).collect()),
+        font_path: matches.value_of_os("font-path").map(Into::into),
+        extra_args: matches
+            .values_of_os("gs-arg")
+            .map_or_else(Vec::new, |args| args.map(Into::into).collect()),
+        batch: !matches.is_present("no-batch"),
+        watermark: matches.value_of("watermark").map(|text| {
+            let mut watermark = Watermark::new(text);
+            if let Some(pt) = matches.value_of("watermark-size") {
+                watermark.font_size = pt.parse().expect("invalid font size");
+            }
+            if let Some(p) = matches.value_of("watermark-opacity") {
+                watermark.opacity = p.parse().expect("invalid opacity");
+            }
+            watermark
+        }),
+        ..defaults
+    };
+
+    // only the resolutions given explicitly can contradict the quality
+    if matches.is_present("quality") && (matches.is_present("dpi") || matches.is_present("preset"))
+    {
+        for conflict in base_options.resolution_conflicts() {
+            warn!("Contradictory options: {}", conflict);
+        }
+    }
 
     // BEGIN DEBUG
     if debug {
@@ -97,86 +902,913 @@ fn main() {
     }
     // END DEBUG
 
-    for inpath in matches.values_of("input").expect("missing input") {
-        if verbose {
-            debug!("Processing {:?}", inpath);
+    if matches.is_present("doctor") {
+        let ghostscript = resolve().map_err(|e| e.to_string());
+        let env = probe_environment(&SystemRunner, ghostscript);
+        print!("{}", doctor_report(&env, &base_options));
+        return;
+    }
+
+    if let Some(path) = matches.value_of("run-plan") {
+        run_plan(path, dry_run);
+        return;
+    }
+
+    if matches.is_present("dpi-report") {
+        for inpath in matches.values_of("input").expect("missing input") {
+            match probe_images(inpath) {
+                Ok(images) => {
+                    let dpis: Vec<u32> = images.iter().map(ImageInfo::ppi).collect();
+                    match dpi_stats(&dpis) {
+                        Some(stats) => println!(
+                            "{}: {} images, min {} dpi, median {} dpi, max {} dpi",
+                            inpath,
+                            images.len(),
+                            stats.min,
+                            stats.median,
+                            stats.max
+                        ),
+                        None => println!("{}: no images", inpath),
+                    }
+                }
+                Err(e) => warn!("Cannot list the images of {:?}: {}", inpath, e),
+            }
         }
+        return;
+    }
 
-        let outpath;
+    let compare = matches.is_present("compare");
+    let json = matches.is_present("json");
+    let inplace = matches.is_present("inplace");
+    if matches.is_present("pairs") {
+        let paths: Vec<&str> = matches.values_of("input").expect("missing input").collect();
+        if !paths.len().is_multiple_of(2) {
+            error!("--pairs requires an even number of paths");
+            std::process::exit(1);
+        }
+        let failed = paths
+            .chunks(2)
+            .filter(|pair| !print_comparison(pair[0], pair[1]))
+            .count();
+        std::process::exit(if failed == 0 { 0 } else { 1 });
+    }
 
-        if matches.is_present("inplace") {
-            // use tempdir::TempDir;
-            todo!("--inplace");
-        } else if matches.is_present("subdir") {
-            let subdir = matches.value_of("subdir").expect("missing subdir");
-            outpath = match pdf_into_subdir(inpath, subdir) {
+    // fail once instead of for every file
+    if !compare {
+        if let Err(message) = require_ghostscript(resolve) {
+            if dry_run || matches.is_present("keep-going-on-missing-gs") {
+                warn!("{}", message);
+            } else {
+                error!("{}", message);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // plan the jobs: compute the outputs and apply the filtering rules
+    let mut inputs: Vec<String> = Vec::new();
+    let mut invalid_inputs = 0;
+    for input in matches.values_of_os("input").into_iter().flatten() {
+        // Ghostscript would not read it correctly, and the plan handles the paths as strings
+        if path_is_lossy(input) {
+            error!("Cannot process {:?}: the name is not valid Unicode", input);
+            invalid_inputs += 1;
+            continue;
+        }
+        let input = input.to_str().expect("invalid Unicode");
+        if matches.is_present("recursive") && std::path::Path::new(input).is_dir() {
+            let max_depth = matches
+                .value_of("max-depth")
+                .map(|n| n.parse().expect("invalid depth"));
+            match collect_pdfs(input, max_depth) {
+                Ok(pdfs) => {
+                    for pdf in pdfs {
+                        if path_is_lossy(&pdf) {
+                            error!("Cannot process {:?}: the name is not valid Unicode", pdf);
+                            invalid_inputs += 1;
+                        } else {
+                            inputs.push(pdf.to_string_lossy().into_owned());
+                        }
+                    }
+                }
+                Err(e) => {
+                    report_error(json, "Cannot read", input, &e);
+                    invalid_inputs += 1;
+                }
+            }
+        } else if matches.is_present("no-glob") {
+            inputs.push(input.into());
+        } else {
+            inputs.extend(expand_input(input, matches.is_present("glob-strict")));
+        }
+    }
+    // the rest of a streamed list, read one path at a time
+    let mut stdin_list = None;
+    if matches.is_present("input-list-from-stdin") {
+        let mut mode = matches.value_of("input-list-mode").unwrap_or("auto");
+        // these need the whole list
+        if ["order", "sample", "save-plan"]
+            .iter()
+            .any(|arg| matches.is_present(arg))
+        {
+            if mode == "stream" {
+                error!(
+                    "--input-list-mode stream cannot be used with --order, --sample or --save-plan"
+                );
+                std::process::exit(1);
+            }
+            mode = "buffer";
+        }
+        let stdin_is_tty = std::io::IsTerminal::is_terminal(&std::io::stdin());
+        let mut stdin = std::io::stdin().lock();
+        let (paths, ended) =
+            read_paths(&mut stdin, read_ahead(mode, stdin_is_tty)).unwrap_or_else(|e| {
+                error!("Cannot read the list of inputs: {}", e);
+                std::process::exit(1);
+            });
+        inputs.extend(paths);
+        if !ended {
+            debug!("Streaming the list of inputs");
+            stdin_list = Some(stdin);
+        }
+    }
+    let checksum: Option<ChecksumAlgorithm> = matches
+        .value_of("checksum")
+        .map(|name| name.parse().expect("invalid checksum algorithm"));
+    let content_address = matches.value_of("content-address");
+    let mut checkpoint = matches.value_of("checkpoint").map(|path| {
+        let checkpoint = Checkpoint::open(path).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        });
+        if !checkpoint.is_empty() {
+            info!("Resuming: {} inputs already shrunk", checkpoint.len());
+        }
+        checkpoint
+    });
+    let mut summary = BatchSummary::default();
+    summary.failed += invalid_inputs;
+    let mut processed = 0;
+    let mut unprocessed = 0;
+    loop {
+        if let Some(order) = matches.value_of("order") {
+            sort_inputs(&mut inputs, order.parse().expect("invalid order"));
+        }
+        let mut jobs = Vec::new();
+        for inpath in inputs.iter().map(String::as_str) {
+            if verbose {
+                debug!("Processing {:?}", inpath);
+            }
+
+            // the output of name.pdf.pdf is named after name.pdf
+            let stem = if matches.is_present("collapse-double-ext") {
+                normalize_pdf_stem(inpath)
+            } else {
+                inpath.into()
+            };
+            // in place, Ghostscript writes to a temporary file renamed over the input
+            let outpath = if inplace {
+                in_place_temp_path_with(inpath, &base_options.temp_suffix)
+            } else if let Some(dir) = content_address {
+                // renamed after its digest once shrunk, numbered to be unique meanwhile
+                let mut name = OsString::from(format!(".{}", processed + jobs.len()));
+                name.push(&base_options.temp_suffix);
+                Some(std::path::Path::new(dir).join(name))
+            } else if matches.is_present("subdir") {
+                let subdir = matches.value_of("subdir").expect("missing subdir");
+                pdf_into_subdir(&stem, subdir)
+            } else if matches.is_present("out-suffix-before-ext") {
+                pdf_with_dash_suffix(&stem, suffix)
+            } else {
+                pdf_with_suffix(&stem, suffix)
+            };
+            let outpath = match outpath {
                 Some(p) => p,
                 None => {
-                    warn!(
-                        "Cannot process {:?} because the computed output is invalid",
-                        inpath
-                    );
+                    let e = ShrinkError::InvalidInputPath {
+                        path: inpath.into(),
+                    };
+                    report_error(json, "Cannot process", inpath, &e);
+                    summary.record_failure();
+                    continue;
+                }
+            };
+
+            if compare {
+                if !print_comparison(inpath, &outpath) {
+                    summary.record_failure();
+                }
+                continue;
+            }
+
+            if checkpoint.as_ref().is_some_and(|c| c.contains(inpath)) {
+                let reason = SkipReason::Checkpointed;
+                log_skip(inpath, reason);
+                summary.record_skip(reason);
+                continue;
+            }
+
+            let options = match with_sidecar(inpath, &base_options) {
+                Ok(options) => options,
+                Err(e) => {
+                    report_error(json, "Cannot process", inpath, &e);
+                    summary.record_failure();
                     continue;
                 }
             };
-            if !dry_run {
-                let subpath = match pdf_subdir(inpath, subdir) {
-                    Some(p) => p,
-                    None => {
-                        warn!(
-                            "Cannot process {:?} because the computed subdir is invalid",
-                            inpath
-                        );
-                        continue;
+            if options != base_options {
+                debug!("Using the options of {:?}", sidecar_path(inpath));
+            }
+
+            match check_skip(inpath, &outpath, &options) {
+                Ok(None) => jobs.push((inpath, outpath, options)),
+                Ok(Some(reason)) => {
+                    log_skip(inpath, reason);
+                    summary.record_skip(reason);
+                }
+                Err(e) => {
+                    report_error(json, "Cannot process", inpath, &e);
+                    summary.record_failure();
+                }
+            }
+        }
+
+        if let Some(n) = matches.value_of("sample") {
+            let n = n.parse().expect("invalid sample size");
+            let seed = if matches.is_present("sample-random") {
+                let seed = match matches.value_of("seed") {
+                    Some(seed) => seed.parse().expect("invalid seed"),
+                    None => std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |d| d.as_nanos() as u64),
+                };
+                info!("Sampling {} files at random with --seed {}", n, seed);
+                Some(seed)
+            } else {
+                None
+            };
+            jobs = sample(jobs, n, seed);
+        }
+
+        // options adapted to each file
+        let file_options = |inpath: &str, options: &ShrinkOptions| -> Option<ShrinkOptions> {
+            let mut options = options.clone();
+
+            if let Some(percent) = first_percent {
+                let pages = match page_count(inpath, &options) {
+                    Ok(pages) => pages,
+                    Err(e) => {
+                        warn!("Cannot count the pages of {:?}: {}", inpath, e);
+                        return None;
                     }
                 };
-                if let Err(e) = std::fs::create_dir_all(&subpath) {
-                    warn!("Cannot create {:?}: {:?}", subpath, e);
-                    continue;
+                options.last_page = Some(last_page_for_percent(pages, percent));
+                debug!(
+                    "Processing pages 1-{} of {}",
+                    options.last_page.unwrap_or(pages),
+                    pages
+                );
+            }
+
+            if dpi_scale.is_some() || respect_scan {
+                match probe_images(inpath) {
+                    Ok(images) => {
+                        let dpis: Vec<u32> = images.iter().map(ImageInfo::ppi).collect();
+                        if let (Some(factor), Some(stats)) = (dpi_scale, dpi_stats(&dpis)) {
+                            let dpi = scaled_dpi(stats.median, factor, min_dpi);
+                            debug!(
+                                "Downsampling the images to {} dpi (median {} dpi)",
+                                dpi, stats.median
+                            );
+                            options.color_resolution = dpi;
+                            options.gray_resolution = dpi;
+                            options.mono_resolution = dpi;
+                        }
+                        if respect_scan {
+                            let dpi = respect_scan_dpi(options.mono_resolution, &images);
+                            if dpi != options.mono_resolution {
+                                debug!("Keeping the monochrome images at {} dpi as scanned", dpi);
+                                options.mono_resolution = dpi;
+                            }
+                        }
+                    }
+                    Err(e) => warn!(
+                        "Cannot list the images of {:?}, using the default resolution: {}",
+                        inpath, e
+                    ),
                 }
             }
+
+            Some(options)
+        };
+
+        if let Some(path) = matches.value_of("save-plan") {
+            let mut plan = Plan::default();
+            for (inpath, outpath, options) in &jobs {
+                if let Some(options) = file_options(inpath, options) {
+                    let cmd = options.build_command(inpath, outpath);
+                    plan.jobs.push(PlannedJob::new(inpath, outpath, &cmd));
+                }
+            }
+            if let Err(e) = plan.save(path) {
+                error!("Cannot save the plan to {:?}: {}", path, e);
+                std::process::exit(1);
+            }
+            info!("Saved the plan of {} files to {:?}", plan.jobs.len(), path);
+            return;
+        }
+
+        // the total is unknown while a list is streamed
+        let total = if stdin_list.is_none() {
+            Some(processed + jobs.len())
         } else {
-            outpath = match pdf_with_suffix(inpath, "shrunk") {
-                Some(p) => p,
-                None => {
-                    warn!(
-                        "Cannot process {:?} because the computed output is invalid",
-                        inpath
+            None
+        };
+        let first = processed;
+        processed += jobs.len();
+        // the options adapted to the file and the probes run in the workers as well
+        let work =
+            |i: usize, (inpath, outpath, options): (&str, std::path::PathBuf, ShrinkOptions)| {
+                let remaining = match budget.map(|budget| budget.remaining(Instant::now())) {
+                    Some(None) => return Finished::OutOfTime,
+                    Some(remaining) => remaining,
+                    None => None,
+                };
+                let mut options = match file_options(inpath, &options) {
+                    Some(options) => options,
+                    None => return Finished::Unplanned,
+                };
+                // the file still running when the budget runs out is stopped like on a timeout
+                if let (true, Some(remaining)) = (kill_at_max_runtime, remaining) {
+                    options.timeout = Some(options.timeout.map_or(remaining, |t| t.min(remaining)));
+                }
+
+                let progress = match total {
+                    Some(total) => format!("[{}/{}]", first + i + 1, total),
+                    None => format!("[{}]", first + i + 1),
+                };
+                if inplace {
+                    if outpath.exists() {
+                        warn!("Replacing the stale temporary file {:?}", outpath);
+                    }
+                    info!("{} Compressing {:?} in place", progress, inpath);
+                } else {
+                    info!("{} Compressing {:?} -> {:?}", progress, inpath, outpath);
+                }
+
+                if dry_run && prepare {
+                    let created = if options.preserve_dir_mode {
+                        let source = std::path::Path::new(inpath)
+                            .parent()
+                            .filter(|dir| !dir.as_os_str().is_empty())
+                            .unwrap_or_else(|| ".".as_ref());
+                        ensure_parent_dir_like(&outpath, source)
+                    } else {
+                        ensure_parent_dir(&outpath)
+                    };
+                    match created {
+                        Ok(Some(dir)) => info!("Created the directory {:?}", dir),
+                        Ok(None) => {}
+                        Err(e) => warn!("{}", e),
+                    }
+                }
+
+                let mut cmd = if dry_run {
+                    options.build_dry_run_command(inpath, &outpath)
+                } else {
+                    options.build_command(inpath, &outpath)
+                };
+                let command = command_line_string(&cmd);
+
+                if verbose {
+                    // debug!("Running {:?}", cmd);
+                    debug!("{}", command);
+                }
+
+                if !dry_run {
+                    let result = if inplace {
+                        shrink_in_place(inpath, &options)
+                    } else {
+                        shrink_file(inpath, &outpath, &options)
+                    };
+                    return Finished::Shrunk {
+                        inpath: inpath.into(),
+                        command,
+                        result,
+                    };
+                }
+
+                // a missing `args` must not abort the rest of the batch
+                let output = match SystemRunner.run(&mut cmd) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        warn!("Cannot run {:?}: {}", cmd.get_program(), e);
+                        return Finished::DryRun;
+                    }
+                };
+                if !output.stdout.is_empty() {
+                    info!(
+                        "STDOUT:\n{}",
+                        String::from_utf8_lossy(&output.stdout).trim_end()
                     );
-                    continue;
                 }
+                if !output.stderr.is_empty() {
+                    debug!(
+                        "STDERR:\n{}",
+                        String::from_utf8_lossy(&output.stderr).trim_end()
+                    );
+                }
+                Finished::DryRun
+            };
+        // the results are reported in the order of the inputs, by this thread only
+        let threads = if dry_run { 1 } else { threads };
+        for_each_ordered(threads, jobs, work, |finished| {
+            let (inpath, command, result) = match finished {
+                Finished::Unplanned => return summary.record_failure(),
+                Finished::OutOfTime => {
+                    unprocessed += 1;
+                    return;
+                }
+                Finished::DryRun => return,
+                Finished::Shrunk {
+                    inpath,
+                    command,
+                    result,
+                } => (inpath, command, result),
+            };
+            let result = match (content_address, result) {
+                (Some(dir), Ok(ShrinkOutcome::Shrunk(mut report))) => {
+                    let algorithm = checksum.unwrap_or(ChecksumAlgorithm::Sha256);
+                    match store_by_content(&report.output, dir, algorithm) {
+                        Ok((stored, true)) => {
+                            report.output = stored;
+                            Ok(ShrinkOutcome::Shrunk(report))
+                        }
+                        Ok((stored, false)) => {
+                            debug!(
+                                "The output of {:?} is already stored as {:?}",
+                                inpath, stored
+                            );
+                            Ok(ShrinkOutcome::Skipped {
+                                reason: SkipReason::Duplicate,
+                            })
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                (_, result) => result,
+            };
+            summary.record(&result);
+            if let (Some(checkpoint), Ok(ShrinkOutcome::Shrunk(_))) = (&mut checkpoint, &result) {
+                if let Err(e) = checkpoint.record(&inpath) {
+                    warn!("{}", e);
+                }
+            }
+            if let (Some(algorithm), Ok(ShrinkOutcome::Shrunk(report))) = (checksum, &result) {
+                if let Err(e) = write_checksum(&report.output, algorithm) {
+                    warn!("Cannot write the checksum: {}", e);
+                }
+            }
+            if let (true, Ok(ShrinkOutcome::Shrunk(report))) = (print_output_paths, &result) {
+                let terminator = if null { b'\0' } else { b'\n' };
+                write_path(&mut std::io::stdout().lock(), &report.output, terminator)
+                    .expect("cannot write to stdout");
+            }
+            if let (true, Ok(ShrinkOutcome::Shrunk(report))) = (json, &result) {
+                let mut entry = serde_json::to_value(report).expect("cannot serialize the report");
+                entry["command"] = command.into();
+                println!("{}", entry);
+            }
+            let explained = match (explain, &result) {
+                (true, Ok(ShrinkOutcome::Shrunk(report))) => Some(report.output.clone()),
+                _ => None,
             };
+            match result {
+                Ok(ShrinkOutcome::Shrunk(report)) if report.status == ExitClass::Warning => {
+                    warn!(
+                        "Shrunk {:?} despite errors reported by Ghostscript: {}",
+                        inpath,
+                        report.sizes()
+                    )
+                }
+                Ok(ShrinkOutcome::Shrunk(report)) => {
+                    info!("{}: {}", inpath, report.sizes())
+                }
+                Ok(ShrinkOutcome::Skipped { reason }) => log_skip(&inpath, reason),
+                Err(e) => {
+                    report_error(json, "Cannot shrink", &inpath, &e);
+                    if let ShrinkError::NonZeroExit { stderr, .. } = &e {
+                        if !stderr.is_empty() {
+                            debug!("STDERR:\n{}", stderr.trim_end());
+                        }
+                    }
+                }
+            }
+            if let Some(outpath) = explained {
+                explain_images(&inpath, &outpath);
+            }
+        });
+
+        // the rest of a streamed list is not even read
+        if budget.is_some_and(|budget| budget.remaining(Instant::now()).is_none()) {
+            break;
+        }
+        inputs = match &mut stdin_list {
+            Some(stdin) => match read_paths(stdin, 1) {
+                Ok((paths, _)) if !paths.is_empty() => paths,
+                Ok(_) => break,
+                Err(e) => {
+                    error!("Cannot read the list of inputs: {}", e);
+                    summary.record_failure();
+                    break;
+                }
+            },
+            None => break,
+        };
+    }
+    if unprocessed > 0 {
+        warn!(
+            "The --max-runtime ran out: {} files were left unprocessed",
+            unprocessed
+        );
+    }
+
+    if compare {
+        std::process::exit(if summary.failed == 0 { 0 } else { 1 });
+    }
+
+    if !dry_run {
+        match matches
+            .value_of("summary-format")
+            .expect("missing summary format")
+            .parse()
+            .expect("invalid summary format")
+        {
+            SummaryFormat::Text => info!("Summary: {}", summary),
+            SummaryFormat::Csv => print!("{}", summary.render(SummaryFormat::Csv)),
+            format => println!("{}", summary.render(format)),
+        }
+        if let Some(extremes) = summary.extremes() {
+            info!("Ratios: {}", extremes);
         }
+        if matches.is_present("histogram") && !json {
+            if let Some(bars) = summary.histogram_bars() {
+                info!("Space saved:\n{}", bars);
+            }
+        }
+    }
 
-        info!("Compressing {:?} -> {:?}", inpath, outpath);
+    let code = exit_code(&summary, fail_on_growth);
+    if code != 0 {
+        for input in &summary.grown {
+            error!("The output of {:?} is larger than the input", input);
+        }
+        std::process::exit(code);
+    }
+}
 
-        let mut cmd = if dry_run {
-            dry_run_command(inpath, outpath)
-        } else {
-            gs_command(inpath, outpath)
-        };
+/// Wall-clock time allowed to the whole batch by `--max-runtime`.
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    start: Instant,
+    max: Duration,
+}
+
+impl Budget {
+    /// Time left at `now`, or `None` once the budget has run out.
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.max
+            .checked_sub(now.saturating_duration_since(self.start))
+            .filter(|left| !left.is_zero())
+    }
+}
+
+/// What became of a planned file, reported by the main thread.
+enum Finished {
+    /// The options adapted to the file could not be computed.
+    Unplanned,
+    /// The command was simulated.
+    DryRun,
+    /// The `--max-runtime` ran out before the file was started.
+    OutOfTime,
+    Shrunk {
+        inpath: String,
+        command: String,
+        result: Result<ShrinkOutcome, ShrinkError>,
+    },
+}
 
-        if verbose {
-            // debug!("Running {:?}", cmd);
-            let mut cmdline = String::from(cmd.get_program().to_string_lossy());
-            for arg in cmd.get_args() {
-                cmdline.push_str(&format!(" {}", shell_escape::escape(arg.to_string_lossy())));
+/// Runs `work` on the `items` with up to `threads` threads, and passes each result to
+/// `handle` on the calling thread, in the order of the items.
+///
+/// The threads take the next item as soon as they are free, so at most `threads` items
+/// are processed at once; the results arriving early are held until their turn.
+fn for_each_ordered<T, R, W, H>(threads: usize, items: Vec<T>, work: W, mut handle: H)
+where
+    T: Send,
+    R: Send,
+    W: Fn(usize, T) -> R + Sync,
+    H: FnMut(R),
+{
+    if threads <= 1 || items.len() <= 1 {
+        for (i, item) in items.into_iter().enumerate() {
+            handle(work(i, item));
+        }
+        return;
+    }
+    let queue = std::sync::Mutex::new(items.into_iter().enumerate());
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            let (queue, work, sender) = (&queue, &work, sender.clone());
+            scope.spawn(move || loop {
+                let next = queue.lock().expect("poisoned queue").next();
+                let (i, item) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                if sender.send((i, work(i, item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        let mut early = std::collections::BTreeMap::new();
+        let mut next = 0;
+        for (i, result) in receiver {
+            early.insert(i, result);
+            while let Some(result) = early.remove(&next) {
+                handle(result);
+                next += 1;
             }
-            debug!("{}", cmdline);
         }
+    });
+}
 
-        let output = cmd.output().expect("failed to execute command");
-        if !output.stdout.is_empty() {
-            info!(
-                "STDOUT:\n{}",
-                String::from_utf8_lossy(&output.stdout).trim_end()
-            );
+/// Reports the error `e` occurred on `inpath`, as a JSON object on stderr in `json` mode,
+/// otherwise as a warning starting with `context`.
+fn report_error(json: bool, context: &str, inpath: &str, e: &ShrinkError) {
+    if json {
+        eprintln!("{}", e.to_json(inpath.as_ref()));
+    } else {
+        warn!("{} {:?}: {}", context, inpath, e);
+    }
+}
+
+/// Lines printed by `--version`, with the version of Ghostscript or why it is unknown.
+fn version_text(gs_version: Result<(u32, u32), ShrinkError>) -> String {
+    let gs = match gs_version {
+        Ok((major, minor)) => format!("{}.{:02}", major, minor),
+        Err(ShrinkError::GhostscriptNotFound { .. }) => String::from("not found"),
+        Err(e) => format!("unknown ({})", e),
+    };
+    format!("{} {}\ngs: {}\n", crate_name!(), crate_version!(), gs)
+}
+
+/// Prints the sizes of `inpath` and of its existing `outpath`, returning whether it succeeded.
+fn print_comparison<P, Q>(inpath: P, outpath: Q) -> bool
+where
+    P: AsRef<std::path::Path>,
+    Q: AsRef<std::path::Path>,
+{
+    match ShrinkReport::compare(&inpath, outpath) {
+        Ok(report) => {
+            println!("{}", report);
+            true
         }
-        if !output.stderr.is_empty() {
-            debug!(
-                "STDERR:\n{}",
-                String::from_utf8_lossy(&output.stderr).trim_end()
-            );
+        Err(e) => {
+            warn!("Cannot compare {:?}: {}", inpath.as_ref(), e);
+            false
+        }
+    }
+}
+
+/// Expands the braces and wildcards of an `input` which does not name an existing file,
+/// for shells which do not do it.
+///
+/// A pattern matching nothing is skipped with a warning, or is a fatal error if `strict`.
+fn expand_input(input: &str, strict: bool) -> Vec<String> {
+    if std::path::Path::new(input).exists() {
+        return vec![input.into()];
+    }
+    let on_empty = if strict {
+        EmptyGlob::Error
+    } else {
+        EmptyGlob::Skip
+    };
+    match glob_inputs_with(input, on_empty) {
+        Ok(paths) => {
+            if paths.is_empty() {
+                warn!("No file matches {:?}", input);
+            }
+            paths
+                .into_iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        }
+        Err(e) if strict => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            warn!("{}", e);
+            vec![input.into()]
+        }
+    }
+}
+
+/// Number of lines of a non-interactive standard input read ahead by `--input-list-mode auto`:
+/// shorter lists are read whole before processing them, longer ones are streamed.
+const INPUT_LIST_READ_AHEAD: usize = 4096;
+
+/// Number of paths to read from the standard input before processing any file,
+/// in the given `--input-list-mode`.
+///
+/// If the list ends within them, it is processed with its total known,
+/// otherwise the rest is streamed.
+fn read_ahead(mode: &str, stdin_is_tty: bool) -> usize {
+    match mode {
+        "buffer" => usize::MAX,
+        "stream" => 0,
+        // typing the paths interactively
+        _ if stdin_is_tty => 0,
+        _ => INPUT_LIST_READ_AHEAD,
+    }
+}
+
+/// Reads up to `max` paths from `reader`, one per line, skipping the blank lines.
+///
+/// Returns the paths and whether the input ended.
+fn read_paths<R>(reader: &mut R, max: usize) -> std::io::Result<(Vec<String>, bool)>
+where
+    R: std::io::BufRead,
+{
+    let mut paths = Vec::new();
+    let mut line = String::new();
+    while paths.len() < max {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok((paths, true));
+        }
+        let path = line.trim_end_matches(&['\n', '\r'][..]);
+        if !path.trim().is_empty() {
+            paths.push(path.to_owned());
+        }
+    }
+    Ok((paths, false))
+}
+
+/// Maximum nesting of `@file` arguments, which stops cycles.
+const MAX_ARG_FILE_DEPTH: usize = 16;
+
+/// Replaces every argument `@file` with the arguments read from `file`, like Ghostscript.
+///
+/// The arguments in the file are separated by whitespace and can be quoted like in a
+/// shell (see [`split_words`]); they can be `@file` arguments in turn.
+/// The first argument, the name of the program, is left alone.
+fn expand_arg_files<I>(args: I) -> Result<Vec<OsString>, String>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let mut args = args.into_iter();
+    let mut expanded: Vec<OsString> = args.next().into_iter().collect();
+    for arg in args {
+        expand_arg(arg, 0, &mut expanded)?;
+    }
+    Ok(expanded)
+}
+
+fn expand_arg(arg: OsString, depth: usize, expanded: &mut Vec<OsString>) -> Result<(), String> {
+    let path = match arg.to_str().and_then(|arg| arg.strip_prefix('@')) {
+        Some(path) if !path.is_empty() => path.to_owned(),
+        _ => {
+            expanded.push(arg);
+            return Ok(());
+        }
+    };
+    if depth >= MAX_ARG_FILE_DEPTH {
+        return Err(format!("too many nested argument files at {:?}", path));
+    }
+    let text = std::fs::read_to_string(&path)
+        .map_err(|e| format!("cannot read the argument file {:?}: {}", path, e))?;
+    let words =
+        split_words(&text).map_err(|e| format!("invalid argument file {:?}: {}", path, e))?;
+    for word in words {
+        expand_arg(word.into(), depth + 1, expanded)?;
+    }
+    Ok(())
+}
+
+/// Exit code of a run, given its `summary`.
+fn exit_code(summary: &BatchSummary, fail_on_growth: bool) -> i32 {
+    if fail_on_growth && summary.grew() {
+        3
+    } else {
+        0
+    }
+}
+
+/// Writes `path` followed by `terminator`, preserving non UTF-8 paths on Unix.
+fn write_path<W: std::io::Write>(
+    w: &mut W,
+    path: &std::path::Path,
+    terminator: u8,
+) -> std::io::Result<()> {
+    #[cfg(unix)]
+    w.write_all(std::os::unix::ffi::OsStrExt::as_bytes(path.as_os_str()))?;
+    #[cfg(not(unix))]
+    w.write_all(path.to_string_lossy().as_bytes())?;
+    w.write_all(&[terminator])
+}
+
+/// Accepts a suffix of file names, which cannot be empty or contain a path separator.
+fn validate_suffix(suffix: String) -> Result<(), String> {
+    if suffix.is_empty() || suffix.contains(std::path::is_separator) {
+        Err(String::from("invalid suffix"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Executes the jobs of a plan saved with `--save-plan`.
+fn run_plan(path: &str, dry_run: bool) {
+    let plan = match Plan::load(path) {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("Cannot load the plan from {:?}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut failed = 0;
+    for job in &plan.jobs {
+        info!("Compressing {:?} -> {:?}", job.input, job.output);
+        debug!("{}", command_line_string(&job.command()));
+        if dry_run {
+            continue;
+        }
+        if let Some(parent) = job.output.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                warn!("Cannot create {:?}: {:?}", parent, e);
+                failed += 1;
+                continue;
+            }
+        }
+        if let Err(e) = job.run_with(&SystemRunner) {
+            warn!("Cannot shrink {:?}: {}", job.input, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        error!("{} of {} jobs of the plan failed", failed, plan.jobs.len());
+        std::process::exit(1);
+    }
+}
+
+/// Logs which images of `inpath` were downsampled in `outpath`, for `--explain`.
+fn explain_images(inpath: &str, outpath: &std::path::Path) {
+    let images = probe_images(inpath).and_then(|input| Ok((input, probe_images(outpath)?)));
+    match images {
+        Ok((input, output)) => {
+            let diffs = diff_images(&input, &output);
+            for diff in &diffs {
+                info!("  {}", diff);
+            }
+            info!("Images of {:?}: {}", inpath, ImageChanges::count(&diffs));
+        }
+        Err(e) => warn!(
+            "Cannot list the images of {:?} to explain them: {}",
+            inpath, e
+        ),
+    }
+}
+
+/// Logs that `inpath` was skipped, with a warning if the user likely expected it to be shrunk.
+fn log_skip(inpath: &str, reason: SkipReason) {
+    match reason {
+        SkipReason::NoPdfHeader | SkipReason::Symlink => {
+            warn!("Skipping {:?}: {}", inpath, skip_message(reason))
+        }
+        _ => info!("Skipping {:?}: {}", inpath, skip_message(reason)),
+    }
+}
+
+/// Human readable explanation of why a file was skipped.
+fn skip_message(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::NotAPdf => "not a PDF file",
+        SkipReason::NoPdfHeader => "not a PDF file, despite the extension .pdf",
+        SkipReason::TooSmall => "smaller than --min-size",
+        SkipReason::TooLarge => "larger than --max-size",
+        SkipReason::AlreadyShrunk => "already shrunk by a previous run",
+        SkipReason::NotMarked => "not shrunk by a previous run with --mark",
+        SkipReason::OutputExists => "the output already exists",
+        SkipReason::OutputLarger => "the output was larger than the input",
+        SkipReason::UpToDate => "the output is up to date",
+        SkipReason::PreProcessFailed => "the pre-processing command failed",
+        SkipReason::Checkpointed => "completed according to --checkpoint",
+        SkipReason::Symlink => "it is a symbolic link and --no-follow-input-symlinks is set",
+        SkipReason::Duplicate => {
+            "an identical output is already in the --content-address directory"
         }
     }
 }
@@ -245,9 +1877,79 @@ fn set_up_logging(verbose: bool) {
 }
 */
 
-fn set_up_env_logger(verbose: bool) {
+/// Style of the log for `--color <always|never|auto>`.
+///
+/// With `auto` the output is colored only if it is a terminal (`is_tty`)
+/// and the environment variable `NO_COLOR` is not set (`no_color`).
+fn write_style(color: &str, is_tty: bool, no_color: bool) -> env_logger::WriteStyle {
+    use env_logger::WriteStyle;
+    match color {
+        "always" => WriteStyle::Always,
+        "never" => WriteStyle::Never,
+        _ if is_tty && !no_color => WriteStyle::Always,
+        _ => WriteStyle::Never,
+    }
+}
+
+/// Destination of the log for `--log <stderr|stdout|FILE>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LogSink {
+    Stderr,
+    Stdout,
+    File(std::path::PathBuf),
+}
+
+impl LogSink {
+    fn parse(sink: &std::ffi::OsStr) -> LogSink {
+        match sink.to_str() {
+            Some("stderr") => LogSink::Stderr,
+            Some("stdout") => LogSink::Stdout,
+            _ => LogSink::File(sink.into()),
+        }
+    }
+
+    /// Stream the logger writes to; a file is written by the format instead,
+    /// since `env_logger` can only target the standard streams.
+    fn target(&self) -> env_logger::Target {
+        match self {
+            LogSink::Stdout => env_logger::Target::Stdout,
+            LogSink::Stderr | LogSink::File(_) => env_logger::Target::Stderr,
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        use std::io::IsTerminal;
+        match self {
+            LogSink::Stderr => std::io::stderr().is_terminal(),
+            LogSink::Stdout => std::io::stdout().is_terminal(),
+            LogSink::File(_) => false,
+        }
+    }
+
+    /// Opens the log file in append mode, so that the logs of several runs accumulate.
+    fn open(&self) -> std::io::Result<Option<std::fs::File>> {
+        match self {
+            LogSink::File(path) => std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+}
+
+fn set_up_env_logger(
+    verbose: bool,
+    write_style: env_logger::WriteStyle,
+    target: env_logger::Target,
+    file: Option<std::fs::File>,
+) {
     use std::io::Write;
+    let file = file.map(std::sync::Mutex::new);
     env_logger::Builder::new()
+        .write_style(write_style)
+        .target(target)
         .filter_level(if verbose {
             log::LevelFilter::Trace
         } else {
@@ -255,7 +1957,7 @@ fn set_up_env_logger(verbose: bool) {
         })
         .format_timestamp(None)
         .format_module_path(false)
-        .format(|buf, record| {
+        .format(move |buf, record| {
             use env_logger::fmt::Color::*;
             use log::Level::*;
 
@@ -277,6 +1979,10 @@ fn set_up_env_logger(verbose: bool) {
                 level_style.clone()
             };
 
+            if let Some(file) = &file {
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                return writeln!(file, "[{:>5}] {}", record.level(), record.args());
+            }
             writeln!(
                 buf,
                 "{}{level:>5}{} {message}",
@@ -288,3 +1994,234 @@ fn set_up_env_logger(verbose: bool) {
         })
         .init();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger::WriteStyle;
+
+    #[test]
+    fn test_version_text() {
+        let crate_line = format!("pdfshrink {}\n", env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            version_text(Ok((10, 2))),
+            format!("{}gs: 10.02\n", crate_line)
+        );
+        let missing = ShrinkError::GhostscriptNotFound {
+            tried: vec!["gs".into()],
+        };
+        assert_eq!(
+            version_text(Err(missing)),
+            format!("{}gs: not found\n", crate_line)
+        );
+        let garbled = ShrinkError::InvalidVersion { output: "?".into() };
+        assert!(version_text(Err(garbled))
+            .ends_with("gs: unknown (cannot parse the Ghostscript version \"?\")\n"));
+    }
+
+    #[test]
+    fn test_expand_arg_files() {
+        let dir = std::env::temp_dir().join(format!("pdfshrink-argfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, content: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, content).unwrap();
+            format!("@{}", path.display())
+        };
+        let expand = |args: &[&str]| {
+            expand_arg_files(args.iter().map(OsString::from)).map(|args| {
+                args.into_iter()
+                    .map(|a| a.into_string().unwrap())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let inner = file("inner", "--mark\n'my file.pdf'\n");
+        let outer = file(
+            "outer",
+            &format!("-v --dpi \"mono=300 dpi\"\n{}\nb.pdf", inner),
+        );
+        assert_eq!(
+            expand(&["pdfshrink", "a.pdf", &outer, "@"]).unwrap(),
+            [
+                "pdfshrink",
+                "a.pdf",
+                "-v",
+                "--dpi",
+                "mono=300 dpi",
+                "--mark",
+                "my file.pdf",
+                "b.pdf",
+                "@"
+            ]
+        );
+        // the program name is not expanded
+        assert_eq!(expand(&[&outer]).unwrap(), [outer.as_str()]);
+
+        let missing = format!("@{}", dir.join("missing").display());
+        assert!(expand(&["pdfshrink", &missing])
+            .unwrap_err()
+            .starts_with("cannot read the argument file"));
+        let unterminated = file("unterminated", "'a.pdf");
+        assert!(expand(&["pdfshrink", &unterminated]).is_err());
+        let cycle = dir.join("cycle");
+        std::fs::write(&cycle, format!("@{}", cycle.display())).unwrap();
+        assert!(expand(&["pdfshrink", &format!("@{}", cycle.display())])
+            .unwrap_err()
+            .starts_with("too many nested"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        assert_eq!(read_ahead("buffer", true), usize::MAX);
+        assert_eq!(read_ahead("stream", false), 0);
+        assert_eq!(read_ahead("auto", true), 0);
+        assert_eq!(read_ahead("auto", false), INPUT_LIST_READ_AHEAD);
+    }
+
+    #[test]
+    fn test_read_paths() {
+        let list = "a.pdf\r\n\nb c.pdf\n  \nd.pdf";
+        // buffered: the whole list and its total
+        let (paths, ended) = read_paths(&mut list.as_bytes(), usize::MAX).unwrap();
+        assert_eq!(paths, ["a.pdf", "b c.pdf", "d.pdf"]);
+        assert!(ended);
+
+        // a long list is streamed after the first lines
+        let mut reader = list.as_bytes();
+        assert_eq!(
+            read_paths(&mut reader, 2).unwrap(),
+            (vec!["a.pdf".into(), "b c.pdf".into()], false)
+        );
+        assert_eq!(
+            read_paths(&mut reader, 1).unwrap(),
+            (vec!["d.pdf".into()], false)
+        );
+        assert_eq!(read_paths(&mut reader, 1).unwrap(), (vec![], true));
+        assert_eq!(read_paths(&mut "".as_bytes(), 0).unwrap(), (vec![], false));
+    }
+
+    #[test]
+    fn test_for_each_ordered() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        for &threads in &[1, 3, 16] {
+            let running = AtomicUsize::new(0);
+            let most = AtomicUsize::new(0);
+            let mut results = Vec::new();
+            for_each_ordered(
+                threads,
+                (0..10).collect(),
+                |i, item: u64| {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    // the first items finish last
+                    std::thread::sleep(Duration::from_millis(20 - 2 * item));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    (i, item * item)
+                },
+                |result| results.push(result),
+            );
+            let expected: Vec<(usize, u64)> = (0..10).map(|i| (i, (i * i) as u64)).collect();
+            assert_eq!(results, expected);
+            assert!(most.into_inner() <= threads.min(10));
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut summary = BatchSummary::default();
+        summary.record(&Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+            input: "a.pdf".into(),
+            output: "a.shrunk.pdf".into(),
+            input_bytes: 100,
+            output_bytes: 101,
+            status: ExitClass::Success,
+        })));
+        assert_eq!(exit_code(&summary, false), 0);
+        assert_eq!(exit_code(&summary, true), 3);
+        assert_eq!(exit_code(&BatchSummary::default(), true), 0);
+    }
+
+    #[test]
+    fn test_write_path() {
+        let mut out = Vec::new();
+        write_path(&mut out, "dir/a b.pdf".as_ref(), b'\n').unwrap();
+        write_path(&mut out, "c.pdf".as_ref(), b'\0').unwrap();
+        assert_eq!(out, b"dir/a b.pdf\nc.pdf\0");
+    }
+
+    #[test]
+    fn test_log_sink() {
+        use env_logger::Target;
+        use std::io::Write;
+
+        let sink = |arg: &str| LogSink::parse(arg.as_ref());
+        assert_eq!(sink("stderr"), LogSink::Stderr);
+        assert_eq!(sink("stdout"), LogSink::Stdout);
+        assert_eq!(sink("run.log"), LogSink::File("run.log".into()));
+        let target = |arg: &str| format!("{:?}", sink(arg).target());
+        assert_eq!(target("stdout"), format!("{:?}", Target::Stdout));
+        assert_eq!(target("stderr"), format!("{:?}", Target::Stderr));
+        assert!(!sink("run.log").is_terminal());
+        assert!(sink("stdout").open().unwrap().is_none());
+
+        // the log file is appended to
+        let path = std::env::temp_dir().join(format!("pdfshrink-log-{}", std::process::id()));
+        std::fs::write(&path, "first\n").unwrap();
+        let sink = LogSink::File(path.clone());
+        writeln!(sink.open().unwrap().unwrap(), "second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_budget() {
+        let start = Instant::now();
+        let budget = Budget {
+            start,
+            max: Duration::from_secs(2 * 3600),
+        };
+        let at = |secs| budget.remaining(start + Duration::from_secs(secs));
+        assert_eq!(at(0), Some(Duration::from_secs(7200)));
+        assert_eq!(at(5400), Some(Duration::from_secs(1800)));
+        assert_eq!(at(7199), Some(Duration::from_secs(1)));
+        assert_eq!(at(7200), None);
+        assert_eq!(at(10000), None);
+        let empty = Budget {
+            start,
+            max: Duration::ZERO,
+        };
+        assert_eq!(empty.remaining(start), None);
+    }
+
+    #[test]
+    fn test_write_style() {
+        let style = |color, is_tty, no_color| format!("{:?}", write_style(color, is_tty, no_color));
+        for &(is_tty, no_color) in &[(false, false), (false, true), (true, false), (true, true)] {
+            assert_eq!(
+                style("always", is_tty, no_color),
+                format!("{:?}", WriteStyle::Always)
+            );
+            assert_eq!(
+                style("never", is_tty, no_color),
+                format!("{:?}", WriteStyle::Never)
+            );
+        }
+        assert_eq!(
+            style("auto", true, false),
+            format!("{:?}", WriteStyle::Always)
+        );
+        assert_eq!(
+            style("auto", false, false),
+            format!("{:?}", WriteStyle::Never)
+        );
+        assert_eq!(
+            style("auto", true, true),
+            format!("{:?}", WriteStyle::Never)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! Marker recording in a PDF the settings it was shrunk with.
+//!
+//! The marker is an entry `/PdfShrink (<settings>)` of the document information
+//! dictionary, written through a `DOCINFO` pdfmark together with an entry
+//! `/PdfShrinkHash (<hash>)` holding the [`settings_hash`] of the settings.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::ps_string;
+
+/// Key of the document information entry holding the marker.
+pub const MARKER_KEY: &str = "PdfShrink";
+
+/// Key of the document information entry holding the [`settings_hash`] of the marker.
+pub const SETTINGS_HASH_KEY: &str = "PdfShrinkHash";
+
+/// Hash identifying `settings`, as 16 hexadecimal digits.
+///
+/// This is the 64-bit FNV-1a hash of the string, which does not depend on the platform
+/// nor on the version of Rust, so that it can be compared with the hash stored by a previous run.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::settings_hash;
+/// assert_eq!(settings_hash(""), "cbf29ce484222325");
+/// assert_ne!(settings_hash("-r150"), settings_hash("-r300"));
+/// ```
+pub fn settings_hash(settings: &str) -> String {
+    let hash = settings.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// PostScript code writing `settings` and their [`settings_hash`] as the marker of the output.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::marker_pdfmark;
+/// assert_eq!(
+///     marker_pdfmark("a b"),
+///     "[ /PdfShrink (a b) /PdfShrinkHash (e63f991904833892) /DOCINFO pdfmark"
+/// );
+/// ```
+pub fn marker_pdfmark(settings: &str) -> String {
+    format!(
+        "[ /{} {} /{} ({}) /DOCINFO pdfmark",
+        MARKER_KEY,
+        ps_string(settings),
+        SETTINGS_HASH_KEY,
+        settings_hash(settings)
+    )
+}
+
+/// Parses the PDF literal string at the start of `bytes`, returning its content.
+fn parse_literal(bytes: &[u8]) -> Option<String> {
+    let mut bytes = bytes.iter().copied();
+    if bytes.next()? != b'(' {
+        return None;
+    }
+    let mut value = Vec::new();
+    let mut depth = 0;
+    loop {
+        match bytes.next()? {
+            b'\\' => value.push(bytes.next()?),
+            b'(' => {
+                depth += 1;
+                value.push(b'(');
+            }
+            b')' if depth == 0 => break,
+            b')' => {
+                depth -= 1;
+                value.push(b')');
+            }
+            b => value.push(b),
+        }
+    }
+    String::from_utf8(value).ok()
+}
+
+/// Finds the value of the last entry `key` in `bytes`.
+///
+/// A longer key starting with `key`, such as [`SETTINGS_HASH_KEY`] for [`MARKER_KEY`],
+/// is not followed by a string, so it is not mistaken for it.
+fn find_entry(bytes: &[u8], key: &str) -> Option<String> {
+    let key = format!("/{}", key);
+    let key = key.as_bytes();
+    bytes
+        .windows(key.len())
+        .enumerate()
+        .rev()
+        .filter(|(_, w)| *w == key)
+        .find_map(|(i, _)| {
+            let rest = &bytes[i + key.len()..];
+            let start = rest.iter().position(|b| !b.is_ascii_whitespace())?;
+            parse_literal(&rest[start..])
+        })
+}
+
+/// Maximum length of a marker which is guaranteed to be found by [`read_marker`].
+const MAX_MARKER_LEN: usize = 4096;
+
+/// Reads the marker of `path`, if any.
+pub fn read_marker<P>(path: P) -> io::Result<Option<String>>
+where
+    P: AsRef<Path>,
+{
+    let marker = read_entry(path.as_ref(), MARKER_KEY)?;
+    #[cfg(feature = "logging")]
+    trace!("read_marker({:?}) = {:?}", path.as_ref(), marker);
+    Ok(marker)
+}
+
+/// Reads the settings hash stored with the marker of `path`, if any.
+///
+/// Files marked before the hash was introduced only have the settings:
+/// use [`settings_hash`] on their [`read_marker`] instead.
+pub fn read_settings_hash<P>(path: P) -> io::Result<Option<String>>
+where
+    P: AsRef<Path>,
+{
+    let hash = read_entry(path.as_ref(), SETTINGS_HASH_KEY)?;
+    #[cfg(feature = "logging")]
+    trace!("read_settings_hash({:?}) = {:?}", path.as_ref(), hash);
+    Ok(hash)
+}
+
+/// Reads the value of the last entry `key` of `path`.
+fn read_entry(path: &Path, key: &str) -> io::Result<Option<String>> {
+    let mut file = File::open(path)?;
+    let mut buf = Vec::new();
+    let mut chunk = vec![0; 1 << 16];
+    let mut marker = None;
+    loop {
+        let n = file.read(&mut chunk)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(m) = find_entry(&buf, key) {
+            marker = Some(m);
+        }
+        // keep enough bytes to find a marker straddling two chunks
+        let keep = buf.len().min(MAX_MARKER_LEN);
+        buf.drain(..buf.len() - keep);
+    }
+    Ok(marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_find_marker() {
+        assert_eq!(find_entry(b"/Producer (GPL Ghostscript)", MARKER_KEY), None);
+        assert_eq!(
+            find_entry(b"<< /PdfShrink (-dX=1 \\(a\\) (b)) >>", MARKER_KEY),
+            Some("-dX=1 (a) (b)".into())
+        );
+        assert_eq!(
+            find_entry(b"/PdfShrink(old) ... /PdfShrink\n(new)", MARKER_KEY),
+            Some("new".into())
+        );
+        assert_eq!(find_entry(b"/PdfShrink (unterminated", MARKER_KEY), None);
+
+        let info = b"<< /PdfShrink (-r150) /PdfShrinkHash (0123456789abcdef) >>";
+        assert_eq!(find_entry(info, MARKER_KEY), Some("-r150".into()));
+        assert_eq!(
+            find_entry(info, SETTINGS_HASH_KEY),
+            Some("0123456789abcdef".into())
+        );
+    }
+
+    #[test]
+    fn test_read_marker() {
+        let dir = TempDir::new();
+        let path = dir.path().join("doc.pdf");
+
+        std::fs::write(&path, b"%PDF-1.4\n%%EOF\n").unwrap();
+        assert_eq!(read_marker(&path).unwrap(), None);
+
+        // place the marker across the boundary of the first chunk
+        let mut bytes = b"%PDF-1.4\n".to_vec();
+        bytes.resize((1 << 16) - 5, b'x');
+        bytes.extend_from_slice(b"/PdfShrink (settings)\n%%EOF\n");
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(read_marker(&path).unwrap(), Some("settings".into()));
+        assert_eq!(read_settings_hash(&path).unwrap(), None);
+
+        let mut bytes = b"%PDF-1.4\n".to_vec();
+        bytes.extend_from_slice(marker_pdfmark("settings").as_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(read_marker(&path).unwrap(), Some("settings".into()));
+        assert_eq!(
+            read_settings_hash(&path).unwrap(),
+            Some(settings_hash("settings"))
+        );
+    }
+}
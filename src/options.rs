@@ -0,0 +1,1115 @@
+//! Options controlling the Ghostscript invocation.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::{marker_pdfmark, settings_hash, Hook, Watermark};
+
+/// Predefined Ghostscript settings (`-dPDFSETTINGS`), from the smallest output to the highest quality.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::PdfSettings;
+/// let settings: PdfSettings = "screen".parse().unwrap();
+/// assert_eq!(settings, PdfSettings::Screen);
+/// assert_eq!(settings.as_str(), "/screen");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PdfSettings {
+    /// Low resolution, for viewing on screen and sending by email.
+    Screen,
+    /// Medium resolution, for e-book readers.
+    #[default]
+    Ebook,
+    /// High resolution, for printing.
+    Printer,
+    /// High resolution preserving the colors, for prepress.
+    Prepress,
+    /// Ghostscript's general purpose defaults.
+    Default,
+}
+
+impl PdfSettings {
+    /// The value of `-dPDFSETTINGS`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PdfSettings::Screen => "/screen",
+            PdfSettings::Ebook => "/ebook",
+            PdfSettings::Printer => "/printer",
+            PdfSettings::Prepress => "/prepress",
+            PdfSettings::Default => "/default",
+        }
+    }
+
+    /// Resolutions the color, gray and monochrome images are downsampled to by these settings.
+    pub fn implied_resolutions(self) -> (u32, u32, u32) {
+        match self {
+            PdfSettings::Screen => (72, 72, 300),
+            PdfSettings::Ebook => (150, 150, 300),
+            PdfSettings::Printer | PdfSettings::Prepress => (300, 300, 1200),
+            PdfSettings::Default => (72, 72, 300),
+        }
+    }
+}
+
+/// Versions of the PDF format Ghostscript can write, valid as
+/// [`ShrinkOptions::compatibility_level`].
+pub const COMPATIBILITY_LEVELS: [&str; 6] = ["1.3", "1.4", "1.5", "1.6", "1.7", "2.0"];
+
+/// Entries of the document information dictionary cleared by
+/// [`ShrinkOptions::strip_metadata`].
+pub const STRIPPED_METADATA: [&str; 7] = [
+    "Title",
+    "Author",
+    "Subject",
+    "Keywords",
+    "Creator",
+    "CreationDate",
+    "ModDate",
+];
+
+/// How Ghostscript rotates the pages according to the direction of their text (`-dAutoRotatePages`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AutoRotate {
+    /// Keep the orientation of every page.
+    #[default]
+    None,
+    /// Rotate all the pages like the majority of the text in the document.
+    All,
+    /// Rotate each page like the majority of its text.
+    PageByPage,
+}
+
+impl AutoRotate {
+    /// The value of `-dAutoRotatePages`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AutoRotate::None => "/None",
+            AutoRotate::All => "/All",
+            AutoRotate::PageByPage => "/PageByPage",
+        }
+    }
+}
+
+/// Resolution of a type of images contradicting the one implied by the [`PdfSettings`],
+/// see [`ShrinkOptions::resolution_conflicts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolutionConflict {
+    pub settings: PdfSettings,
+    /// `"color"` or `"gray"`.
+    pub image_type: &'static str,
+    pub resolution: u32,
+    pub implied: u32,
+}
+
+impl std::fmt::Display for ResolutionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the {} settings imply {} dpi {} images, but they are downsampled to {} dpi \
+             because an explicit resolution overrides the settings",
+            self.settings.as_str().trim_start_matches('/'),
+            self.implied,
+            self.image_type,
+            self.resolution
+        )
+    }
+}
+
+impl std::str::FromStr for PdfSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PdfSettings, String> {
+        match s.trim_start_matches('/') {
+            "screen" => Ok(PdfSettings::Screen),
+            "ebook" => Ok(PdfSettings::Ebook),
+            "printer" => Ok(PdfSettings::Printer),
+            "prepress" => Ok(PdfSettings::Prepress),
+            "default" => Ok(PdfSettings::Default),
+            _ => Err(format!(
+                "invalid settings {:?}, expected screen, ebook, printer, prepress or default",
+                s
+            )),
+        }
+    }
+}
+
+/// Options used to build the Ghostscript command and to decide which files to process.
+///
+/// The [`Default`] value reproduces the historical behavior of [`gs_command`](crate::gs_command).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShrinkOptions {
+    /// Ghostscript executable, as a path or a name searched in the `PATH`;
+    /// if `None`, the program `gs` is used.
+    ///
+    /// [`shrink_file`](crate::shrink_file) fills this in with [`resolve_ghostscript`](crate::resolve_ghostscript).
+    pub ghostscript: Option<PathBuf>,
+
+    /// Predefined settings the other options refine (`-dPDFSETTINGS`).
+    pub pdf_settings: PdfSettings,
+    /// Version of the PDF format of the output (`-dCompatibilityLevel`), one of the
+    /// [`COMPATIBILITY_LEVELS`].
+    pub compatibility_level: String,
+    /// Rotation of the pages (`-dAutoRotatePages`).
+    pub auto_rotate: AutoRotate,
+    /// First page to process (`-dFirstPage`), 1-based.
+    pub first_page: Option<u32>,
+    /// Last page to process (`-dLastPage`), 1-based and inclusive.
+    pub last_page: Option<u32>,
+    /// Resolution color images are downsampled to, in dots per inch.
+    pub color_resolution: u32,
+    /// Resolution grayscale images are downsampled to, in dots per inch.
+    pub gray_resolution: u32,
+    /// Resolution monochrome images are downsampled to, in dots per inch.
+    pub mono_resolution: u32,
+    /// Downsample the color images; if `false`, they keep their resolution
+    /// (`-dDownsampleColorImages=false`) and [`color_resolution`](Self::color_resolution) is ignored.
+    pub downsample_color: bool,
+    /// Downsample the grayscale images, see [`downsample_color`](Self::downsample_color).
+    pub downsample_gray: bool,
+    /// Downsample the monochrome images, such as scanned text, see
+    /// [`downsample_color`](Self::downsample_color).
+    pub downsample_mono: bool,
+    /// Resolution of the output device (`-r`), in dots per inch.
+    ///
+    /// This is the resolution used to rasterize content which cannot be kept
+    /// as vectors (e.g. some shadings and transparency groups), and is unrelated
+    /// to the resolution embedded images are downsampled to.
+    /// If `None`, the Ghostscript default is used.
+    pub device_resolution: Option<u32>,
+    /// Memory Ghostscript uses to buffer the rendering of a page (`-dBufferSpace`), in bytes.
+    ///
+    /// If `None`, the Ghostscript default is used.
+    /// [`shrink_file`](crate::shrink_file) raises it once when Ghostscript runs out of memory.
+    pub buffer_space: Option<u64>,
+    /// Largest bitmap Ghostscript renders a page into without banding (`-dMaxBitmap`), in bytes.
+    ///
+    /// If `None`, the Ghostscript default is used.
+    pub max_bitmap: Option<u64>,
+    /// Render the interactive form fields into the page content, so that their
+    /// values can no longer be edited (`-dShowAcroForm=true -dPreserveAnnots=false`).
+    ///
+    /// Unlike removing the annotations, this keeps the appearance of the fields.
+    /// Ghostscript flattens the other annotations (such as comments and links) as well.
+    pub flatten_forms: bool,
+    /// Convert all the colors to shades of gray
+    /// (`-sColorConversionStrategy=Gray -dProcessColorModel=/DeviceGray`).
+    ///
+    /// The conversion is lossy: the colors cannot be recovered from the output.
+    pub grayscale: bool,
+    /// Linearize the output for fast web view (`-dFastWebView=true`), so that a browser
+    /// can display the first page before downloading the whole file.
+    ///
+    /// It suits documents served over HTTP, for instance with the `/screen` settings.
+    pub fast_web_view: bool,
+    /// Keep the document outline (bookmarks), which Ghostscript does by default.
+    ///
+    /// If `false`, the outline is dropped (`-dNO_PDFMARK_OUTLINES`).
+    pub preserve_bookmarks: bool,
+    /// Read the input with the new PDF interpreter (`-dNEWPDF`), the default since Ghostscript 9.56.
+    ///
+    /// Either way `pdfwrite` writes a new file from scratch, so the incremental updates
+    /// appended by editors, and the objects they superseded, never reach the output:
+    /// this is often where most of the size of an edited PDF goes.
+    /// If `false`, the legacy interpreter is used (`-dNEWPDF=false`), which may render some
+    /// damaged or unusual files differently; Ghostscript 10.02 and later ignore this.
+    pub full_rewrite: bool,
+
+    /// Skip inputs smaller than this many bytes.
+    pub min_size: Option<u64>,
+    /// Skip inputs larger than this many bytes.
+    pub max_size: Option<u64>,
+    /// Skip inputs named `*.<shrunk_suffix>.pdf` or `*-<shrunk_suffix>.pdf`, produced by a previous run.
+    pub shrunk_suffix: Option<OsString>,
+    /// Skip inputs whose output already exists.
+    pub no_clobber: bool,
+    /// Skip inputs whose output exists and is newer than the input.
+    pub update: bool,
+    /// Discard the output if it is larger than the input.
+    pub keep_smaller: bool,
+    /// When the output is discarded by [`keep_smaller`](Self::keep_smaller), hardlink
+    /// (or copy, across devices) the input in its place.
+    pub link_unchanged: bool,
+    /// Permissions set on the output, such as `0o664` (Unix only, ignored elsewhere).
+    pub output_mode: Option<u32>,
+    /// Give the output directories which have to be created the permissions of the directory
+    /// of the input, see [`ensure_parent_dir_like`](crate::ensure_parent_dir_like)
+    /// (Unix only, ignored elsewhere).
+    pub preserve_dir_mode: bool,
+    /// Record the settings and their hash in the output, see [`settings_string`](Self::settings_string),
+    /// and skip inputs already marked with the same settings.
+    pub marker: bool,
+    /// Shrink again only the inputs marked by a previous run with different settings,
+    /// comparing their [`settings_hash`](Self::settings_hash) with the current one.
+    ///
+    /// Inputs without a marker are skipped. Set [`marker`](Self::marker) as well so that
+    /// the new outputs record the current settings.
+    pub upgrade: bool,
+    /// Clear the entries [`STRIPPED_METADATA`] of the document information dictionary
+    /// (title, author, subject, keywords, creator application and dates), through a
+    /// `DOCINFO` pdfmark setting them to empty strings.
+    ///
+    /// The XMP metadata that Ghostscript writes is generated from the information
+    /// dictionary, so it does not keep them either. The `Producer` is always set by
+    /// Ghostscript, and the [`marker`](Self::marker) is still written.
+    pub strip_metadata: bool,
+
+    /// Command run on the input before shrinking it, see [`Hook`].
+    ///
+    /// The placeholder `{input}` is replaced by the input path.
+    /// If the command fails, the file is skipped.
+    pub pre_process: Option<Hook>,
+    /// Command run on the output after a successful shrink, see [`Hook`].
+    ///
+    /// The placeholder `{output}` is replaced by the output path.
+    pub post_process: Option<Hook>,
+    /// Command checking the output after a successful shrink, such as `qpdf --check`, see [`Hook`].
+    ///
+    /// The placeholder `{output}` is replaced by the output path. If the command fails,
+    /// the output is removed and the shrink fails, before running [`post_process`](Self::post_process).
+    pub validate: Option<Hook>,
+    /// When the output fails [`validate`](Self::validate), copy the input in its place.
+    pub fallback_copy: bool,
+    /// Suffix of the temporary files written when shrinking in place,
+    /// see [`in_place_temp_path_with`](crate::in_place_temp_path_with).
+    pub temp_suffix: OsString,
+    /// When shrinking in place, replace the input even with a larger output,
+    /// see [`shrink_in_place`](crate::shrink_in_place).
+    pub always_replace: bool,
+    /// When shrinking in place an input which is a symbolic link, replace the file it points to;
+    /// if `false`, such inputs are skipped.
+    pub follow_input_symlinks: bool,
+    /// Longest time Ghostscript may take to shrink a file before it is killed and the shrink
+    /// fails with [`ShrinkError::Timeout`](crate::ShrinkError::Timeout).
+    ///
+    /// The default, `None`, waits forever.
+    pub timeout: Option<Duration>,
+
+    /// Text drawn across every page of the output.
+    pub watermark: Option<Watermark>,
+
+    /// Pass `-dBATCH -dNOPAUSE`, so that Ghostscript exits after processing the input
+    /// instead of waiting at each page and at the end.
+    ///
+    /// Disabling this is only meant to debug Ghostscript interactively:
+    /// unattended runs hang forever waiting for input.
+    pub batch: bool,
+
+    /// Directories searched by Ghostscript for resources such as ICC profiles (`-I`), in order.
+    pub include_paths: Vec<PathBuf>,
+    /// Directories searched by Ghostscript for fonts (`-sFONTPATH`).
+    pub font_path: Option<PathBuf>,
+    /// Further arguments for Ghostscript, such as `-dDownsampleColorImages=false`, placed
+    /// after the ones set by the other options and before the output and input files.
+    ///
+    /// They are passed verbatim and are not validated: an argument which Ghostscript does
+    /// not understand, or which names a file, can make every shrink fail or misbehave.
+    pub extra_args: Vec<OsString>,
+}
+
+impl Default for ShrinkOptions {
+    fn default() -> Self {
+        ShrinkOptions {
+            ghostscript: None,
+            pdf_settings: PdfSettings::Ebook,
+            compatibility_level: "1.4".into(),
+            auto_rotate: AutoRotate::None,
+            first_page: None,
+            last_page: None,
+            color_resolution: 135,
+            gray_resolution: 135,
+            mono_resolution: 135,
+            downsample_color: true,
+            downsample_gray: true,
+            downsample_mono: true,
+            device_resolution: None,
+            buffer_space: None,
+            max_bitmap: None,
+            flatten_forms: false,
+            grayscale: false,
+            fast_web_view: false,
+            preserve_bookmarks: true,
+            full_rewrite: true,
+            min_size: None,
+            max_size: None,
+            shrunk_suffix: None,
+            no_clobber: false,
+            update: false,
+            keep_smaller: false,
+            link_unchanged: false,
+            output_mode: None,
+            preserve_dir_mode: false,
+            marker: false,
+            upgrade: false,
+            strip_metadata: false,
+            pre_process: None,
+            post_process: None,
+            validate: None,
+            fallback_copy: false,
+            temp_suffix: crate::DEFAULT_TEMP_SUFFIX.into(),
+            always_replace: false,
+            follow_input_symlinks: true,
+            timeout: None,
+            watermark: None,
+            batch: true,
+            include_paths: Vec::new(),
+            font_path: None,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// Builder methods, to set the options in a chain starting from the [`Default`] ones.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{gs_command, AutoRotate, PdfSettings, ShrinkOptions};
+/// let cmd = ShrinkOptions::default()
+///     .settings(PdfSettings::Screen)
+///     .color_resolution(100)
+///     .compatibility_level("1.7")
+///     .auto_rotate(AutoRotate::PageByPage)
+///     .build_command("in.pdf", "out.pdf");
+/// assert!(cmd.get_args().any(|arg| arg == "-dCompatibilityLevel=1.7"));
+///
+/// let default = ShrinkOptions::default().build_command("in.pdf", "out.pdf");
+/// let historical = gs_command("in.pdf", "out.pdf");
+/// assert!(default.get_args().eq(historical.get_args()));
+/// ```
+impl ShrinkOptions {
+    /// Sets [`ghostscript`](Self::ghostscript) to the executable `path`.
+    pub fn ghostscript<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.ghostscript = Some(path.into());
+        self
+    }
+
+    /// Sets [`pdf_settings`](Self::pdf_settings).
+    pub fn settings(mut self, settings: PdfSettings) -> Self {
+        self.pdf_settings = settings;
+        self
+    }
+
+    /// Sets [`color_resolution`](Self::color_resolution), in dots per inch.
+    pub fn color_resolution(mut self, dpi: u32) -> Self {
+        self.color_resolution = dpi;
+        self
+    }
+
+    /// Sets [`gray_resolution`](Self::gray_resolution), in dots per inch.
+    pub fn gray_resolution(mut self, dpi: u32) -> Self {
+        self.gray_resolution = dpi;
+        self
+    }
+
+    /// Sets [`mono_resolution`](Self::mono_resolution), in dots per inch.
+    pub fn mono_resolution(mut self, dpi: u32) -> Self {
+        self.mono_resolution = dpi;
+        self
+    }
+
+    /// Sets [`compatibility_level`](Self::compatibility_level).
+    pub fn compatibility_level<S>(mut self, level: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.compatibility_level = level.into();
+        self
+    }
+
+    /// Sets [`auto_rotate`](Self::auto_rotate).
+    pub fn auto_rotate(mut self, auto_rotate: AutoRotate) -> Self {
+        self.auto_rotate = auto_rotate;
+        self
+    }
+}
+
+impl ShrinkOptions {
+    /// Arguments passed to Ghostscript which determine how the files are shrunk.
+    ///
+    /// These are the arguments of [`to_gs_args`](Self::to_gs_args) except the ones
+    /// naming the input and output files.
+    pub fn settings_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = vec!["-q".into()];
+        if self.batch {
+            args.push("-dBATCH".into());
+        }
+        args.push("-dSAFER".into());
+        if self.batch {
+            args.push("-dNOPAUSE".into());
+        }
+        args.push("-sDEVICE=pdfwrite".into());
+        args.push(format!("-dCompatibilityLevel={}", self.compatibility_level).into());
+        args.push(format!("-dPDFSETTINGS={}", self.pdf_settings.as_str()).into());
+        args.push(format!("-dAutoRotatePages={}", self.auto_rotate.as_str()).into());
+        for &(kind, downsample, resolution) in &[
+            ("Color", self.downsample_color, self.color_resolution),
+            ("Gray", self.downsample_gray, self.gray_resolution),
+            ("Mono", self.downsample_mono, self.mono_resolution),
+        ] {
+            if downsample {
+                args.push(format!("-d{}ImageDownsampleType=/Bicubic", kind).into());
+                args.push(format!("-d{}ImageResolution={}", kind, resolution).into());
+            } else {
+                args.push(format!("-dDownsample{}Images=false", kind).into());
+            }
+        }
+        if let Some(dpi) = self.device_resolution {
+            args.push(format!("-r{}", dpi).into());
+        }
+        if let Some(bytes) = self.buffer_space {
+            args.push(format!("-dBufferSpace={}", bytes).into());
+        }
+        if let Some(bytes) = self.max_bitmap {
+            args.push(format!("-dMaxBitmap={}", bytes).into());
+        }
+        if self.flatten_forms {
+            args.push("-dShowAcroForm=true".into());
+            args.push("-dPreserveAnnots=false".into());
+        }
+        if self.grayscale {
+            args.push("-sColorConversionStrategy=Gray".into());
+            args.push("-dProcessColorModel=/DeviceGray".into());
+        }
+        if self.fast_web_view {
+            args.push("-dFastWebView=true".into());
+        }
+        if !self.preserve_bookmarks {
+            args.push("-dNO_PDFMARK_OUTLINES".into());
+        }
+        if !self.full_rewrite {
+            args.push("-dNEWPDF=false".into());
+        }
+        for path in &self.include_paths {
+            let mut arg = OsString::from("-I");
+            arg.push(path);
+            args.push(arg);
+        }
+        if let Some(path) = &self.font_path {
+            let mut arg = OsString::from("-sFONTPATH=");
+            arg.push(path);
+            args.push(arg);
+        }
+        if let Some(first) = self.first_page {
+            args.push(format!("-dFirstPage={}", first).into());
+        }
+        if let Some(last) = self.last_page {
+            args.push(format!("-dLastPage={}", last).into());
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    /// Image resolutions at least twice as high or as low as the ones implied
+    /// by [`pdf_settings`](Self::pdf_settings).
+    ///
+    /// The explicit resolutions always win, but such a combination is probably a mistake,
+    /// like asking for the small `/screen` output while keeping the images at 300 dpi.
+    /// Image types which are not downsampled are not checked, and neither are the monochrome
+    /// images, whose resolution is usually chosen for the legibility of scanned text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::{PdfSettings, ShrinkOptions};
+    /// let options = ShrinkOptions {
+    ///     pdf_settings: PdfSettings::Screen,
+    ///     color_resolution: 300,
+    ///     ..Default::default()
+    /// };
+    /// let conflicts = options.resolution_conflicts();
+    /// assert_eq!(conflicts.len(), 1);
+    /// assert_eq!(conflicts[0].image_type, "color");
+    /// ```
+    pub fn resolution_conflicts(&self) -> Vec<ResolutionConflict> {
+        let (color, gray, _) = self.pdf_settings.implied_resolutions();
+        [
+            ("color", self.downsample_color, self.color_resolution, color),
+            ("gray", self.downsample_gray, self.gray_resolution, gray),
+        ]
+        .iter()
+        .filter(|&&(_, downsample, resolution, implied)| {
+            downsample && (resolution >= 2 * implied || 2 * resolution <= implied)
+        })
+        .map(|&(image_type, _, resolution, implied)| ResolutionConflict {
+            settings: self.pdf_settings,
+            image_type,
+            resolution,
+            implied,
+        })
+        .collect()
+    }
+
+    /// The [`settings_args`](Self::settings_args) as a single line.
+    ///
+    /// This identifies the settings in the marker written when [`marker`](Self::marker) is set.
+    pub fn settings_string(&self) -> String {
+        self.settings_args()
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The [`settings_hash`](crate::settings_hash) of the [`settings_string`](Self::settings_string),
+    /// stored in the marker and compared by [`upgrade`](Self::upgrade).
+    pub fn settings_hash(&self) -> String {
+        settings_hash(&self.settings_string())
+    }
+
+    /// Arguments passed to Ghostscript to shrink `inpath` and write to `outpath`.
+    ///
+    /// The paths are passed literally: a `%` in `outpath` is doubled, since Ghostscript
+    /// would otherwise read it as a page number template, and relative paths starting
+    /// with `-` are prefixed with `./` so that they are not mistaken for switches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::ShrinkOptions;
+    /// let options = ShrinkOptions {
+    ///     last_page: Some(3),
+    ///     ..Default::default()
+    /// };
+    /// let args = options.to_gs_args("in.pdf", "out.pdf");
+    /// assert!(args.contains(&"-dLastPage=3".into()));
+    /// assert_eq!(args.last(), Some(&"in.pdf".into()));
+    /// ```
+    pub fn to_gs_args<P, Q>(&self, inpath: P, outpath: Q) -> Vec<OsString>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let mut args = self.settings_args();
+        let mut output_file = OsString::from("-sOutputFile=");
+        output_file.push(escape_output_file(&guard_dash(outpath.as_ref())));
+        args.push(output_file);
+        if let Some(watermark) = &self.watermark {
+            // the EndPage procedure must be installed before the input is processed
+            args.push("-c".into());
+            args.push(watermark.postscript().into());
+            args.push("-f".into());
+        }
+        args.push(guard_dash(inpath.as_ref()).into_os_string());
+        if self.strip_metadata {
+            // like the marker, the pdfmark must follow the input
+            let entries: Vec<String> = STRIPPED_METADATA
+                .iter()
+                .map(|key| format!("/{} ()", key))
+                .collect();
+            args.push("-c".into());
+            args.push(format!("[ {} /DOCINFO pdfmark", entries.join(" ")).into());
+        }
+        if self.marker {
+            // the pdfmark must follow the input to override its information dictionary
+            args.push("-c".into());
+            args.push(marker_pdfmark(&self.settings_string()).into());
+        }
+        args
+    }
+
+    /// Ghostscript command to shrink `inpath` and write to `outpath`.
+    ///
+    /// The program is [`ghostscript`](Self::ghostscript), falling back to `gs`.
+    pub fn build_command<P, Q>(&self, inpath: P, outpath: Q) -> Command
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (program, args) = crate::gs_argv(inpath, outpath, self);
+        let mut cmd = Command::new(program);
+        cmd.args(args);
+        cmd
+    }
+
+    /// Command to simulate [`build_command`](Self::build_command), with the same arguments.
+    ///
+    /// This command requires a program `args` which diagnoses the command line,
+    /// see [`dry_run_command`](crate::dry_run_command).
+    pub fn build_dry_run_command<P, Q>(&self, inpath: P, outpath: Q) -> Command
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let mut cmd = Command::new("args");
+        cmd.args(self.to_gs_args(inpath, outpath));
+        cmd
+    }
+}
+
+/// Prefixes `path` with `./` if it starts with `-`.
+fn guard_dash(path: &Path) -> PathBuf {
+    if path.to_string_lossy().starts_with('-') {
+        Path::new(".").join(path)
+    } else {
+        path.into()
+    }
+}
+
+/// Doubles the `%` in `path`, so that Ghostscript reads them literally in `-sOutputFile`.
+///
+/// The path is escaped even if it is not valid Unicode.
+fn escape_output_file(path: &Path) -> OsString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        let mut escaped = Vec::new();
+        for &b in path.as_os_str().as_bytes() {
+            if b == b'%' {
+                escaped.push(b'%');
+            }
+            escaped.push(b);
+        }
+        OsString::from_vec(escaped)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::{OsStrExt, OsStringExt};
+        let mut escaped = Vec::new();
+        for w in path.as_os_str().encode_wide() {
+            if w == u16::from(b'%') {
+                escaped.push(w);
+            }
+            escaped.push(w);
+        }
+        OsString::from_wide(&escaped)
+    }
+    #[cfg(not(any(unix, windows)))]
+    match path.to_str() {
+        Some(s) => s.replace('%', "%%").into(),
+        None => path.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(options: &ShrinkOptions) -> Vec<String> {
+        options
+            .to_gs_args("in.pdf", "out.pdf")
+            .into_iter()
+            .map(|a| a.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_default_args() {
+        let args = args(&ShrinkOptions::default());
+        assert_eq!(args.len(), 16);
+        assert_eq!(args[14], "-sOutputFile=out.pdf");
+        assert_eq!(args[15], "in.pdf");
+        assert!(!args.iter().any(|a| a.contains("Page=")));
+    }
+
+    #[test]
+    fn test_image_resolution_args() {
+        let options = ShrinkOptions {
+            color_resolution: 100,
+            gray_resolution: 150,
+            mono_resolution: 300,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[8], "-dColorImageDownsampleType=/Bicubic");
+        assert_eq!(args[9], "-dColorImageResolution=100");
+        assert_eq!(args[11], "-dGrayImageResolution=150");
+        assert_eq!(args[13], "-dMonoImageResolution=300");
+    }
+
+    #[test]
+    fn test_downsample_args() {
+        let options = ShrinkOptions {
+            downsample_mono: false,
+            ..Default::default()
+        };
+        let a = args(&options);
+        assert_eq!(a[9], "-dColorImageResolution=135");
+        assert_eq!(a[11], "-dGrayImageResolution=135");
+        assert_eq!(a[12], "-dDownsampleMonoImages=false");
+        assert_eq!(a[13], "-sOutputFile=out.pdf");
+        assert!(!a.iter().any(|a| a.starts_with("-dMonoImage")));
+
+        let options = ShrinkOptions {
+            downsample_color: false,
+            ..Default::default()
+        };
+        let a = args(&options);
+        assert_eq!(a[8], "-dDownsampleColorImages=false");
+        assert_eq!(a[9], "-dGrayImageDownsampleType=/Bicubic");
+        assert!(!a.iter().any(|a| a.starts_with("-dColorImage")));
+        assert_eq!(a.len(), 15);
+
+        let options = ShrinkOptions {
+            downsample_gray: false,
+            ..Default::default()
+        };
+        let a = args(&options);
+        assert_eq!(a[10], "-dDownsampleGrayImages=false");
+        assert!(!a.iter().any(|a| a.starts_with("-dGrayImage")));
+        assert!(a.contains(&"-dMonoImageResolution=135".to_string()));
+        assert_eq!(a.len(), 15);
+    }
+
+    #[test]
+    fn test_batch_args() {
+        let a = args(&ShrinkOptions::default());
+        assert_eq!(a[1], "-dBATCH");
+        assert_eq!(a[3], "-dNOPAUSE");
+
+        let options = ShrinkOptions {
+            batch: false,
+            ..Default::default()
+        };
+        let a = args(&options);
+        assert_eq!(a[..3], ["-q", "-dSAFER", "-sDEVICE=pdfwrite"]);
+        assert!(!a.iter().any(|a| a == "-dBATCH" || a == "-dNOPAUSE"));
+        assert_eq!(a.len(), 14);
+    }
+
+    #[test]
+    fn test_pdf_settings_args() {
+        assert_eq!(args(&ShrinkOptions::default())[6], "-dPDFSETTINGS=/ebook");
+        for &(name, expected) in &[
+            ("screen", "-dPDFSETTINGS=/screen"),
+            ("printer", "-dPDFSETTINGS=/printer"),
+            ("/prepress", "-dPDFSETTINGS=/prepress"),
+            ("default", "-dPDFSETTINGS=/default"),
+        ] {
+            let options = ShrinkOptions {
+                pdf_settings: name.parse().unwrap(),
+                ..Default::default()
+            };
+            assert_eq!(args(&options)[6], expected);
+        }
+        assert!("Screen".parse::<PdfSettings>().is_err());
+    }
+
+    #[test]
+    fn test_builder() {
+        let options = ShrinkOptions::default()
+            .settings(PdfSettings::Printer)
+            .color_resolution(100)
+            .gray_resolution(110)
+            .mono_resolution(300)
+            .compatibility_level("1.7")
+            .auto_rotate(AutoRotate::All);
+        assert_eq!(
+            options,
+            ShrinkOptions {
+                pdf_settings: PdfSettings::Printer,
+                color_resolution: 100,
+                gray_resolution: 110,
+                mono_resolution: 300,
+                compatibility_level: "1.7".into(),
+                auto_rotate: AutoRotate::All,
+                ..Default::default()
+            }
+        );
+        let a = args(&options);
+        assert_eq!(a[5], "-dCompatibilityLevel=1.7");
+        assert_eq!(a[6], "-dPDFSETTINGS=/printer");
+        assert_eq!(a[7], "-dAutoRotatePages=/All");
+        assert!(a.contains(&"-dGrayImageResolution=110".into()));
+
+        assert_eq!(
+            ShrinkOptions::default().to_gs_args("in.pdf", "out.pdf"),
+            crate::gs_command("in.pdf", "out.pdf")
+                .get_args()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_resolution_conflicts() {
+        let conflicts = |pdf_settings, resolution| {
+            ShrinkOptions {
+                pdf_settings,
+                color_resolution: resolution,
+                gray_resolution: resolution,
+                mono_resolution: resolution,
+                ..Default::default()
+            }
+            .resolution_conflicts()
+            .iter()
+            .map(|c| c.image_type)
+            .collect::<Vec<_>>()
+        };
+        assert!(ShrinkOptions::default().resolution_conflicts().is_empty());
+        assert_eq!(conflicts(PdfSettings::Screen, 300), ["color", "gray"]);
+        assert_eq!(conflicts(PdfSettings::Screen, 100), [] as [&str; 0]);
+        assert_eq!(conflicts(PdfSettings::Printer, 72), ["color", "gray"]);
+        assert_eq!(conflicts(PdfSettings::Ebook, 300), ["color", "gray"]);
+        assert_eq!(conflicts(PdfSettings::Prepress, 600), ["color", "gray"]);
+        assert_eq!(conflicts(PdfSettings::Prepress, 200), [] as [&str; 0]);
+
+        let options = ShrinkOptions {
+            pdf_settings: PdfSettings::Screen,
+            color_resolution: 300,
+            downsample_color: false,
+            ..Default::default()
+        };
+        assert!(options.resolution_conflicts().is_empty());
+
+        let conflict = ResolutionConflict {
+            settings: PdfSettings::Screen,
+            image_type: "color",
+            resolution: 300,
+            implied: 72,
+        };
+        assert!(conflict
+            .to_string()
+            .starts_with("the screen settings imply 72 dpi color images, but"));
+    }
+
+    #[test]
+    fn test_page_range_args() {
+        let options = ShrinkOptions {
+            first_page: Some(2),
+            last_page: Some(5),
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dFirstPage=2");
+        assert_eq!(args[15], "-dLastPage=5");
+        assert_eq!(args[16], "-sOutputFile=out.pdf");
+    }
+
+    #[test]
+    fn test_marker_args() {
+        let options = ShrinkOptions {
+            marker: true,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[15], "in.pdf");
+        assert_eq!(args[16], "-c");
+        assert!(args[17].starts_with("[ /PdfShrink (-q -dBATCH "));
+        assert!(args[17].ends_with(&format!(
+            " -dMonoImageResolution=135) /PdfShrinkHash ({}) /DOCINFO pdfmark",
+            options.settings_hash()
+        )));
+    }
+
+    #[test]
+    fn test_strip_metadata_args() {
+        assert!(!args(&ShrinkOptions::default())
+            .iter()
+            .any(|a| a.contains("DOCINFO")));
+        let options = ShrinkOptions {
+            strip_metadata: true,
+            marker: true,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[15], "in.pdf");
+        assert_eq!(args[16], "-c");
+        assert_eq!(
+            args[17],
+            "[ /Title () /Author () /Subject () /Keywords () /Creator () \
+             /CreationDate () /ModDate () /DOCINFO pdfmark"
+        );
+        // the marker is written after clearing the rest
+        assert_eq!(args[18], "-c");
+        assert!(args[19].starts_with("[ /PdfShrink ("));
+        assert_eq!(args.len(), 20);
+    }
+
+    #[test]
+    fn test_flatten_forms_args() {
+        assert!(!args(&ShrinkOptions::default())
+            .iter()
+            .any(|a| a.contains("AcroForm") || a.contains("Annots")));
+        let options = ShrinkOptions {
+            flatten_forms: true,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dShowAcroForm=true");
+        assert_eq!(args[15], "-dPreserveAnnots=false");
+    }
+
+    #[test]
+    fn test_grayscale_args() {
+        assert!(!args(&ShrinkOptions::default())
+            .iter()
+            .any(|a| a.contains("ColorConversion") || a.contains("ProcessColorModel")));
+        let options = ShrinkOptions {
+            grayscale: true,
+            flatten_forms: true,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[16], "-sColorConversionStrategy=Gray");
+        assert_eq!(args[17], "-dProcessColorModel=/DeviceGray");
+        assert_eq!(args[18], "-sOutputFile=out.pdf");
+    }
+
+    #[test]
+    fn test_fast_web_view_args() {
+        assert!(!args(&ShrinkOptions::default())
+            .iter()
+            .any(|a| a.contains("FastWebView")));
+        let options = ShrinkOptions {
+            fast_web_view: true,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dFastWebView=true");
+        assert_eq!(args[15], "-sOutputFile=out.pdf");
+    }
+
+    #[test]
+    fn test_extra_args() {
+        let options = ShrinkOptions {
+            extra_args: vec![
+                "-dDownsampleColorImages=false".into(),
+                "-dNOINTERPOLATE".into(),
+            ],
+            last_page: Some(5),
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dLastPage=5");
+        assert_eq!(args[15], "-dDownsampleColorImages=false");
+        assert_eq!(args[16], "-dNOINTERPOLATE");
+        assert_eq!(args[17], "-sOutputFile=out.pdf");
+        assert_eq!(args[18], "in.pdf");
+        assert!(options.settings_string().ends_with(" -dNOINTERPOLATE"));
+    }
+
+    #[test]
+    fn test_bookmarks_args() {
+        let options = ShrinkOptions::default();
+        assert!(options.preserve_bookmarks);
+        assert!(!args(&options).iter().any(|a| a.contains("OUTLINES")));
+
+        let options = ShrinkOptions {
+            preserve_bookmarks: false,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dNO_PDFMARK_OUTLINES");
+        assert_eq!(args.len(), 17);
+    }
+
+    #[test]
+    fn test_full_rewrite_args() {
+        let options = ShrinkOptions::default();
+        assert!(options.full_rewrite);
+        assert!(!args(&options).iter().any(|a| a.contains("NEWPDF")));
+
+        let options = ShrinkOptions {
+            full_rewrite: false,
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-dNEWPDF=false");
+        assert_eq!(args.len(), 17);
+    }
+
+    #[test]
+    fn test_device_resolution_args() {
+        let options = ShrinkOptions {
+            device_resolution: Some(300),
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args.iter().filter(|a| a.starts_with("-r")).count(), 1);
+        assert_eq!(args[14], "-r300");
+    }
+
+    #[test]
+    fn test_special_paths_args() {
+        let options = ShrinkOptions::default();
+        let args = |inpath: &str, outpath: &str| -> Vec<String> {
+            options
+                .to_gs_args(inpath, outpath)
+                .into_iter()
+                .map(|a| a.into_string().unwrap())
+                .collect()
+        };
+
+        let a = args("100%.pdf", "out/100%.pdf");
+        assert_eq!(a[14], "-sOutputFile=out/100%%.pdf");
+        assert_eq!(a[15], "100%.pdf");
+
+        let a = args("-in.pdf", "-out %d.pdf");
+        assert_eq!(a[14], "-sOutputFile=./-out %%d.pdf");
+        assert_eq!(a[15], "./-in.pdf");
+
+        let a = args("dir/-in.pdf", "/tmp/-out.pdf");
+        assert_eq!(a[14], "-sOutputFile=/tmp/-out.pdf");
+        assert_eq!(a[15], "dir/-in.pdf");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_unicode_output_file() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let outpath = OsStr::from_bytes(b"caf\xe9 100%.pdf");
+        let args = ShrinkOptions::default().to_gs_args("in.pdf", outpath);
+        assert_eq!(
+            args[14].as_bytes(),
+            b"-sOutputFile=caf\xe9 100%%.pdf".as_slice()
+        );
+    }
+
+    #[test]
+    fn test_watermark_args() {
+        let watermark = Watermark::new("DRAFT");
+        let options = ShrinkOptions {
+            watermark: Some(watermark.clone()),
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-sOutputFile=out.pdf");
+        assert_eq!(args[15], "-c");
+        assert_eq!(args[16], watermark.postscript());
+        assert_eq!(args[17], "-f");
+        assert_eq!(args[18], "in.pdf");
+    }
+
+    #[test]
+    fn test_search_path_args() {
+        let options = ShrinkOptions {
+            include_paths: vec!["/usr/share/color/icc".into(), "my resources".into()],
+            font_path: Some("/usr/share/fonts".into()),
+            first_page: Some(2),
+            ..Default::default()
+        };
+        let args = args(&options);
+        assert_eq!(args[14], "-I/usr/share/color/icc");
+        assert_eq!(args[15], "-Imy resources");
+        assert_eq!(args[16], "-sFONTPATH=/usr/share/fonts");
+        assert_eq!(args[17], "-dFirstPage=2");
+        assert_eq!(args[18], "-sOutputFile=out.pdf");
+    }
+
+    #[test]
+    fn test_dry_run_args() {
+        let options = ShrinkOptions {
+            ghostscript: Some("/opt/gs/bin/gs".into()),
+            color_resolution: 100,
+            downsample_mono: false,
+            first_page: Some(2),
+            marker: true,
+            full_rewrite: false,
+            watermark: Some(Watermark::new("DRAFT")),
+            ..Default::default()
+        };
+        for options in &[ShrinkOptions::default(), options] {
+            let real = options.build_command("-in.pdf", "out%d.pdf");
+            let dry = options.build_dry_run_command("-in.pdf", "out%d.pdf");
+            assert_eq!(dry.get_program(), "args");
+            assert!(real.get_args().eq(dry.get_args()));
+        }
+    }
+}
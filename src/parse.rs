@@ -0,0 +1,263 @@
+//! Parsers for the values of command line options.
+
+use std::time::Duration;
+
+/// Parses a size in bytes, optionally followed by a binary multiple `K`, `M` or `G`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_size;
+/// assert_eq!(parse_size("512"), Ok(512));
+/// assert_eq!(parse_size("10k"), Ok(10 * 1024));
+/// assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+/// assert!(parse_size("2X").is_err());
+/// ```
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k')) | Some((i, 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm')) | Some((i, 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g')) | Some((i, 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size {:?}", s))
+}
+
+/// Parses a positive resolution in dots per inch, optionally followed by `dpi` or `ppi`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_dpi;
+/// assert_eq!(parse_dpi("150"), Ok(150));
+/// assert_eq!(parse_dpi("150dpi"), Ok(150));
+/// assert_eq!(parse_dpi("300 ppi"), Ok(300));
+/// assert!(parse_dpi("150mm").is_err());
+/// ```
+pub fn parse_dpi(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let lower = s.to_ascii_lowercase();
+    let number = lower
+        .strip_suffix("dpi")
+        .or_else(|| lower.strip_suffix("ppi"))
+        .unwrap_or(&lower);
+    match number.trim().parse::<u32>() {
+        Ok(dpi) if dpi > 0 => Ok(dpi),
+        _ => Err(format!("invalid resolution {:?}", s)),
+    }
+}
+
+/// Parses a table `color=C,gray=G,mono=M` of image resolutions, in dots per inch.
+///
+/// The keys can be given in any order, and omitted keys take the default
+/// resolution of [`ShrinkOptions`](crate::ShrinkOptions). An entry without a key
+/// sets all three resolutions. The values are parsed with [`parse_dpi`].
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_dpi_table;
+/// assert_eq!(parse_dpi_table("color=150,gray=150,mono=300"), Ok((150, 150, 300)));
+/// assert_eq!(parse_dpi_table("mono=300"), Ok((135, 135, 300)));
+/// assert_eq!(parse_dpi_table("150dpi"), Ok((150, 150, 150)));
+/// assert!(parse_dpi_table("color=high").is_err());
+/// ```
+pub fn parse_dpi_table(s: &str) -> Result<(u32, u32, u32), String> {
+    let defaults = crate::ShrinkOptions::default();
+    parse_dpi_table_over(
+        s,
+        (
+            defaults.color_resolution,
+            defaults.gray_resolution,
+            defaults.mono_resolution,
+        ),
+    )
+}
+
+/// Like [`parse_dpi_table`], but the omitted keys take the resolutions in `table`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_dpi_table_over;
+/// assert_eq!(parse_dpi_table_over("mono=300", (72, 72, 150)), Ok((72, 72, 300)));
+/// ```
+pub fn parse_dpi_table_over(
+    s: &str,
+    mut table: (u32, u32, u32),
+) -> Result<(u32, u32, u32), String> {
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (key, value) = match entry.split_once('=') {
+            Some((key, value)) => (key.trim(), parse_dpi(value)?),
+            None => match parse_dpi(entry) {
+                Ok(value) => ("", value),
+                Err(_) => return Err(format!("expected KEY=DPI, found {:?}", entry)),
+            },
+        };
+        match key {
+            "" => table = (value, value, value),
+            "color" => table.0 = value,
+            "gray" => table.1 = value,
+            "mono" => table.2 = value,
+            key => return Err(format!("unknown image type {:?}", key)),
+        }
+    }
+    Ok(table)
+}
+
+/// Parses an octal file mode such as `664` or `0o2775`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_mode;
+/// assert_eq!(parse_mode("664"), Ok(0o664));
+/// assert_eq!(parse_mode("0o2775"), Ok(0o2775));
+/// assert!(parse_mode("778").is_err());
+/// ```
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 && !digits.starts_with('+') => Ok(mode),
+        _ => Err(format!("invalid file mode {:?}", s)),
+    }
+}
+
+/// Parses a duration in seconds, or made of hours `h`, minutes `m` and seconds `s`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_duration;
+/// # use std::time::Duration;
+/// assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(2 * 3600)));
+/// assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+/// assert!(parse_duration("1.5h").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let invalid = || format!("invalid duration {:?}", s);
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return s.parse().map(Duration::from_secs).map_err(|_| invalid());
+    }
+    let mut secs: u64 = 0;
+    let mut rest = s;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let number: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        let unit = match rest.as_bytes()[digits] {
+            b'h' => 3600,
+            b'm' => 60,
+            b's' => 1,
+            _ => return Err(invalid()),
+        };
+        secs = number
+            .checked_mul(unit)
+            .and_then(|n| secs.checked_add(n))
+            .ok_or_else(invalid)?;
+        rest = &rest[digits + 1..];
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("1k"), Ok(1024));
+        assert_eq!(parse_size("1 K"), Ok(1024));
+        assert_eq!(parse_size("3g"), Ok(3 << 30));
+        for s in &["", "k", "-1", "1.5M", "1kb", "99999999999G"] {
+            assert!(parse_size(s).is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_dpi() {
+        assert_eq!(parse_dpi("150"), Ok(150));
+        assert_eq!(parse_dpi("150dpi"), Ok(150));
+        assert_eq!(parse_dpi("150ppi"), Ok(150));
+        assert_eq!(parse_dpi(" 150 DPI "), Ok(150));
+        for s in &["", "dpi", "0dpi", "-150", "150mm", "150 dpi dpi", "1.5e2"] {
+            assert!(parse_dpi(s).is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_dpi_table() {
+        assert_eq!(
+            parse_dpi_table("color=150,gray=200,mono=300"),
+            Ok((150, 200, 300))
+        );
+        assert_eq!(
+            parse_dpi_table(" mono = 300 , color=100 "),
+            Ok((100, 135, 300))
+        );
+        assert_eq!(parse_dpi_table("gray=72"), Ok((135, 72, 135)));
+        assert_eq!(parse_dpi_table(""), Ok((135, 135, 135)));
+        assert_eq!(parse_dpi_table("200ppi,mono=300dpi"), Ok((200, 200, 300)));
+        for s in &[
+            "color",
+            "color=",
+            "color=0",
+            "color=-1",
+            "cmyk=100",
+            "color:100",
+        ] {
+            assert!(parse_dpi_table(s).is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let secs = |s| parse_duration(s).map(|d| d.as_secs());
+        assert_eq!(secs("0"), Ok(0));
+        assert_eq!(secs(" 45 "), Ok(45));
+        assert_eq!(secs("45s"), Ok(45));
+        assert_eq!(secs("10m"), Ok(600));
+        assert_eq!(secs("2h"), Ok(7200));
+        assert_eq!(secs("1h2m3s"), Ok(3723));
+        assert_eq!(secs("90m30s"), Ok(5430));
+        for s in &[
+            "",
+            "h",
+            "1d",
+            "1h30",
+            "+5",
+            "-5s",
+            "1.5h",
+            "1 h",
+            "99999999999999999999h",
+        ] {
+            assert!(parse_duration(s).is_err(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0"), Ok(0));
+        assert_eq!(parse_mode("644"), Ok(0o644));
+        assert_eq!(parse_mode("0664"), Ok(0o664));
+        assert_eq!(parse_mode("0o775"), Ok(0o775));
+        assert_eq!(parse_mode("7777"), Ok(0o7777));
+        for s in &["", "8", "rw-r--r--", "10000", "-644", "+644", "0x1ff"] {
+            assert!(parse_mode(s).is_err(), "{:?}", s);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Expansion of the patterns naming the inputs, for shells which do not do it.
+
+#[cfg(feature = "patterns")]
+use std::path::PathBuf;
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+/// Position of the closing brace matching the opening one at `open`, if any.
+fn matching_brace(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Splits the contents of a brace group at its top level commas.
+fn alternatives(chars: &[char]) -> Vec<&[char]> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start, mut i) = (0, 0, 0);
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&chars[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    parts.push(&chars[start..]);
+    parts
+}
+
+/// Removes the backslashes escaping braces and commas.
+fn unescape(chars: &[char]) -> String {
+    let mut s = String::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match (chars[i], chars.get(i + 1)) {
+            ('\\', Some(&c)) if "{},".contains(c) => {
+                s.push(c);
+                i += 2;
+            }
+            (c, _) => {
+                s.push(c);
+                i += 1;
+            }
+        }
+    }
+    s
+}
+
+fn expand_into(chars: &[char], out: &mut Vec<String>) {
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if let Some(close) = matching_brace(chars, i) {
+                    let parts = alternatives(&chars[i + 1..close]);
+                    // like in the shell, a group without commas is literal
+                    if parts.len() > 1 {
+                        for part in parts {
+                            let mut expanded = chars[..i].to_vec();
+                            expanded.extend_from_slice(part);
+                            expanded.extend_from_slice(&chars[close + 1..]);
+                            expand_into(&expanded, out);
+                        }
+                        return;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out.push(unescape(chars));
+}
+
+/// Expands the alternatives `{a,b}` in `pattern`, like the shell does.
+///
+/// Groups can be nested, and a group without commas or without the closing brace is kept
+/// literally. The characters `{`, `}` and `,` can be escaped with a backslash; other
+/// backslashes are kept, so that Windows paths are unaffected.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::expand_braces;
+/// assert_eq!(
+///     expand_braces("reports/{2022,2023}/*.pdf"),
+///     ["reports/2022/*.pdf", "reports/2023/*.pdf"]
+/// );
+/// assert_eq!(expand_braces("a{,.bak}.pdf"), ["a.pdf", "a.bak.pdf"]);
+/// ```
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = Vec::new();
+    expand_into(&chars, &mut out);
+    #[cfg(feature = "logging")]
+    trace!("expand_braces({:?}) = {:?}", pattern, out);
+    out
+}
+
+/// What [`glob_inputs_with`] does with an alternative which contains wildcards
+/// but matches no file.
+#[cfg(feature = "patterns")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmptyGlob {
+    /// Keep the alternative as it is, like the shell does.
+    Keep,
+    /// Drop the alternative.
+    Skip,
+    /// Fail.
+    Error,
+}
+
+/// Paths matching `pattern`, after expanding its braces with [`expand_braces`].
+///
+/// Each alternative is matched with the wildcards `*`, `?` and `[...]` and its matches are
+/// sorted; like in the shell, an alternative without matches is kept as it is.
+#[cfg(feature = "patterns")]
+pub fn glob_inputs(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    glob_inputs_with(pattern, EmptyGlob::Keep)
+}
+
+/// Like [`glob_inputs`], but an alternative with wildcards and without matches is
+/// treated according to `on_empty`.
+///
+/// An alternative without wildcards is always kept, even if the file does not exist.
+#[cfg(feature = "patterns")]
+pub fn glob_inputs_with(pattern: &str, on_empty: EmptyGlob) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for alternative in expand_braces(pattern) {
+        let matches = glob::glob(&alternative)
+            .map_err(|e| format!("invalid pattern {:?}: {}", alternative, e))?;
+        let before = paths.len();
+        paths.extend(matches.filter_map(Result::ok));
+        if paths.len() > before {
+            continue;
+        }
+        let wildcards = alternative.contains(['*', '?', '[']);
+        match on_empty {
+            _ if !wildcards => paths.push(alternative.into()),
+            EmptyGlob::Keep => paths.push(alternative.into()),
+            EmptyGlob::Skip => {
+                #[cfg(feature = "logging")]
+                trace!("glob_inputs_with: no file matches {:?}", alternative);
+            }
+            EmptyGlob::Error => return Err(format!("no file matches {:?}", alternative)),
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_braces_single_level() {
+        assert_eq!(expand_braces("a.pdf"), ["a.pdf"]);
+        assert_eq!(expand_braces("{a,b,c}.pdf"), ["a.pdf", "b.pdf", "c.pdf"]);
+        assert_eq!(expand_braces("{a,b}-{1,2}"), ["a-1", "a-2", "b-1", "b-2"]);
+        assert_eq!(expand_braces("{,x}"), ["", "x"]);
+        // literal braces
+        assert_eq!(expand_braces("{a}.pdf"), ["{a}.pdf"]);
+        assert_eq!(expand_braces("{}"), ["{}"]);
+        assert_eq!(expand_braces("{a,b"), ["{a,b"]);
+        assert_eq!(expand_braces("a}b,c"), ["a}b,c"]);
+    }
+
+    #[test]
+    fn test_expand_braces_nested() {
+        assert_eq!(expand_braces("x{a,{b,c}d}y"), ["xay", "xbdy", "xcdy"]);
+        assert_eq!(expand_braces("{{a,b}}"), ["{a}", "{b}"]);
+        assert_eq!(
+            expand_braces("{a,b{1,2}}/*.pdf"),
+            ["a/*.pdf", "b1/*.pdf", "b2/*.pdf"]
+        );
+    }
+
+    #[test]
+    fn test_expand_braces_escaped() {
+        assert_eq!(expand_braces(r"\{a,b\}"), ["{a,b}"]);
+        assert_eq!(expand_braces(r"{a\,b,c}"), ["a,b", "c"]);
+        assert_eq!(expand_braces(r"{a\},b}"), ["a}", "b"]);
+        assert_eq!(expand_braces(r"C:\docs\{a,b}.pdf"), [r"C:\docs{a,b}.pdf"]);
+        assert_eq!(expand_braces(r"C:\docs\a.pdf"), [r"C:\docs\a.pdf"]);
+    }
+
+    #[cfg(feature = "patterns")]
+    #[test]
+    fn test_glob_inputs() {
+        let dir = crate::testing::TempDir::new();
+        for sub in &["2022", "2023", "2024"] {
+            std::fs::create_dir(dir.path().join(sub)).unwrap();
+            dir.pdf(&format!("{}/b.pdf", sub), 10);
+            dir.pdf(&format!("{}/a.pdf", sub), 10);
+        }
+        let root = dir.path().to_str().unwrap();
+        let paths = glob_inputs(&format!("{}/{{2022,2024}}/*.pdf", root)).unwrap();
+        let expected: Vec<PathBuf> = ["2022/a.pdf", "2022/b.pdf", "2024/a.pdf", "2024/b.pdf"]
+            .iter()
+            .map(|p| dir.path().join(p))
+            .collect();
+        assert_eq!(paths, expected);
+
+        let missing = format!("{}/2025/*.pdf", root);
+        assert_eq!(glob_inputs(&missing).unwrap(), [PathBuf::from(&missing)]);
+        assert!(glob_inputs("[").is_err());
+    }
+
+    #[cfg(feature = "patterns")]
+    #[test]
+    fn test_glob_inputs_on_empty() {
+        let dir = crate::testing::TempDir::new();
+        let a = dir.pdf("a.pdf", 10);
+        let root = dir.path().to_str().unwrap();
+        let pattern = format!("{}/{{a,b*}}.pdf", root);
+        let missing = format!("{}/b*.pdf", root);
+
+        assert_eq!(
+            glob_inputs_with(&pattern, EmptyGlob::Keep).unwrap(),
+            [a.clone(), PathBuf::from(&missing)]
+        );
+        assert_eq!(glob_inputs_with(&pattern, EmptyGlob::Skip).unwrap(), [a]);
+        assert_eq!(
+            glob_inputs_with(&pattern, EmptyGlob::Error),
+            Err(format!("no file matches {:?}", missing))
+        );
+
+        // not a pattern
+        let plain = format!("{}/c.pdf", root);
+        assert_eq!(
+            glob_inputs_with(&plain, EmptyGlob::Error).unwrap(),
+            [PathBuf::from(&plain)]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! Plan which files to process and in which order.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{CommandOutput, Runner, ShrinkError};
+
+/// Command planned to shrink one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlannedJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl PlannedJob {
+    /// Records `cmd`, which shrinks `input` into `output`.
+    pub fn new<P, Q>(input: P, output: Q, cmd: &Command) -> PlannedJob
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        PlannedJob {
+            input: input.as_ref().into(),
+            output: output.as_ref().into(),
+            program: cmd.get_program().to_string_lossy().into_owned(),
+            args: cmd
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        }
+    }
+
+    /// The recorded command.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        cmd
+    }
+
+    /// Executes the recorded command through `runner`.
+    pub fn run_with<R>(&self, runner: &R) -> Result<CommandOutput, ShrinkError>
+    where
+        R: Runner + ?Sized,
+    {
+        let output =
+            runner
+                .run(&mut self.command())
+                .map_err(|source| ShrinkError::SpawnFailed {
+                    program: self.program.clone().into(),
+                    source,
+                })?;
+        if output.success() {
+            Ok(output)
+        } else {
+            Err(ShrinkError::NonZeroExit {
+                code: output.code,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            })
+        }
+    }
+}
+
+/// Commands planned for a whole run, which can be saved and executed later.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plan {
+    pub jobs: Vec<PlannedJob>,
+}
+
+#[cfg(feature = "json")]
+impl Plan {
+    /// Writes the plan to `path` as JSON.
+    pub fn save<P>(&self, path: P) -> std::io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+    }
+
+    /// Reads a plan written by [`save`](Self::save).
+    pub fn load<P>(path: P) -> std::io::Result<Plan>
+    where
+        P: AsRef<Path>,
+    {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
+
+/// Order in which the inputs are processed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Order {
+    /// By path.
+    Name,
+    /// Smallest files first.
+    SizeAsc,
+    /// Largest files first, which balances the load of parallel runs.
+    SizeDesc,
+}
+
+impl std::str::FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Order, String> {
+        match s {
+            "name" => Ok(Order::Name),
+            "size-asc" => Ok(Order::SizeAsc),
+            "size-desc" => Ok(Order::SizeDesc),
+            _ => Err(format!(
+                "invalid order {:?}, expected name, size-asc or size-desc",
+                s
+            )),
+        }
+    }
+}
+
+/// Sorts the `inputs` according to `order`.
+///
+/// The sort is stable; files whose size cannot be read count as empty.
+pub fn sort_inputs<P>(inputs: &mut [P], order: Order)
+where
+    P: AsRef<Path>,
+{
+    let size = |path: &P| std::fs::metadata(path).map_or(0, |m| m.len());
+    match order {
+        Order::Name => inputs.sort_by(|a, b| a.as_ref().cmp(b.as_ref())),
+        Order::SizeAsc => inputs.sort_by_cached_key(size),
+        Order::SizeDesc => inputs.sort_by_cached_key(|path| std::cmp::Reverse(size(path))),
+    }
+}
+
+/// Small deterministic pseudo-random generator ([SplitMix64](https://prng.di.unimi.it/splitmix64.c)).
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`, for `n > 0`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Keeps at most `n` of the `items`.
+///
+/// Without a `seed` the first `n` items are kept, otherwise `n` items are chosen
+/// at random (deterministically for a given seed). The relative order of the
+/// kept items is preserved.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::sample;
+/// assert_eq!(sample(vec![1, 2, 3, 4], 2, None), [1, 2]);
+/// assert_eq!(sample(vec![1, 2, 3, 4], 2, Some(42)), sample(vec![1, 2, 3, 4], 2, Some(42)));
+/// ```
+pub fn sample<T>(mut items: Vec<T>, n: usize, seed: Option<u64>) -> Vec<T> {
+    let seed = match seed {
+        Some(seed) if n < items.len() => seed,
+        _ => {
+            items.truncate(n);
+            return items;
+        }
+    };
+    let mut rng = SplitMix64(seed);
+    let mut indices: Vec<usize> = (0..items.len()).collect();
+    for i in 0..n {
+        let j = i + rng.below(indices.len() - i);
+        indices.swap(i, j);
+    }
+    let mut chosen = vec![false; items.len()];
+    for &i in &indices[..n] {
+        chosen[i] = true;
+    }
+    items
+        .into_iter()
+        .zip(chosen)
+        .filter_map(|(item, chosen)| if chosen { Some(item) } else { None })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planned_job() {
+        let mut cmd = Command::new("gs");
+        cmd.args(["-q", "-sOutputFile=out dir/a.pdf", "a.pdf"]);
+        let job = PlannedJob::new("a.pdf", "out dir/a.pdf", &cmd);
+        assert_eq!(job.program, "gs");
+        assert_eq!(job.args, ["-q", "-sOutputFile=out dir/a.pdf", "a.pdf"]);
+
+        let runner = crate::testing::MockRunner::succeeding();
+        job.run_with(&runner).unwrap();
+        assert_eq!(runner.calls()[0][0], "gs");
+        assert_eq!(runner.calls()[0][3], "a.pdf");
+
+        let runner = crate::testing::MockRunner::failing(1, "oops");
+        assert!(matches!(
+            job.run_with(&runner),
+            Err(ShrinkError::NonZeroExit { code: Some(1), .. })
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_plan_round_trip() {
+        let dir = crate::testing::TempDir::new();
+        let options = crate::ShrinkOptions::default();
+        let plan = Plan {
+            jobs: vec![
+                PlannedJob::new(
+                    "a.pdf",
+                    "a.shrunk.pdf",
+                    &options.build_command("a.pdf", "a.shrunk.pdf"),
+                ),
+                PlannedJob::new(
+                    "dir/b \"quoted\".pdf",
+                    "dir/sub/b \"quoted\".pdf",
+                    &options.build_command("dir/b \"quoted\".pdf", "dir/sub/b \"quoted\".pdf"),
+                ),
+            ],
+        };
+        let path = dir.path().join("plan.json");
+        plan.save(&path).unwrap();
+        assert_eq!(Plan::load(&path).unwrap(), plan);
+
+        std::fs::write(&path, "{\"jobs\": [{\"input\": 1}]}").unwrap();
+        assert!(Plan::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_sort_inputs() {
+        let dir = crate::testing::TempDir::new();
+        let b = dir.pdf("b.pdf", 300);
+        let a = dir.pdf("a.pdf", 200);
+        let c = dir.pdf("c.pdf", 100);
+        let missing = dir.path().join("missing.pdf");
+        let inputs = vec![b.clone(), missing.clone(), a.clone(), c.clone()];
+
+        let sorted = |order| {
+            let mut inputs = inputs.clone();
+            sort_inputs(&mut inputs, order);
+            inputs
+        };
+        assert_eq!(
+            sorted(Order::Name),
+            [a.clone(), b.clone(), c.clone(), missing.clone()]
+        );
+        assert_eq!(
+            sorted(Order::SizeAsc),
+            [missing.clone(), c.clone(), a.clone(), b.clone()]
+        );
+        assert_eq!(sorted(Order::SizeDesc), [b, a, c, missing]);
+
+        assert_eq!("size-desc".parse(), Ok(Order::SizeDesc));
+        assert!("size".parse::<Order>().is_err());
+    }
+
+    #[test]
+    fn test_sample_first() {
+        let items: Vec<u32> = (0..10).collect();
+        assert_eq!(sample(items.clone(), 3, None), [0, 1, 2]);
+        assert!(sample(items.clone(), 0, None).is_empty());
+        assert_eq!(sample(items.clone(), 10, None), items);
+        assert_eq!(sample(items.clone(), 20, None), items);
+    }
+
+    #[test]
+    fn test_sample_random() {
+        let items: Vec<u32> = (0..100).collect();
+        for seed in 0..20 {
+            let a = sample(items.clone(), 10, Some(seed));
+            let b = sample(items.clone(), 10, Some(seed));
+            assert_eq!(a, b);
+            assert_eq!(a.len(), 10);
+            // sorted and without repetitions
+            assert!(a.windows(2).all(|w| w[0] < w[1]));
+        }
+        assert_ne!(
+            sample(items.clone(), 10, Some(1)),
+            sample(items.clone(), 10, Some(2))
+        );
+        assert_ne!(sample(items.clone(), 10, Some(1)), sample(items, 10, None));
+        assert_eq!(sample(vec![1, 2, 3], 5, Some(7)), [1, 2, 3]);
+    }
+}
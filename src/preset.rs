@@ -0,0 +1,179 @@
+//! Named sets of options for common uses of the output.
+//!
+//! The built-in presets only set the resolution the images are downsampled to:
+//!
+//! | Preset    | Color | Gray | Mono |
+//! |-----------|------:|-----:|-----:|
+//! | `email`   |    72 |   72 |  150 |
+//! | `web`     |   100 |  100 |  200 |
+//! | `archive` |   200 |  200 |  400 |
+//! | `print`   |   300 |  300 |  600 |
+//!
+//! More presets can be defined in a TOML file with a table per preset, whose keys are
+//! the ones of the [sidecar files](crate::sidecar_path). A table named after an existing
+//! preset modifies it, any other starts from the default options:
+//!
+//! ```toml
+//! [scans]
+//! color_resolution = 150
+//! mono_resolution = 300
+//!
+//! [email]
+//! keep_smaller = true
+//! ```
+
+use std::collections::HashMap;
+#[cfg(feature = "sidecar")]
+use std::path::Path;
+
+#[cfg(feature = "sidecar")]
+use crate::sidecar::Overrides;
+#[cfg(feature = "sidecar")]
+use crate::ShrinkError;
+use crate::ShrinkOptions;
+
+/// Registry of named [`ShrinkOptions`].
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{Presets, ShrinkOptions};
+/// let mut presets = Presets::builtin();
+/// assert_eq!(presets.get("web").unwrap().color_resolution, 100);
+///
+/// presets.insert("tiny", ShrinkOptions { color_resolution: 50, ..Default::default() });
+/// assert!(presets.names().contains(&"tiny"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presets(HashMap<String, ShrinkOptions>);
+
+impl Presets {
+    /// The presets listed in the [module documentation](self).
+    pub fn builtin() -> Presets {
+        let mut presets = Presets::default();
+        for &(name, color, gray, mono) in &[
+            ("email", 72, 72, 150),
+            ("web", 100, 100, 200),
+            ("archive", 200, 200, 400),
+            ("print", 300, 300, 600),
+        ] {
+            presets.insert(
+                name,
+                ShrinkOptions {
+                    color_resolution: color,
+                    gray_resolution: gray,
+                    mono_resolution: mono,
+                    ..Default::default()
+                },
+            );
+        }
+        presets
+    }
+
+    /// Adds the preset `name`, replacing the one with the same name.
+    pub fn insert<S>(&mut self, name: S, options: ShrinkOptions)
+    where
+        S: Into<String>,
+    {
+        self.0.insert(name.into(), options);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ShrinkOptions> {
+        self.0.get(name)
+    }
+
+    /// Names of the presets, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Adds or modifies the presets defined in the TOML file `path`.
+    #[cfg(feature = "sidecar")]
+    pub fn load<P>(&mut self, path: P) -> Result<(), ShrinkError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ShrinkError::Io {
+            path: path.into(),
+            source,
+        })?;
+        let tables: HashMap<String, Overrides> =
+            toml::from_str(&text).map_err(|e| ShrinkError::InvalidPresets {
+                path: path.into(),
+                message: e.message().into(),
+            })?;
+        for (name, overrides) in tables {
+            let options = self.0.entry(name).or_default();
+            overrides.apply(options);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_presets() {
+        let presets = Presets::builtin();
+        assert_eq!(presets.names(), ["archive", "email", "print", "web"]);
+        for &(name, color, gray, mono) in &[
+            ("email", 72, 72, 150),
+            ("web", 100, 100, 200),
+            ("archive", 200, 200, 400),
+            ("print", 300, 300, 600),
+        ] {
+            let options = presets.get(name).unwrap();
+            assert_eq!(
+                (
+                    options.color_resolution,
+                    options.gray_resolution,
+                    options.mono_resolution
+                ),
+                (color, gray, mono),
+                "{}",
+                name
+            );
+            let expected = ShrinkOptions {
+                color_resolution: color,
+                gray_resolution: gray,
+                mono_resolution: mono,
+                ..Default::default()
+            };
+            assert_eq!(*options, expected);
+        }
+        assert_eq!(presets.get("screen"), None);
+    }
+
+    #[cfg(feature = "sidecar")]
+    #[test]
+    fn test_load_presets() {
+        let dir = crate::testing::TempDir::new();
+        let path = dir.path().join("presets.toml");
+        std::fs::write(
+            &path,
+            "[scans]\nmono_resolution = 300\n\n[email]\nkeep_smaller = true\n",
+        )
+        .unwrap();
+
+        let mut presets = Presets::builtin();
+        presets.load(&path).unwrap();
+        let scans = presets.get("scans").unwrap();
+        assert_eq!(scans.mono_resolution, 300);
+        assert_eq!(scans.color_resolution, 135);
+        let email = presets.get("email").unwrap();
+        assert!(email.keep_smaller);
+        assert_eq!(email.color_resolution, 72);
+
+        std::fs::write(&path, "[scans]\nmono_dpi = 300\n").unwrap();
+        assert!(matches!(
+            presets.load(&path),
+            Err(ShrinkError::InvalidPresets { .. })
+        ));
+        assert!(presets.load(dir.path().join("missing.toml")).is_err());
+    }
+}
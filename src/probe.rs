@@ -0,0 +1,523 @@
+//! Inspect PDF files before shrinking them.
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::{resolve_ghostscript, CommandOutput, Runner, ShrinkOptions, SystemRunner};
+
+/// Quotes `s` as a PostScript string literal.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::ps_string;
+/// assert_eq!(ps_string("a (b) \\c"), "(a \\(b\\) \\\\c)");
+/// ```
+pub fn ps_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('(');
+    for c in s.chars() {
+        if let '(' | ')' | '\\' = c {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push(')');
+    quoted
+}
+
+/// Ghostscript command printing the number of pages of `inpath`.
+///
+/// The program is [`ShrinkOptions::ghostscript`], falling back to `gs`.
+/// Ghostscript runs with `-dSAFER`, allowed to read only `inpath`.
+pub fn page_count_command<P>(inpath: P, options: &ShrinkOptions) -> Command
+where
+    P: AsRef<Path>,
+{
+    let inpath = inpath.as_ref();
+    let program = options.ghostscript.as_deref().unwrap_or("gs".as_ref());
+    let mut permit = OsString::from("--permit-file-read=");
+    permit.push(inpath);
+    let mut cmd = Command::new(program);
+    cmd.args(["-q", "-dNODISPLAY", "-dSAFER"])
+        .arg(permit)
+        .arg("-c")
+        .arg(format!(
+            "{} (r) file runpdfbegin pdfpagecount = quit",
+            ps_string(&inpath.to_string_lossy())
+        ));
+    cmd
+}
+
+/// Error describing the unsuccessful termination of `program`.
+fn exit_error(program: &str, output: &CommandOutput) -> io::Error {
+    match output.code {
+        Some(code) => io::Error::other(format!("{} exited with code {}", program, code)),
+        None => io::Error::other(format!("{} was terminated by a signal", program)),
+    }
+}
+
+/// Returns the number of pages of `inpath`, as reported by Ghostscript.
+///
+/// If [`ShrinkOptions::ghostscript`] is not set, the executable is located
+/// with [`resolve_ghostscript`].
+pub fn page_count<P>(inpath: P, options: &ShrinkOptions) -> io::Result<u32>
+where
+    P: AsRef<Path>,
+{
+    if options.ghostscript.is_some() {
+        return page_count_with(&SystemRunner, inpath, options);
+    }
+    let options = ShrinkOptions {
+        ghostscript: Some(resolve_ghostscript().map_err(io::Error::other)?),
+        ..options.clone()
+    };
+    page_count_with(&SystemRunner, inpath, &options)
+}
+
+/// Like [`page_count`], but executes [`page_count_command`] with `runner`.
+pub fn page_count_with<R, P>(runner: &R, inpath: P, options: &ShrinkOptions) -> io::Result<u32>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+{
+    let output = runner.run(&mut page_count_command(&inpath, options))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = if output.success() {
+        stdout.trim().parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unexpected page count {:?}", stdout.trim()),
+            )
+        })
+    } else {
+        Err(exit_error("gs", &output))
+    };
+    #[cfg(feature = "logging")]
+    trace!("page_count_with({:?}) = {:?}", inpath.as_ref(), result);
+    result
+}
+
+/// Last page to process in order to cover `percent`% of `page_count` pages.
+///
+/// The result is rounded up and always includes at least the first page.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::last_page_for_percent;
+/// assert_eq!(last_page_for_percent(200, 10.0), 20);
+/// assert_eq!(last_page_for_percent(7, 10.0), 1);
+/// ```
+pub fn last_page_for_percent(page_count: u32, percent: f64) -> u32 {
+    let last = (f64::from(page_count) * percent / 100.0).ceil();
+    if last < 1.0 {
+        1
+    } else if last > f64::from(page_count) {
+        page_count.max(1)
+    } else {
+        last as u32
+    }
+}
+
+/// Embedded image, as listed by `pdfimages -list`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Page where the image is drawn, 1-based.
+    pub page: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Color space, such as `rgb`, `gray` or `cmyk`.
+    pub color: String,
+    /// Bits per color component, 1 for monochrome images.
+    pub bits_per_component: u32,
+    /// Horizontal resolution at which the image is drawn.
+    pub x_ppi: u32,
+    /// Vertical resolution at which the image is drawn.
+    pub y_ppi: u32,
+}
+
+impl ImageInfo {
+    /// Effective resolution of the image: the smaller of the two directions.
+    pub fn ppi(&self) -> u32 {
+        self.x_ppi.min(self.y_ppi)
+    }
+
+    /// Whether the image is monochrome (1 bit per pixel), as produced by scanners in line art mode.
+    pub fn is_mono(&self) -> bool {
+        self.bits_per_component == 1
+    }
+}
+
+/// Command listing the images embedded in `inpath`.
+///
+/// This command requires the program `pdfimages` from [Poppler](https://poppler.freedesktop.org/).
+pub fn image_probe_command<P>(inpath: P) -> Command
+where
+    P: AsRef<Path>,
+{
+    let mut cmd = Command::new("pdfimages");
+    cmd.arg("-list").arg(inpath.as_ref());
+    cmd
+}
+
+/// Parses the output of `pdfimages -list`.
+///
+/// Soft masks and masks are ignored, since they are not downsampled on their own.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::parse_image_list;
+/// let list = "\
+/// page   num  type   width height color comp bpc  enc interp  object ID x-ppi y-ppi size ratio
+/// --------------------------------------------------------------------------------------------
+///    1     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  528K 6.2%
+/// ";
+/// let images = parse_image_list(list);
+/// assert_eq!(images.len(), 1);
+/// assert_eq!(images[0].ppi(), 300);
+/// ```
+pub fn parse_image_list(list: &str) -> Vec<ImageInfo> {
+    list.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 14 || !matches!(fields[2], "image" | "stencil") {
+                return None;
+            }
+            Some(ImageInfo {
+                page: fields[0].parse().ok()?,
+                width: fields[3].parse().ok()?,
+                height: fields[4].parse().ok()?,
+                color: fields[5].into(),
+                bits_per_component: fields[7].parse().ok()?,
+                x_ppi: fields[12].parse().ok()?,
+                y_ppi: fields[13].parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Lists the images embedded in `inpath`, see [`image_probe_command`].
+pub fn probe_images<P>(inpath: P) -> io::Result<Vec<ImageInfo>>
+where
+    P: AsRef<Path>,
+{
+    probe_images_with(&SystemRunner, inpath)
+}
+
+/// Like [`probe_images`], but executes [`image_probe_command`] with `runner`.
+pub fn probe_images_with<R, P>(runner: &R, inpath: P) -> io::Result<Vec<ImageInfo>>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+{
+    let output = runner.run(&mut image_probe_command(&inpath))?;
+    if !output.success() {
+        return Err(exit_error("pdfimages", &output));
+    }
+    let images = parse_image_list(&String::from_utf8_lossy(&output.stdout));
+    #[cfg(feature = "logging")]
+    trace!("probe_images({:?}) = {:?}", inpath.as_ref(), images);
+    Ok(images)
+}
+
+/// Summary statistics of a set of image resolutions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DpiStats {
+    pub min: u32,
+    /// The middle value, or the mean of the two middle values (rounded down).
+    pub median: u32,
+    pub max: u32,
+}
+
+/// Computes the statistics of `dpis`, or `None` if it is empty.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{dpi_stats, DpiStats};
+/// assert_eq!(
+///     dpi_stats(&[300, 72, 150]),
+///     Some(DpiStats { min: 72, median: 150, max: 300 })
+/// );
+/// ```
+pub fn dpi_stats(dpis: &[u32]) -> Option<DpiStats> {
+    let mut sorted = dpis.to_vec();
+    sorted.sort_unstable();
+    let n = sorted.len();
+    let median = match n {
+        0 => return None,
+        n if n % 2 == 1 => sorted[n / 2],
+        n => ((u64::from(sorted[n / 2 - 1]) + u64::from(sorted[n / 2])) / 2) as u32,
+    };
+    Some(DpiStats {
+        min: sorted[0],
+        median,
+        max: sorted[n - 1],
+    })
+}
+
+/// Target resolution obtained scaling the `source` resolution by `factor`.
+///
+/// The result is rounded to the nearest integer and is never lower than `min`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::scaled_dpi;
+/// assert_eq!(scaled_dpi(600, 0.5, 72), 300);
+/// assert_eq!(scaled_dpi(100, 0.5, 72), 72);
+/// ```
+pub fn scaled_dpi(source: u32, factor: f64, min: u32) -> u32 {
+    let scaled = (f64::from(source) * factor).round();
+    if scaled.is_nan() || scaled < f64::from(min) {
+        min
+    } else if scaled > f64::from(u32::MAX) {
+        u32::MAX
+    } else {
+        scaled as u32
+    }
+}
+
+/// Resolutions commonly used by scanners.
+const SCAN_DPIS: &[u32] = &[75, 100, 150, 200, 240, 300, 400, 600, 1200, 2400];
+
+/// Rounds `ppi` to a common scanner resolution, if it is within 2% of one.
+fn nominal_dpi(ppi: u32) -> u32 {
+    SCAN_DPIS
+        .iter()
+        .copied()
+        .find(|&dpi| u64::from(ppi.abs_diff(dpi)) * 50 <= u64::from(dpi))
+        .unwrap_or(ppi)
+}
+
+/// Nominal resolution at which the monochrome `images` were scanned, if any.
+///
+/// This is the highest resolution of the monochrome images, rounded to a common
+/// scanner resolution to absorb the rounding of the page size.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{scan_dpi, ImageInfo};
+/// let image = ImageInfo {
+///     page: 1,
+///     width: 5100,
+///     height: 6600,
+///     color: "gray".into(),
+///     bits_per_component: 1,
+///     x_ppi: 600,
+///     y_ppi: 601,
+/// };
+/// assert_eq!(scan_dpi(&[image]), Some(600));
+/// ```
+pub fn scan_dpi(images: &[ImageInfo]) -> Option<u32> {
+    images
+        .iter()
+        .filter(|image| image.is_mono())
+        .map(|image| nominal_dpi(image.ppi()))
+        .max()
+}
+
+/// Resolution to downsample the monochrome images to, given the `target`
+/// resolution and the [`scan_dpi`] of the `images`.
+///
+/// Scanned monochrome pages are not downsampled below their nominal resolution,
+/// since that would degrade the text while saving little space.
+pub fn respect_scan_dpi(target: u32, images: &[ImageInfo]) -> u32 {
+    match scan_dpi(images) {
+        Some(dpi) => target.max(dpi),
+        None => target,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockRunner;
+
+    #[test]
+    fn test_page_count() {
+        let runner = MockRunner::new(|_| {
+            Ok(CommandOutput {
+                code: Some(0),
+                stdout: b"42\n".to_vec(),
+                ..Default::default()
+            })
+        });
+        let options = ShrinkOptions::default().ghostscript("/opt/gs/bin/gs");
+        assert_eq!(page_count_with(&runner, "a (1).pdf", &options).unwrap(), 42);
+        let calls = runner.calls();
+        assert_eq!(
+            calls[0],
+            [
+                "/opt/gs/bin/gs",
+                "-q",
+                "-dNODISPLAY",
+                "-dSAFER",
+                "--permit-file-read=a (1).pdf",
+                "-c",
+                "(a \\(1\\).pdf) (r) file runpdfbegin pdfpagecount = quit"
+            ]
+        );
+
+        let err = page_count_with(&MockRunner::failing(1, ""), "a.pdf", &options).unwrap_err();
+        assert_eq!(err.to_string(), "gs exited with code 1");
+    }
+
+    #[test]
+    fn test_last_page_for_percent() {
+        for &(pages, percent, last) in &[
+            (100, 10.0, 10),
+            (200, 10.0, 20),
+            (101, 10.0, 11),
+            (7, 10.0, 1),
+            (1, 10.0, 1),
+            (10, 50.0, 5),
+            (10, 55.0, 6),
+            (10, 100.0, 10),
+            (10, 0.0, 1),
+            (10, 150.0, 10),
+            (0, 10.0, 1),
+        ] {
+            assert_eq!(
+                last_page_for_percent(pages, percent),
+                last,
+                "{} pages at {}%",
+                pages,
+                percent
+            );
+        }
+    }
+
+    #[test]
+    fn test_dpi_stats() {
+        let stats = |min, median, max| Some(DpiStats { min, median, max });
+        assert_eq!(dpi_stats(&[]), None);
+        assert_eq!(dpi_stats(&[300]), stats(300, 300, 300));
+        assert_eq!(dpi_stats(&[600, 150]), stats(150, 375, 600));
+        assert_eq!(dpi_stats(&[72, 300, 72, 301]), stats(72, 186, 301));
+        assert_eq!(dpi_stats(&[200, 100, 300, 400, 500]), stats(100, 300, 500));
+        assert_eq!(
+            dpi_stats(&[u32::MAX, u32::MAX]),
+            stats(u32::MAX, u32::MAX, u32::MAX)
+        );
+    }
+
+    #[test]
+    fn test_scaled_dpi() {
+        for &(source, factor, min, target) in &[
+            (300, 0.5, 72, 150),
+            (300, 1.0, 72, 300),
+            (300, 2.0, 72, 600),
+            (301, 0.5, 72, 151),
+            (299, 0.5, 72, 150),
+            (120, 0.5, 72, 72),
+            (144, 0.5, 72, 72),
+            (146, 0.5, 72, 73),
+            (300, 0.0, 50, 50),
+            (0, 0.5, 72, 72),
+            (u32::MAX, 4.0, 72, u32::MAX),
+        ] {
+            assert_eq!(
+                scaled_dpi(source, factor, min),
+                target,
+                "{} * {} (min {})",
+                source,
+                factor,
+                min
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_image_list() {
+        let list = "\
+page   num  type   width height color comp bpc  enc interp  object ID x-ppi y-ppi size ratio
+--------------------------------------------------------------------------------------------
+   1     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  528K 6.2%
+   1     1 smask     640   480  gray    1   8  image  no         9  0    72    72 1000B 0.3%
+   2     2 stencil  5100  6600  -       1   1  ccitt  no        12  0   600   601 42.0K 1.0%
+   3     3 image     640   480  rgb     3   8  image  no        15  0    96    72  900K 100%
+";
+        let images = parse_image_list(list);
+        assert_eq!(images.len(), 3);
+        assert_eq!(
+            images[0],
+            ImageInfo {
+                page: 1,
+                width: 2480,
+                height: 3508,
+                color: "gray".into(),
+                bits_per_component: 8,
+                x_ppi: 300,
+                y_ppi: 300,
+            }
+        );
+        assert_eq!(images[1].page, 2);
+        assert_eq!(images[1].ppi(), 600);
+        assert!(images[1].is_mono());
+        assert_eq!(images[2].ppi(), 72);
+        assert!(parse_image_list("").is_empty());
+    }
+
+    #[test]
+    fn test_probe_images_with() {
+        let runner = MockRunner::new(|_| {
+            Ok(CommandOutput {
+                code: Some(0),
+                stdout: b"   1     0 image    2480  3508  gray    1   8  jpeg   no         7  0   300   300  528K 6.2%\n".to_vec(),
+                ..Default::default()
+            })
+        });
+        let images = probe_images_with(&runner, "a.pdf").unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(runner.calls()[0], ["pdfimages", "-list", "a.pdf"]);
+
+        let err = probe_images_with(&MockRunner::failing(99, ""), "a.pdf").unwrap_err();
+        assert_eq!(err.to_string(), "pdfimages exited with code 99");
+    }
+
+    #[test]
+    fn test_respect_scan_dpi() {
+        let image = |bits_per_component, ppi| ImageInfo {
+            page: 1,
+            width: 100,
+            height: 100,
+            color: "gray".into(),
+            bits_per_component,
+            x_ppi: ppi,
+            y_ppi: ppi,
+        };
+        assert_eq!(nominal_dpi(301), 300);
+        assert_eq!(nominal_dpi(594), 600);
+        assert_eq!(nominal_dpi(280), 280);
+
+        // no monochrome images
+        assert_eq!(respect_scan_dpi(135, &[]), 135);
+        assert_eq!(respect_scan_dpi(135, &[image(8, 600)]), 135);
+        // scanned at 300 or 600 dpi
+        assert_eq!(respect_scan_dpi(135, &[image(1, 299)]), 300);
+        assert_eq!(respect_scan_dpi(135, &[image(1, 300), image(1, 601)]), 600);
+        assert_eq!(respect_scan_dpi(135, &[image(8, 1200), image(1, 300)]), 300);
+        // the target is higher than the scan
+        assert_eq!(respect_scan_dpi(400, &[image(1, 200)]), 400);
+        // unusual resolution, taken as is
+        assert_eq!(respect_scan_dpi(135, &[image(1, 170)]), 170);
+    }
+
+    #[test]
+    fn test_ps_string() {
+        assert_eq!(ps_string("name.pdf"), "(name.pdf)");
+        assert_eq!(ps_string("a(b).pdf"), "(a\\(b\\).pdf)");
+        assert_eq!(ps_string("C:\\x.pdf"), "(C:\\\\x.pdf)");
+    }
+}
@@ -0,0 +1,220 @@
+//! Execution of external commands.
+
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+/// Exit status and captured output of a command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// Exit code, or `None` if the process was terminated by a signal.
+    pub code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandOutput {
+    /// Whether the command terminated successfully.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/// Something able to execute a [`Command`].
+///
+/// The library runs every external program through a `Runner`, so that
+/// embedders can intercept the invocations.
+pub trait Runner {
+    /// Executes `cmd` to completion, capturing its output.
+    fn run(&self, cmd: &mut Command) -> io::Result<CommandOutput>;
+
+    /// Like [`run`](Self::run), but kills `cmd` if it is still running after `timeout`,
+    /// failing with an error of kind [`io::ErrorKind::TimedOut`].
+    ///
+    /// The default implementation ignores the timeout.
+    fn run_with_timeout(&self, cmd: &mut Command, timeout: Duration) -> io::Result<CommandOutput> {
+        let _ = timeout;
+        self.run(cmd)
+    }
+}
+
+/// How often [`run_polling`] checks whether the command must be interrupted.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Reads `pipe` to the end in a separate thread, so that the child does not block on a full pipe.
+fn read_in_background<R>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Executes `cmd` capturing its output, checking `interrupt` while it runs:
+/// as soon as it returns an error, the child is killed and the error returned.
+pub(crate) fn run_polling<F>(cmd: &mut Command, interrupt: F) -> io::Result<CommandOutput>
+where
+    F: Fn() -> Option<io::Error>,
+{
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if let Some(e) = interrupt() {
+            #[cfg(feature = "logging")]
+            trace!("run_polling: killing {}: {}", child.id(), e);
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    Ok(CommandOutput {
+        code: status.code(),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Error returned by [`Runner::run_with_timeout`] if `deadline` has passed.
+pub(crate) fn check_deadline(deadline: Instant) -> Option<io::Error> {
+    if Instant::now() >= deadline {
+        Some(io::Error::new(io::ErrorKind::TimedOut, "timed out"))
+    } else {
+        None
+    }
+}
+
+/// [`Runner`] spawning the commands as child processes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRunner;
+
+impl Runner for SystemRunner {
+    fn run(&self, cmd: &mut Command) -> io::Result<CommandOutput> {
+        #[cfg(feature = "logging")]
+        trace!("SystemRunner::run({:?})", cmd);
+        let output = cmd.output()?;
+        Ok(CommandOutput {
+            code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn run_with_timeout(&self, cmd: &mut Command, timeout: Duration) -> io::Result<CommandOutput> {
+        #[cfg(feature = "logging")]
+        trace!("SystemRunner::run_with_timeout({:?}, {:?})", cmd, timeout);
+        let deadline = Instant::now() + timeout;
+        run_polling(cmd, || check_deadline(deadline))
+    }
+}
+
+/// Quotes `word` for a POSIX shell, if it contains whitespace or metacharacters.
+fn shell_quote(word: &str) -> Cow<'_, str> {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_=/,.+:@%^".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        Cow::Borrowed(word)
+    } else {
+        Cow::Owned(format!("'{}'", word.replace('\'', "'\\''")))
+    }
+}
+
+/// The command line of `cmd`, as it would be typed in a shell.
+///
+/// Only the words which need it are quoted.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::command_line_string;
+/// let mut cmd = std::process::Command::new("gs");
+/// cmd.args(["-sOutputFile=out.pdf", "my file.pdf"]);
+/// assert_eq!(command_line_string(&cmd), "gs -sOutputFile=out.pdf 'my file.pdf'");
+/// ```
+#[doc(alias = "shell_escape")]
+pub fn command_line_string(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|word| shell_quote(&word.to_string_lossy()).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Same as [`command_line_string`].
+pub fn command_to_shell_string(cmd: &Command) -> String {
+    command_line_string(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote() {
+        for word in &[
+            "gs",
+            "-dPDFSETTINGS=/ebook",
+            "-sOutputFile=a%%d.pdf",
+            "C:/x",
+        ] {
+            assert_eq!(shell_quote(word), *word);
+        }
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b.pdf"), "'a b.pdf'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_with_timeout() {
+        let start = Instant::now();
+        let mut cmd = Command::new("sleep");
+        cmd.arg("10");
+        let err = SystemRunner
+            .run_with_timeout(&mut cmd, Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let mut cmd = Command::new("echo");
+        cmd.arg("done");
+        let output = SystemRunner
+            .run_with_timeout(&mut cmd, Duration::from_secs(10))
+            .unwrap();
+        assert_eq!((output.code, output.stdout), (Some(0), b"done\n".to_vec()));
+    }
+
+    #[test]
+    fn test_command_line_string() {
+        let mut cmd = Command::new("/usr/bin/gs");
+        cmd.args([
+            "-q",
+            "-sOutputFile=dir/a b.pdf",
+            "a*.pdf",
+            "[ /Title (x) /DOCINFO pdfmark",
+        ]);
+        assert_eq!(
+            command_line_string(&cmd),
+            "/usr/bin/gs -q '-sOutputFile=dir/a b.pdf' 'a*.pdf' '[ /Title (x) /DOCINFO pdfmark'"
+        );
+        assert_eq!(command_to_shell_string(&cmd), command_line_string(&cmd));
+    }
+}
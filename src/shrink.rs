@@ -0,0 +1,1998 @@
+//! Shrink a single file, deciding whether it is worth processing.
+
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::{
+    format_bytes, read_marker, read_settings_hash, resolve_ghostscript, settings_hash,
+    CommandOutput, Hook, Runner, ShrinkError, ShrinkOptions, SystemRunner, COMPATIBILITY_LEVELS,
+};
+
+/// Reason why a file was not shrunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    /// The input does not have a `.pdf` extension.
+    NotAPdf,
+    /// The input has a `.pdf` extension but does not start with the `%PDF-` header,
+    /// see [`is_pdf`].
+    NoPdfHeader,
+    /// The input is smaller than [`ShrinkOptions::min_size`].
+    TooSmall,
+    /// The input is larger than [`ShrinkOptions::max_size`].
+    TooLarge,
+    /// The input is itself the output of a previous run, see [`ShrinkOptions::shrunk_suffix`]
+    /// and [`ShrinkOptions::marker`].
+    AlreadyShrunk,
+    /// The input has no marker and [`ShrinkOptions::upgrade`] is set.
+    NotMarked,
+    /// The output exists and [`ShrinkOptions::no_clobber`] is set.
+    OutputExists,
+    /// The output was larger than the input and [`ShrinkOptions::keep_smaller`] is set.
+    OutputLarger,
+    /// The output is newer than the input and [`ShrinkOptions::update`] is set.
+    UpToDate,
+    /// The [`ShrinkOptions::pre_process`] command failed.
+    PreProcessFailed,
+    /// The input was completed by a previous run, according to a [`Checkpoint`](crate::Checkpoint).
+    Checkpointed,
+    /// The input to shrink in place is a symbolic link and
+    /// [`ShrinkOptions::follow_input_symlinks`] is not set.
+    Symlink,
+    /// An output with the same content is already stored, see
+    /// [`store_by_content`](crate::store_by_content).
+    Duplicate,
+}
+
+impl SkipReason {
+    /// Short name of the reason, such as `too-small`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkipReason::NotAPdf => "not-a-pdf",
+            SkipReason::NoPdfHeader => "no-pdf-header",
+            SkipReason::TooSmall => "too-small",
+            SkipReason::TooLarge => "too-large",
+            SkipReason::AlreadyShrunk => "already-shrunk",
+            SkipReason::NotMarked => "not-marked",
+            SkipReason::OutputExists => "output-exists",
+            SkipReason::OutputLarger => "output-larger",
+            SkipReason::UpToDate => "up-to-date",
+            SkipReason::PreProcessFailed => "pre-process-failed",
+            SkipReason::Checkpointed => "checkpointed",
+            SkipReason::Symlink => "symlink",
+            SkipReason::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// How Ghostscript terminated, see [`classify_exit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub enum ExitClass {
+    /// Ghostscript succeeded.
+    Success,
+    /// Ghostscript reported an error, but the output is usable.
+    Warning,
+    /// Ghostscript did not produce a usable output.
+    Fatal,
+}
+
+/// Classifies the exit `code` of Ghostscript, knowing whether it produced a valid output.
+///
+/// Ghostscript exits with code 0 on success and 1 when it reports an error,
+/// which can concern a single page or object and still leave a usable output.
+/// Any other code (for instance 255 if it cannot start), or a signal, is fatal,
+/// and so is any exit without a valid output.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{classify_exit, ExitClass};
+/// assert_eq!(classify_exit(Some(0), true), ExitClass::Success);
+/// assert_eq!(classify_exit(Some(1), true), ExitClass::Warning);
+/// assert_eq!(classify_exit(Some(1), false), ExitClass::Fatal);
+/// ```
+pub fn classify_exit(code: Option<i32>, output_valid: bool) -> ExitClass {
+    match code {
+        _ if !output_valid => ExitClass::Fatal,
+        Some(0) => ExitClass::Success,
+        Some(1) => ExitClass::Warning,
+        _ => ExitClass::Fatal,
+    }
+}
+
+/// Memory given to Ghostscript when retrying after it ran out of memory,
+/// unless the options already set at least a quarter of it.
+const RETRY_MEMORY: u64 = 256 * 1024 * 1024;
+
+/// Whether Ghostscript printed `stderr` because it ran out of memory.
+fn is_memory_error(stderr: &[u8]) -> bool {
+    let stderr = String::from_utf8_lossy(stderr);
+    stderr.contains("/VMerror") || stderr.contains("Out of memory")
+}
+
+/// The `options` with [`buffer_space`](ShrinkOptions::buffer_space) and
+/// [`max_bitmap`](ShrinkOptions::max_bitmap) raised to retry after running out of memory.
+fn with_more_memory(options: &ShrinkOptions) -> ShrinkOptions {
+    let more =
+        |bytes: Option<u64>| Some(bytes.map_or(0, |b| b.saturating_mul(4)).max(RETRY_MEMORY));
+    ShrinkOptions {
+        buffer_space: more(options.buffer_space),
+        max_bitmap: more(options.max_bitmap),
+        ..options.clone()
+    }
+}
+
+/// Runs the Ghostscript `cmd` shrinking `inpath` into `outpath` and classifies its result.
+///
+/// If the command exceeds [`ShrinkOptions::timeout`], its partial output is removed.
+fn run_ghostscript<R>(
+    runner: &R,
+    cmd: &mut Command,
+    inpath: &Path,
+    outpath: &Path,
+    options: &ShrinkOptions,
+) -> Result<(CommandOutput, ExitClass), ShrinkError>
+where
+    R: Runner + ?Sized,
+{
+    let output = match options.timeout {
+        Some(timeout) => runner.run_with_timeout(cmd, timeout),
+        None => runner.run(cmd),
+    };
+    let output = output.map_err(|source| match options.timeout {
+        Some(timeout) if source.kind() == io::ErrorKind::TimedOut => {
+            let _ = fs::remove_file(outpath);
+            ShrinkError::Timeout {
+                path: inpath.into(),
+                timeout,
+            }
+        }
+        _ => ShrinkError::SpawnFailed {
+            program: cmd.get_program().into(),
+            source,
+        },
+    })?;
+    let valid = is_valid_output(outpath).map_err(finalize_error(outpath))?;
+    let status = classify_exit(output.code, valid);
+    #[cfg(feature = "logging")]
+    trace!("Ghostscript exited with {:?}: {:?}", output.code, status);
+    Ok((output, status))
+}
+
+/// Whether the file `path` looks like a PDF file, starting with the `%PDF-` header.
+///
+/// A file shorter than the header is not a PDF file.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::is_pdf;
+/// let path = std::env::temp_dir().join("pdfshrink-is-pdf-doctest.pdf");
+/// std::fs::write(&path, "%PDF-1.7\n")?;
+/// assert!(is_pdf(&path)?);
+/// std::fs::write(&path, "%PDF")?;
+/// assert!(!is_pdf(&path)?);
+/// # std::fs::remove_file(&path)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn is_pdf<P>(path: P) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+{
+    let mut header = [0; 5];
+    let read = fs::File::open(path).and_then(|mut file| file.read_exact(&mut header));
+    match read {
+        Ok(()) => Ok(&header == b"%PDF-"),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether the output `path` of Ghostscript is a PDF file, see [`is_pdf`].
+///
+/// A missing file is not valid, other errors are reported.
+fn is_valid_output(path: &Path) -> io::Result<bool> {
+    match is_pdf(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        valid => valid,
+    }
+}
+
+/// Sizes of a successfully shrunk file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize))]
+pub struct ShrinkReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Either [`ExitClass::Success`] or [`ExitClass::Warning`].
+    pub status: ExitClass,
+}
+
+impl ShrinkReport {
+    /// Report comparing an `input` with an already produced `output`, without running Ghostscript.
+    pub fn compare<P, Q>(input: P, output: Q) -> Result<ShrinkReport, ShrinkError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let (input, output) = (input.as_ref(), output.as_ref());
+        let input_bytes = stat_input(input)?.len();
+        let output_bytes = fs::metadata(output)
+            .map_err(|source| ShrinkError::Io {
+                path: output.into(),
+                source,
+            })?
+            .len();
+        Ok(ShrinkReport {
+            input: input.into(),
+            output: output.into(),
+            input_bytes,
+            output_bytes,
+            status: ExitClass::Success,
+        })
+    }
+
+    /// Size of the output relative to the input.
+    pub fn ratio(&self) -> f64 {
+        if self.input_bytes == 0 {
+            return 1.0;
+        }
+        self.output_bytes as f64 / self.input_bytes as f64
+    }
+
+    /// Fraction of the input saved by the output, negative if the output is larger.
+    pub fn saved_ratio(&self) -> f64 {
+        1.0 - self.ratio()
+    }
+
+    /// The sizes of the input and of the output, with the percentage saved,
+    /// like `4.0 KiB -> 1.0 KiB (75% smaller)`.
+    pub fn sizes(&self) -> String {
+        let saved = self.saved_ratio();
+        format!(
+            "{} -> {} ({:.0}% {})",
+            format_bytes(self.input_bytes),
+            format_bytes(self.output_bytes),
+            100.0 * saved.abs(),
+            if saved < 0.0 { "larger" } else { "smaller" }
+        )
+    }
+}
+
+impl fmt::Display for ShrinkReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}: {} -> {} ({:.1}%)",
+            self.input.display(),
+            self.output.display(),
+            format_bytes(self.input_bytes),
+            format_bytes(self.output_bytes),
+            100.0 * self.ratio()
+        )
+    }
+}
+
+/// Result of processing a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShrinkOutcome {
+    /// Ghostscript produced the output.
+    Shrunk(ShrinkReport),
+    /// The file was left alone.
+    Skipped { reason: SkipReason },
+}
+
+impl ShrinkOutcome {
+    /// The report of the kept output, or `None` if the file was skipped or its output
+    /// discarded, e.g. by [`ShrinkOptions::keep_smaller`].
+    pub fn report(&self) -> Option<&ShrinkReport> {
+        match self {
+            ShrinkOutcome::Shrunk(report) => Some(report),
+            ShrinkOutcome::Skipped { .. } => None,
+        }
+    }
+}
+
+fn stat_input(path: &Path) -> Result<fs::Metadata, ShrinkError> {
+    fs::metadata(path).map_err(|source| ShrinkError::ReadInput {
+        path: path.into(),
+        source,
+    })
+}
+
+/// Wraps an error occurred on the output `path` after running Ghostscript.
+fn finalize_error(path: &Path) -> impl FnOnce(io::Error) -> ShrinkError + '_ {
+    move |source| ShrinkError::FinalizeOutput {
+        path: path.into(),
+        source,
+    }
+}
+
+/// Whether the name of `inpath` ends with `.<suffix>.pdf`.
+fn has_suffix(inpath: &Path, suffix: &OsStr) -> bool {
+    match inpath.file_stem() {
+        Some(stem) => {
+            let stem = stem.to_string_lossy();
+            let suffix = suffix.to_string_lossy();
+            stem.len() > suffix.len() + 1
+                && stem.ends_with(&*suffix)
+                && stem[..stem.len() - suffix.len()].ends_with(&['.', '-'][..])
+        }
+        None => false,
+    }
+}
+
+/// Runs `hook` on `path` through `runner`.
+///
+/// On failure returns the exit code (if any) and a description of the problem.
+fn run_hook<R>(
+    runner: &R,
+    hook: &Hook,
+    placeholder: &str,
+    path: &Path,
+) -> Result<(), (Option<i32>, String)>
+where
+    R: Runner + ?Sized,
+{
+    match runner.run(&mut hook.command(placeholder, path)) {
+        Ok(CommandOutput { code: Some(0), .. }) => Ok(()),
+        Ok(output) => Err((
+            output.code,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )),
+        Err(e) => Err((None, e.to_string())),
+    }
+}
+
+/// Checks the filtering rules of `options` which can be decided before running Ghostscript.
+///
+/// Returns the reason to skip `inpath`, or `None` if it should be shrunk into `outpath`.
+pub fn check_skip<P, Q>(
+    inpath: P,
+    outpath: Q,
+    options: &ShrinkOptions,
+) -> Result<Option<SkipReason>, ShrinkError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let inpath = inpath.as_ref();
+    let outpath = outpath.as_ref();
+    let reason = if inpath.extension() != Some("pdf".as_ref()) {
+        Some(SkipReason::NotAPdf)
+    } else if matches!(&options.shrunk_suffix, Some(s) if has_suffix(inpath, s)) {
+        Some(SkipReason::AlreadyShrunk)
+    } else {
+        check_skip_contents(inpath, outpath, options)?
+    };
+    #[cfg(feature = "logging")]
+    trace!("check_skip({:?}, {:?}) = {:?}", inpath, outpath, reason);
+    Ok(reason)
+}
+
+/// The rules of [`check_skip`] which do not depend on the name of `inpath`.
+fn check_skip_contents(
+    inpath: &Path,
+    outpath: &Path,
+    options: &ShrinkOptions,
+) -> Result<Option<SkipReason>, ShrinkError> {
+    let input = stat_input(inpath)?;
+    let read_error = |source| ShrinkError::ReadInput {
+        path: inpath.into(),
+        source,
+    };
+    let reason = if matches!(options.min_size, Some(min) if input.len() < min) {
+        Some(SkipReason::TooSmall)
+    } else if matches!(options.max_size, Some(max) if input.len() > max) {
+        Some(SkipReason::TooLarge)
+    } else if !is_pdf(inpath).map_err(read_error)? {
+        Some(SkipReason::NoPdfHeader)
+    } else if let Some(reason) = check_upgrade(inpath, options).map_err(read_error)? {
+        Some(reason)
+    } else if options.marker
+        && read_marker(inpath).map_err(read_error)? == Some(options.settings_string())
+    {
+        Some(SkipReason::AlreadyShrunk)
+    } else if let Ok(output) = fs::metadata(outpath) {
+        let newer = match (input.modified(), output.modified()) {
+            (Ok(i), Ok(o)) => o >= i,
+            _ => false,
+        };
+        if options.update && newer {
+            Some(SkipReason::UpToDate)
+        } else if options.no_clobber {
+            Some(SkipReason::OutputExists)
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+    Ok(reason)
+}
+
+/// The rule of [`ShrinkOptions::upgrade`]: skip the inputs which are not marked
+/// or whose settings hash is the current one.
+fn check_upgrade(inpath: &Path, options: &ShrinkOptions) -> io::Result<Option<SkipReason>> {
+    if !options.upgrade {
+        return Ok(None);
+    }
+    let hash = match read_settings_hash(inpath)? {
+        Some(hash) => Some(hash),
+        // marked before the hash was stored
+        None => read_marker(inpath)?.map(|settings| settings_hash(&settings)),
+    };
+    Ok(match hash {
+        None => Some(SkipReason::NotMarked),
+        Some(hash) if hash == options.settings_hash() => Some(SkipReason::AlreadyShrunk),
+        Some(_) => None,
+    })
+}
+
+/// Hardlinks `input` to `output` with `hard_link`, or copies it if they are on different devices.
+fn link_or_copy<F>(input: &Path, output: &Path, hard_link: F) -> io::Result<()>
+where
+    F: FnOnce(&Path, &Path) -> io::Result<()>,
+{
+    match hard_link(input, output) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            #[cfg(feature = "logging")]
+            trace!("cannot link {:?} to {:?}, copying: {}", input, output, e);
+            fs::copy(input, output).map(drop)
+        }
+        result => result,
+    }
+}
+
+/// Creates the missing directories containing `outpath`, without touching any file.
+///
+/// Every output goes through this before running Ghostscript, whatever the naming scheme,
+/// because the parent of the output may not exist even if the input's does.
+/// Returns the directory of the output if it had to be created.
+pub fn ensure_parent_dir<P>(outpath: P) -> Result<Option<PathBuf>, ShrinkError>
+where
+    P: AsRef<Path>,
+{
+    create_parent_dir(outpath.as_ref(), None)
+}
+
+/// Like [`ensure_parent_dir`], but the directories created get the permissions of the
+/// directory `source`, rather than the ones given by the umask (Unix only).
+///
+/// This is used with [`ShrinkOptions::preserve_dir_mode`], where `source` is the
+/// directory of the input.
+pub fn ensure_parent_dir_like<P, Q>(outpath: P, source: Q) -> Result<Option<PathBuf>, ShrinkError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    create_parent_dir(outpath.as_ref(), Some(source.as_ref()))
+}
+
+fn create_parent_dir(
+    outpath: &Path,
+    source: Option<&Path>,
+) -> Result<Option<PathBuf>, ShrinkError> {
+    let dir = match outpath.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() && !dir.is_dir() => dir,
+        _ => return Ok(None),
+    };
+    let create_error = |source| ShrinkError::CreateOutputDir {
+        path: dir.into(),
+        source,
+    };
+    let permissions = match source {
+        Some(source) => Some(fs::metadata(source).map_err(create_error)?.permissions()),
+        None => None,
+    };
+    // the missing ancestors, which are created along with `dir`
+    let missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|d| !d.as_os_str().is_empty() && !d.exists())
+        .collect();
+    fs::create_dir_all(dir).map_err(create_error)?;
+    #[cfg(feature = "logging")]
+    trace!("ensure_parent_dir: created {:?}", dir);
+    #[cfg(unix)]
+    if let Some(permissions) = permissions {
+        for created in missing {
+            fs::set_permissions(created, permissions.clone()).map_err(create_error)?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (permissions, missing);
+    Ok(Some(dir.into()))
+}
+
+/// Directory containing `inpath`, which is `.` for a bare file name.
+pub(crate) fn input_dir(inpath: &Path) -> &Path {
+    match inpath.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Checks that Ghostscript will be able to write the output `path`, by opening it for
+/// writing without truncating it, or by creating and removing it.
+///
+/// Only the lack of permissions is reported, other errors are left to Ghostscript.
+fn check_writable(path: &Path) -> Result<(), ShrinkError> {
+    let probe = if path.exists() {
+        fs::OpenOptions::new().write(true).open(path).map(drop)
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .and_then(|_| fs::remove_file(path))
+    };
+    match probe {
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            #[cfg(feature = "logging")]
+            trace!("check_writable({:?}): {}", path, e);
+            Err(ShrinkError::OutputNotWritable { path: path.into() })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether `a` and `b` are the same existing file, possibly through different paths.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Default suffix of the temporary files written when shrinking in place,
+/// see [`ShrinkOptions::temp_suffix`].
+pub const DEFAULT_TEMP_SUFFIX: &str = ".pdfshrink.tmp";
+
+/// Temporary file Ghostscript writes to when shrinking `inpath` in place,
+/// named with the [`DEFAULT_TEMP_SUFFIX`].
+///
+/// It is a hidden file next to the input, so that it can be renamed over it.
+/// Returns `None` if `inpath` does not name a file.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::in_place_temp_path;
+/// assert_eq!(
+///     in_place_temp_path("dir/name.pdf"),
+///     Some("dir/.name.pdf.pdfshrink.tmp".into())
+/// );
+/// assert_eq!(in_place_temp_path("dir/.."), None);
+/// ```
+pub fn in_place_temp_path<P>(inpath: P) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    in_place_temp_path_with(inpath, DEFAULT_TEMP_SUFFIX)
+}
+
+/// Like [`in_place_temp_path`], but with the given `suffix`.
+pub fn in_place_temp_path_with<P, S>(inpath: P, suffix: S) -> Option<PathBuf>
+where
+    P: AsRef<Path>,
+    S: AsRef<OsStr>,
+{
+    let inpath = inpath.as_ref();
+    let mut name = std::ffi::OsString::from(".");
+    name.push(inpath.file_name()?);
+    name.push(suffix);
+    Some(inpath.with_file_name(name))
+}
+
+/// Shrinks `inpath`, replacing it with the output.
+///
+/// Ghostscript never writes to the file it is reading, which would corrupt the input
+/// (or fail with a sharing violation on Windows): it writes to [`in_place_temp_path`],
+/// which is renamed over the input only if the shrink succeeds, and removed otherwise.
+/// Exiting with a [`Warning`](ExitClass::Warning) does not count as a success here, since
+/// the output may be incomplete: the input is kept and [`ShrinkError::NonZeroExit`] returned.
+/// Its name ends with [`ShrinkOptions::temp_suffix`]; a stale one left by an interrupted
+/// run is replaced.
+/// An output larger than the input is discarded, as if
+/// [`keep_smaller`](ShrinkOptions::keep_smaller) were set,
+/// unless [`always_replace`](ShrinkOptions::always_replace) is set.
+/// If `inpath` is a symbolic link, the file it points to is replaced, so that the link
+/// is preserved, unless [`follow_input_symlinks`](ShrinkOptions::follow_input_symlinks)
+/// is unset, in which case the input is skipped.
+/// The options [`no_clobber`](ShrinkOptions::no_clobber), [`update`](ShrinkOptions::update)
+/// and [`link_unchanged`](ShrinkOptions::link_unchanged) do not apply, and the
+/// post-processing command runs on the replaced input.
+pub fn shrink_in_place<P>(inpath: P, options: &ShrinkOptions) -> Result<ShrinkOutcome, ShrinkError>
+where
+    P: AsRef<Path>,
+{
+    if options.ghostscript.is_some() {
+        shrink_in_place_with(&SystemRunner, inpath, options)
+    } else {
+        let options = ShrinkOptions {
+            ghostscript: Some(resolve_ghostscript()?),
+            ..options.clone()
+        };
+        shrink_in_place_with(&SystemRunner, inpath, &options)
+    }
+}
+
+/// Like [`shrink_in_place`], but executes the commands with `runner`.
+pub fn shrink_in_place_with<R, P>(
+    runner: &R,
+    inpath: P,
+    options: &ShrinkOptions,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+{
+    let link = inpath.as_ref();
+    let is_symlink = fs::symlink_metadata(link).is_ok_and(|m| m.file_type().is_symlink());
+    let target;
+    let inpath = if is_symlink {
+        if !options.follow_input_symlinks {
+            return Ok(ShrinkOutcome::Skipped {
+                reason: SkipReason::Symlink,
+            });
+        }
+        // renaming over the link would replace it with a regular file
+        target = fs::canonicalize(link).map_err(|source| ShrinkError::ReadInput {
+            path: link.into(),
+            source,
+        })?;
+        #[cfg(feature = "logging")]
+        trace!("shrink_in_place_with: {:?} links to {:?}", link, target);
+        &target
+    } else {
+        link
+    };
+    let temp = in_place_temp_path_with(inpath, &options.temp_suffix).ok_or_else(|| {
+        ShrinkError::InvalidInputPath {
+            path: inpath.into(),
+        }
+    })?;
+    let temp_options = ShrinkOptions {
+        no_clobber: false,
+        update: false,
+        // replacing the input with a larger file defeats the purpose
+        keep_smaller: !options.always_replace,
+        link_unchanged: false,
+        post_process: None,
+        // the input stays in place when the validation fails
+        fallback_copy: false,
+        ..options.clone()
+    };
+    let mut report = match shrink_file_with(runner, inpath, &temp, &temp_options) {
+        Ok(ShrinkOutcome::Shrunk(report)) if report.status == ExitClass::Success => report,
+        Ok(ShrinkOutcome::Shrunk(_)) => {
+            // an error on a page may have left a truncated output
+            let _ = fs::remove_file(&temp);
+            return Err(ShrinkError::NonZeroExit {
+                code: Some(1),
+                stderr: String::new(),
+            });
+        }
+        other => {
+            let _ = fs::remove_file(&temp);
+            return other;
+        }
+    };
+    #[cfg(feature = "logging")]
+    trace!("shrink_in_place_with: renaming {:?} to {:?}", temp, inpath);
+    if let Err(e) = fs::rename(&temp, inpath) {
+        let _ = fs::remove_file(&temp);
+        return Err(finalize_error(inpath)(e));
+    }
+    report.input = link.into();
+    report.output = link.into();
+
+    if let Some(hook) = &options.post_process {
+        run_hook(runner, hook, "{output}", inpath).map_err(|(code, stderr)| {
+            ShrinkError::PostProcessFailed {
+                command: hook.template(),
+                code,
+                stderr,
+            }
+        })?;
+    }
+    Ok(ShrinkOutcome::Shrunk(report))
+}
+
+/// Shrinks `inpath` into `outpath` running Ghostscript.
+///
+/// If [`ShrinkOptions::ghostscript`] is not set, the executable is located
+/// with [`resolve_ghostscript`].
+/// If Ghostscript runs out of memory, it is run once more with a larger
+/// [`buffer_space`](ShrinkOptions::buffer_space) and [`max_bitmap`](ShrinkOptions::max_bitmap).
+/// See [`shrink_file_with`] to customize how the command is executed.
+pub fn shrink_file<P, Q>(
+    inpath: P,
+    outpath: Q,
+    options: &ShrinkOptions,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    if options.ghostscript.is_some() {
+        shrink_file_with(&SystemRunner, inpath, outpath, options)
+    } else {
+        let options = ShrinkOptions {
+            ghostscript: Some(resolve_ghostscript()?),
+            ..options.clone()
+        };
+        shrink_file_with(&SystemRunner, inpath, outpath, &options)
+    }
+}
+
+/// Shrinks `inpath` into `outpath`, executing Ghostscript through `runner`.
+pub fn shrink_file_with<R, P, Q>(
+    runner: &R,
+    inpath: P,
+    outpath: Q,
+    options: &ShrinkOptions,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    R: Runner + ?Sized,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let inpath = inpath.as_ref();
+    let outpath = outpath.as_ref();
+
+    if let Some(reason) = check_skip(inpath, outpath, options)? {
+        return Ok(ShrinkOutcome::Skipped { reason });
+    }
+    shrink_checked(runner, inpath, outpath, options, |_| {})
+}
+
+/// Shrinks the already opened `input` into `outpath` running Ghostscript,
+/// without opening the input again by its path.
+///
+/// If [`ShrinkOptions::ghostscript`] is not set, the executable is located
+/// with [`resolve_ghostscript`].
+/// See [`shrink_open_file_with`] to customize how the command is executed.
+pub fn shrink_open_file<Q>(
+    input: &fs::File,
+    outpath: Q,
+    options: &ShrinkOptions,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    Q: AsRef<Path>,
+{
+    if options.ghostscript.is_some() {
+        shrink_open_file_with(&SystemRunner, input, outpath, options)
+    } else {
+        let options = ShrinkOptions {
+            ghostscript: Some(resolve_ghostscript()?),
+            ..options.clone()
+        };
+        shrink_open_file_with(&SystemRunner, input, outpath, &options)
+    }
+}
+
+/// Shrinks the already opened `input` into `outpath`, executing Ghostscript through `runner`.
+///
+/// On Unix Ghostscript inherits the descriptor of `input` and reads it as `/dev/fd/N`,
+/// which is also the input path of the report; elsewhere it reads a temporary copy
+/// written next to `outpath`. The rules of [`check_skip`] based on the name of the input
+/// do not apply, and the [`pre_process`](ShrinkOptions::pre_process) hook is not run,
+/// since it could only reach the input through its path.
+pub fn shrink_open_file_with<R, Q>(
+    runner: &R,
+    input: &fs::File,
+    outpath: Q,
+    options: &ShrinkOptions,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    R: Runner + ?Sized,
+    Q: AsRef<Path>,
+{
+    let outpath = outpath.as_ref();
+    let options = ShrinkOptions {
+        pre_process: None,
+        ..options.clone()
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let fd = input.as_raw_fd();
+        let inpath = PathBuf::from(format!("/dev/fd/{}", fd));
+        #[cfg(feature = "logging")]
+        trace!("shrink_open_file_with({:?}, {:?})", inpath, outpath);
+        if let Some(reason) = check_skip_contents(&inpath, outpath, &options)? {
+            return Ok(ShrinkOutcome::Skipped { reason });
+        }
+        shrink_checked(runner, &inpath, outpath, &options, |cmd| {
+            inherit_fd(cmd, fd)
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        use std::io::{Seek, SeekFrom};
+        let mut copy = outpath.as_os_str().to_owned();
+        copy.push(".input.tmp");
+        let copy = PathBuf::from(copy);
+        #[cfg(feature = "logging")]
+        trace!("shrink_open_file_with({:?}, {:?})", copy, outpath);
+        let mut input = input;
+        input
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| fs::File::create(&copy))
+            .and_then(|mut file| io::copy(&mut input, &mut file))
+            .map_err(|source| ShrinkError::ReadInput {
+                path: copy.clone(),
+                source,
+            })?;
+        let result = match check_skip_contents(&copy, outpath, &options) {
+            Ok(Some(reason)) => Ok(ShrinkOutcome::Skipped { reason }),
+            Ok(None) => shrink_checked(runner, &copy, outpath, &options, |_| {}),
+            Err(e) => Err(e),
+        };
+        let _ = fs::remove_file(&copy);
+        result
+    }
+}
+
+/// Lets the Ghostscript `cmd` inherit the descriptor `fd`, which the standard library
+/// opens with the close-on-exec flag.
+#[cfg(unix)]
+fn inherit_fd(cmd: &mut Command, fd: std::os::unix::io::RawFd) {
+    use std::os::unix::process::CommandExt;
+    // SAFETY: the closure only calls fcntl, which is async-signal-safe, and it
+    // changes the flags of the descriptor in the child process alone.
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
+            }
+        });
+    }
+}
+
+/// Shrinks `inpath` into `outpath` once [`check_skip`] decided to, calling `prepare`
+/// on each Ghostscript command before running it.
+fn shrink_checked<R, F>(
+    runner: &R,
+    inpath: &Path,
+    outpath: &Path,
+    options: &ShrinkOptions,
+    prepare: F,
+) -> Result<ShrinkOutcome, ShrinkError>
+where
+    R: Runner + ?Sized,
+    F: Fn(&mut Command),
+{
+    // Ghostscript would truncate the input while reading it
+    if same_file(inpath, outpath) {
+        return Err(ShrinkError::OutputIsInput {
+            path: inpath.into(),
+        });
+    }
+
+    if !COMPATIBILITY_LEVELS.contains(&options.compatibility_level.as_str()) {
+        return Err(ShrinkError::InvalidCompatibilityLevel {
+            level: options.compatibility_level.clone(),
+        });
+    }
+
+    if let Some(hook) = &options.pre_process {
+        if let Err(_e) = run_hook(runner, hook, "{input}", inpath) {
+            #[cfg(feature = "logging")]
+            trace!("pre-processing {:?} failed: {:?}", inpath, _e);
+            return Ok(ShrinkOutcome::Skipped {
+                reason: SkipReason::PreProcessFailed,
+            });
+        }
+    }
+
+    let input_bytes = stat_input(inpath)?.len();
+
+    if options.preserve_dir_mode {
+        ensure_parent_dir_like(outpath, input_dir(inpath))?;
+    } else {
+        ensure_parent_dir(outpath)?;
+    }
+    check_writable(outpath)?;
+
+    let mut cmd = options.build_command(inpath, outpath);
+    prepare(&mut cmd);
+    let (mut output, mut status) = run_ghostscript(runner, &mut cmd, inpath, outpath, options)?;
+    if status == ExitClass::Fatal && is_memory_error(&output.stderr) {
+        let retry_options = with_more_memory(options);
+        #[cfg(feature = "logging")]
+        trace!(
+            "Ghostscript ran out of memory, retrying with BufferSpace={:?} MaxBitmap={:?}",
+            retry_options.buffer_space,
+            retry_options.max_bitmap
+        );
+        let mut cmd = retry_options.build_command(inpath, outpath);
+        prepare(&mut cmd);
+        let (retry_output, retry_status) =
+            run_ghostscript(runner, &mut cmd, inpath, outpath, &retry_options)?;
+        output = retry_output;
+        status = retry_status;
+    }
+    match status {
+        ExitClass::Success | ExitClass::Warning => {}
+        ExitClass::Fatal => {
+            // a partial output would look up to date to `update` and `no_clobber`
+            let _ = fs::remove_file(outpath);
+            return Err(if output.success() {
+                ShrinkError::InvalidOutput {
+                    path: outpath.into(),
+                }
+            } else {
+                ShrinkError::NonZeroExit {
+                    code: output.code,
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                }
+            });
+        }
+    }
+    let output_bytes = fs::metadata(outpath)
+        .map_err(finalize_error(outpath))?
+        .len();
+
+    if options.keep_smaller && output_bytes > input_bytes {
+        fs::remove_file(outpath).map_err(finalize_error(outpath))?;
+        if options.link_unchanged {
+            link_or_copy(inpath, outpath, |from, to| fs::hard_link(from, to))
+                .map_err(finalize_error(outpath))?;
+        }
+        return Ok(ShrinkOutcome::Skipped {
+            reason: SkipReason::OutputLarger,
+        });
+    }
+
+    #[cfg(unix)]
+    if let Some(mode) = options.output_mode {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(outpath, fs::Permissions::from_mode(mode))
+            .map_err(finalize_error(outpath))?;
+    }
+
+    if let Some(hook) = &options.validate {
+        if let Err((code, stderr)) = run_hook(runner, hook, "{output}", outpath) {
+            #[cfg(feature = "logging")]
+            trace!("validation of {:?} failed: {:?}", outpath, code);
+            fs::remove_file(outpath).map_err(finalize_error(outpath))?;
+            if options.fallback_copy {
+                fs::copy(inpath, outpath).map_err(finalize_error(outpath))?;
+            }
+            return Err(ShrinkError::ValidationFailed {
+                command: hook.template(),
+                code,
+                stderr,
+            });
+        }
+    }
+
+    if let Some(hook) = &options.post_process {
+        run_hook(runner, hook, "{output}", outpath).map_err(|(code, stderr)| {
+            ShrinkError::PostProcessFailed {
+                command: hook.template(),
+                code,
+                stderr,
+            }
+        })?;
+    }
+
+    Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+        input: inpath.into(),
+        output: outpath.into(),
+        input_bytes,
+        output_bytes,
+        status,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{output_file, pdf_bytes, MockRunner, TempDir};
+
+    fn skipped(reason: SkipReason) -> ShrinkOutcome {
+        ShrinkOutcome::Skipped { reason }
+    }
+
+    #[test]
+    fn test_shrink_in_place() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let temp = dir.path().join(".doc.pdf.pdfshrink.tmp");
+
+        let runner = MockRunner::gs(40);
+        let outcome = shrink_in_place_with(&runner, &input, &ShrinkOptions::default()).unwrap();
+        let written = output_file(&runner.calls()[0]).unwrap();
+        assert_ne!(written, input);
+        assert_eq!(written, temp);
+        match outcome {
+            ShrinkOutcome::Shrunk(report) => {
+                assert_eq!(report.output, input);
+                assert_eq!((report.input_bytes, report.output_bytes), (100, 40));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(fs::metadata(&input).unwrap().len(), 40);
+        assert!(!temp.exists());
+
+        // a failure leaves the input alone
+        let runner = MockRunner::new(|argv| {
+            fs::write(output_file(argv).unwrap(), b"garbage")?;
+            Ok(CommandOutput {
+                code: Some(1),
+                ..Default::default()
+            })
+        });
+        assert!(shrink_in_place_with(&runner, &input, &ShrinkOptions::default()).is_err());
+        assert_eq!(fs::read(&input).unwrap(), crate::testing::pdf_bytes(40));
+        assert!(!temp.exists());
+
+        // an exit code 1 after writing part of the output is not enough to replace it
+        let runner = MockRunner::new(|argv| {
+            fs::write(output_file(argv).unwrap(), b"%PDF-1.4\n")?;
+            Ok(CommandOutput {
+                code: Some(1),
+                ..Default::default()
+            })
+        });
+        let outcome = shrink_in_place_with(&runner, &input, &ShrinkOptions::default());
+        assert!(matches!(
+            outcome,
+            Err(ShrinkError::NonZeroExit { code: Some(1), .. })
+        ));
+        assert_eq!(fs::read(&input).unwrap(), crate::testing::pdf_bytes(40));
+        assert!(!temp.exists());
+
+        // so does a larger output, even without keep_smaller
+        let runner = MockRunner::gs(60);
+        let outcome = shrink_in_place_with(&runner, &input, &ShrinkOptions::default());
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::OutputLarger));
+        assert_eq!(fs::read(&input).unwrap(), crate::testing::pdf_bytes(40));
+        assert!(!temp.exists());
+
+        let outcome = shrink_in_place_with(&runner, dir.path().join(".."), &Default::default());
+        assert!(matches!(outcome, Err(ShrinkError::InvalidInputPath { .. })));
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shrink_in_place_symlink() {
+        let dir = TempDir::new();
+        let target = dir.pdf("doc.pdf", 100);
+        let link = dir.path().join("link.pdf");
+        std::os::unix::fs::symlink("doc.pdf", &link).unwrap();
+
+        let runner = MockRunner::gs(40);
+        let outcome = shrink_in_place_with(&runner, &link, &ShrinkOptions::default()).unwrap();
+        assert_eq!(
+            output_file(&runner.calls()[0]).unwrap(),
+            dir.path().join(".doc.pdf.pdfshrink.tmp")
+        );
+        assert_eq!(
+            outcome.report().map(|r| r.output.clone()),
+            Some(link.clone())
+        );
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read(&target).unwrap(), pdf_bytes(40));
+
+        let options = ShrinkOptions {
+            follow_input_symlinks: false,
+            ..Default::default()
+        };
+        let outcome = shrink_in_place_with(&runner, &link, &options).unwrap();
+        assert_eq!(outcome, skipped(SkipReason::Symlink));
+        assert_eq!(runner.calls().len(), 1);
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn test_always_replace() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let options = ShrinkOptions {
+            always_replace: true,
+            ..Default::default()
+        };
+
+        for &len in &[40, 60] {
+            let runner = MockRunner::gs(len);
+            let outcome = shrink_in_place_with(&runner, &input, &options).unwrap();
+            assert_eq!(outcome.report().map(|r| r.output_bytes), Some(len as u64));
+            assert_eq!(fs::read(&input).unwrap(), pdf_bytes(len));
+        }
+        assert!(!dir.path().join(".doc.pdf.pdfshrink.tmp").exists());
+    }
+
+    #[test]
+    fn test_output_is_input() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let runner = MockRunner::gs(40);
+        let alias = dir.path().join(".").join("doc.pdf");
+        assert!(matches!(
+            shrink_file_with(&runner, &input, &alias, &ShrinkOptions::default()),
+            Err(ShrinkError::OutputIsInput { .. })
+        ));
+        assert!(runner.calls().is_empty());
+        assert_eq!(fs::metadata(&input).unwrap().len(), 100);
+    }
+
+    #[test]
+    fn test_invalid_compatibility_level() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let runner = MockRunner::gs(40);
+        let options = ShrinkOptions::default().compatibility_level("1.7");
+        assert!(shrink_file_with(&runner, &input, &output, &options).is_ok());
+
+        let options = ShrinkOptions::default().compatibility_level("1.7 -dNOSAFER");
+        assert!(matches!(
+            shrink_file_with(&runner, &input, &output, &options),
+            Err(ShrinkError::InvalidCompatibilityLevel { level }) if level == "1.7 -dNOSAFER"
+        ));
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_memory_error_retry() {
+        let dir = TempDir::new();
+        let input = dir.pdf("huge.pdf", 100);
+        let output = dir.path().join("huge.shrunk.pdf");
+        // Ghostscript runs out of memory unless given more buffer space
+        let runner = MockRunner::new(|argv| {
+            if argv
+                .iter()
+                .any(|a| a.to_string_lossy().starts_with("-dBufferSpace="))
+            {
+                fs::write(output_file(argv).unwrap(), pdf_bytes(40))?;
+                Ok(CommandOutput {
+                    code: Some(0),
+                    ..Default::default()
+                })
+            } else {
+                Ok(CommandOutput {
+                    code: Some(1),
+                    stderr: b"Error: /VMerror in --run--\n".to_vec(),
+                    ..Default::default()
+                })
+            }
+        });
+        let outcome = shrink_file_with(&runner, &input, &output, &ShrinkOptions::default());
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[1].contains(&"-dBufferSpace=268435456".into()));
+        assert!(calls[1].contains(&"-dMaxBitmap=268435456".into()));
+
+        // the retry happens once, raising the memory set by the options
+        fs::remove_file(&output).unwrap();
+        let runner = MockRunner::failing(1, "Error: /VMerror in --run--\n");
+        let options = ShrinkOptions {
+            buffer_space: Some(100_000_000),
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Err(ShrinkError::NonZeroExit { .. })));
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls[0].contains(&"-dBufferSpace=100000000".into()));
+        assert!(calls[1].contains(&"-dBufferSpace=400000000".into()));
+
+        // other errors are not retried
+        let runner = MockRunner::failing(1, "Error: /undefined in foo\n");
+        assert!(shrink_file_with(&runner, &input, &output, &Default::default()).is_err());
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let dir = TempDir::new();
+        let input = dir.pdf("slow.pdf", 100);
+        let output = dir.path().join("slow.shrunk.pdf");
+        // Ghostscript is killed after writing part of the output
+        let runner = MockRunner::new(|argv| {
+            fs::write(output_file(argv).unwrap(), &pdf_bytes(40)[..10])?;
+            Err(io::ErrorKind::TimedOut.into())
+        });
+        let options = ShrinkOptions {
+            timeout: Some(std::time::Duration::from_secs(1)),
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Err(ShrinkError::Timeout { .. })));
+        assert_eq!(outcome.unwrap_err().kind(), "timeout");
+        assert!(!output.exists());
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_compare() {
+        let dir = TempDir::new();
+        let input = dir.pdf("a.pdf", 4096);
+        let output = dir.pdf("a.shrunk.pdf", 1024);
+        let report = ShrinkReport::compare(&input, &output).unwrap();
+        assert_eq!((report.input_bytes, report.output_bytes), (4096, 1024));
+        assert_eq!(report.ratio(), 0.25);
+        assert_eq!(report.saved_ratio(), 0.75);
+        assert!(report.to_string().ends_with(": 4.0 KiB -> 1.0 KiB (25.0%)"));
+        assert_eq!(report.sizes(), "4.0 KiB -> 1.0 KiB (75% smaller)");
+        let grown = ShrinkReport::compare(&output, &input).unwrap();
+        assert_eq!(grown.saved_ratio(), -3.0);
+        assert_eq!(grown.sizes(), "1.0 KiB -> 4.0 KiB (300% larger)");
+
+        assert!(matches!(
+            ShrinkReport::compare(dir.path().join("missing.pdf"), &output),
+            Err(ShrinkError::ReadInput { .. })
+        ));
+        assert!(matches!(
+            ShrinkReport::compare(&input, dir.path().join("missing.pdf")),
+            Err(ShrinkError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_has_suffix() {
+        let s = OsStr::new("shrunk");
+        assert!(has_suffix("a.shrunk.pdf".as_ref(), s));
+        assert!(has_suffix("dir/a b.shrunk.pdf".as_ref(), s));
+        assert!(!has_suffix("a.pdf".as_ref(), s));
+        assert!(!has_suffix("ashrunk.pdf".as_ref(), s));
+        assert!(!has_suffix(".shrunk.pdf".as_ref(), s));
+        assert!(has_suffix("a-shrunk.pdf".as_ref(), s));
+        assert!(!has_suffix("-shrunk.pdf".as_ref(), s));
+    }
+
+    #[test]
+    fn test_not_a_pdf() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.txt", 100);
+        let runner = MockRunner::gs(10);
+        let outcome = shrink_file_with(
+            &runner,
+            &input,
+            dir.path().join("out.pdf"),
+            &Default::default(),
+        );
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::NotAPdf));
+        assert!(runner.calls().is_empty());
+    }
+
+    #[test]
+    fn test_no_pdf_header() {
+        let dir = TempDir::new();
+        let output = dir.path().join("out.pdf");
+        let runner = MockRunner::gs(10);
+        for content in &[&b"<html></html>"[..], b"%PDF", b""] {
+            let input = dir.path().join("page.pdf");
+            fs::write(&input, content).unwrap();
+            assert!(!is_pdf(&input).unwrap());
+            let outcome = shrink_file_with(&runner, &input, &output, &Default::default());
+            assert_eq!(outcome.unwrap(), skipped(SkipReason::NoPdfHeader));
+        }
+        assert!(runner.calls().is_empty());
+        assert!(is_pdf(dir.pdf("doc.pdf", 5)).unwrap());
+        assert!(is_pdf(dir.path().join("missing.pdf")).is_err());
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let runner = MockRunner::gs(10);
+
+        let options = ShrinkOptions {
+            min_size: Some(101),
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::TooSmall));
+
+        let options = ShrinkOptions {
+            max_size: Some(99),
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::TooLarge));
+
+        assert!(runner.calls().is_empty());
+
+        let options = ShrinkOptions {
+            min_size: Some(100),
+            max_size: Some(100),
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+    }
+
+    #[test]
+    fn test_already_shrunk() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.shrunk.pdf", 100);
+        let options = ShrinkOptions {
+            shrunk_suffix: Some("shrunk".into()),
+            ..Default::default()
+        };
+        let outcome = check_skip(&input, dir.path().join("doc.shrunk.shrunk.pdf"), &options);
+        assert_eq!(outcome.unwrap(), Some(SkipReason::AlreadyShrunk));
+    }
+
+    #[test]
+    fn test_marker() {
+        let dir = TempDir::new();
+        let options = ShrinkOptions {
+            marker: true,
+            ..Default::default()
+        };
+        let marked = |name: &str, settings: &str| {
+            let path = dir.path().join(name);
+            let mut bytes = crate::testing::pdf_bytes(100);
+            bytes.extend_from_slice(b"<< /Producer (GPL Ghostscript) /PdfShrink ");
+            bytes.extend_from_slice(crate::ps_string(settings).as_bytes());
+            bytes.extend_from_slice(b" >>\n%%EOF\n");
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let runner = MockRunner::gs(10);
+
+        let same = marked("same.pdf", &options.settings_string());
+        let outcome = shrink_file_with(&runner, &same, dir.path().join("same.out.pdf"), &options);
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::AlreadyShrunk));
+        assert!(runner.calls().is_empty());
+
+        let other = ShrinkOptions {
+            device_resolution: Some(300),
+            ..options.clone()
+        };
+        let different = marked("different.pdf", &other.settings_string());
+        let outcome = shrink_file_with(
+            &runner,
+            &different,
+            dir.path().join("different.out.pdf"),
+            &options,
+        );
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        assert_eq!(runner.calls().len(), 1);
+
+        // without the option the marker is ignored
+        let outcome = check_skip(&same, dir.path().join("same.out.pdf"), &Default::default());
+        assert_eq!(outcome.unwrap(), None);
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let dir = TempDir::new();
+        let options = ShrinkOptions {
+            marker: true,
+            upgrade: true,
+            ..Default::default()
+        };
+        let old = ShrinkOptions {
+            device_resolution: Some(300),
+            ..options.clone()
+        };
+        let marked = |name: &str, pdfmark: &str| {
+            let path = dir.path().join(name);
+            let mut bytes = pdf_bytes(100);
+            bytes.extend_from_slice(pdfmark.as_bytes());
+            std::fs::write(&path, bytes).unwrap();
+            path
+        };
+        let check = |path: &Path| check_skip(path, dir.path().join("out.pdf"), &options).unwrap();
+
+        // the stored hash is the current one
+        let current = marked(
+            "current.pdf",
+            &crate::marker_pdfmark(&options.settings_string()),
+        );
+        assert_eq!(check(&current), Some(SkipReason::AlreadyShrunk));
+        // the stored hash differs
+        let outdated = marked(
+            "outdated.pdf",
+            &crate::marker_pdfmark(&old.settings_string()),
+        );
+        assert_eq!(check(&outdated), None);
+        // marked before the hash was stored
+        let legacy = format!(
+            "[ /PdfShrink {} >>",
+            crate::ps_string(&old.settings_string())
+        );
+        assert_eq!(check(&marked("legacy.pdf", &legacy)), None);
+        let legacy = format!(
+            "[ /PdfShrink {} >>",
+            crate::ps_string(&options.settings_string())
+        );
+        assert_eq!(
+            check(&marked("legacy.pdf", &legacy)),
+            Some(SkipReason::AlreadyShrunk)
+        );
+        // never shrunk
+        assert_eq!(check(&dir.pdf("new.pdf", 100)), Some(SkipReason::NotMarked));
+
+        let runner = MockRunner::gs(10);
+        let outcome = shrink_in_place_with(&runner, &outdated, &options);
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        let args = &runner.calls()[0];
+        let pdfmark = crate::marker_pdfmark(&options.settings_string());
+        assert!(args.iter().any(|arg| *arg == *pdfmark));
+    }
+
+    #[test]
+    fn test_existing_output() {
+        let dir = TempDir::new();
+        let output = dir.pdf("doc.shrunk.pdf", 10);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let input = dir.pdf("doc.pdf", 100);
+
+        let options = ShrinkOptions {
+            no_clobber: true,
+            ..Default::default()
+        };
+        let outcome = check_skip(&input, &output, &options);
+        assert_eq!(outcome.unwrap(), Some(SkipReason::OutputExists));
+
+        // the output is older than the input
+        let options = ShrinkOptions {
+            update: true,
+            ..Default::default()
+        };
+        assert_eq!(check_skip(&input, &output, &options).unwrap(), None);
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let output = dir.pdf("doc.shrunk.pdf", 10);
+        let outcome = check_skip(&input, &output, &options);
+        assert_eq!(outcome.unwrap(), Some(SkipReason::UpToDate));
+
+        assert_eq!(
+            check_skip(&input, &output, &Default::default()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_output_larger() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let runner = MockRunner::gs(200);
+
+        let options = ShrinkOptions {
+            keep_smaller: true,
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&runner, &input, &output, &options).unwrap();
+        assert_eq!(outcome, skipped(SkipReason::OutputLarger));
+        assert_eq!(outcome.report(), None);
+        assert!(!output.exists());
+
+        let outcome = shrink_file_with(&runner, &input, &output, &Default::default()).unwrap();
+        assert_eq!(outcome.report().map(|r| r.output_bytes), Some(200));
+        assert_eq!(
+            outcome,
+            ShrinkOutcome::Shrunk(ShrinkReport {
+                input: input.clone(),
+                output: output.clone(),
+                input_bytes: 100,
+                output_bytes: 200,
+                status: ExitClass::Success,
+            })
+        );
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[test]
+    fn test_post_process() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let options = ShrinkOptions {
+            post_process: Some(Hook::parse("ocrmypdf {output} {output}").unwrap()),
+            ..Default::default()
+        };
+
+        let runner = MockRunner::gs(10);
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0][0], "gs");
+        assert_eq!(
+            calls[1],
+            ["ocrmypdf".as_ref(), output.as_os_str(), output.as_os_str()]
+        );
+
+        let runner = MockRunner::new(|argv| {
+            let code = if argv[0] == "gs" {
+                std::fs::write(crate::testing::output_file(argv).unwrap(), b"%PDF-")?;
+                0
+            } else {
+                2
+            };
+            Ok(CommandOutput {
+                code: Some(code),
+                ..Default::default()
+            })
+        });
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        match outcome {
+            Err(ShrinkError::PostProcessFailed { command, code, .. }) => {
+                assert_eq!(command, "ocrmypdf {output} {output}");
+                assert_eq!(code, Some(2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_temp_suffix() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let temp = dir.path().join(".doc.pdf.audit-tmp");
+        let options = ShrinkOptions {
+            temp_suffix: ".audit-tmp".into(),
+            ..Default::default()
+        };
+        // left over by an interrupted run
+        fs::write(&temp, b"stale").unwrap();
+
+        let runner = MockRunner::gs(40);
+        let outcome = shrink_in_place_with(&runner, &input, &options).unwrap();
+        assert!(matches!(outcome, ShrinkOutcome::Shrunk(_)));
+        assert_eq!(output_file(&runner.calls()[0]).unwrap(), temp);
+        assert_eq!(fs::read(&input).unwrap(), pdf_bytes(40));
+        assert!(!temp.exists());
+
+        // cleaned up after a failure
+        let runner = MockRunner::failing(255, "");
+        assert!(shrink_in_place_with(&runner, &input, &options).is_err());
+        assert!(!temp.exists());
+        assert!(!in_place_temp_path(&input).unwrap().exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        // Ghostscript succeeds, the validator (qpdf) rejects its output
+        let runner = || {
+            MockRunner::new(|argv| {
+                let code = if argv[0] == "gs" {
+                    std::fs::write(output_file(argv).unwrap(), pdf_bytes(10))?;
+                    0
+                } else {
+                    2
+                };
+                Ok(CommandOutput {
+                    code: Some(code),
+                    ..Default::default()
+                })
+            })
+        };
+        let mut options = ShrinkOptions {
+            validate: Some(Hook::parse("qpdf --check").unwrap()),
+            post_process: Some(Hook::parse("ocrmypdf {output} {output}").unwrap()),
+            ..Default::default()
+        };
+
+        let runner_without_fallback = runner();
+        let outcome = shrink_file_with(&runner_without_fallback, &input, &output, &options);
+        match outcome {
+            Err(ShrinkError::ValidationFailed { command, code, .. }) => {
+                assert_eq!(command, "qpdf --check");
+                assert_eq!(code, Some(2));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let calls = runner_without_fallback.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[1],
+            ["qpdf".as_ref(), "--check".as_ref(), output.as_os_str()]
+        );
+        assert!(!output.exists());
+
+        options.fallback_copy = true;
+        let outcome = shrink_file_with(&runner(), &input, &output, &options);
+        assert!(matches!(outcome, Err(ShrinkError::ValidationFailed { .. })));
+        assert_eq!(
+            std::fs::read(&output).unwrap(),
+            std::fs::read(&input).unwrap()
+        );
+
+        // in place, the input is kept
+        let outcome = shrink_in_place_with(&runner(), &input, &options);
+        assert!(matches!(outcome, Err(ShrinkError::ValidationFailed { .. })));
+        assert_eq!(std::fs::read(&input).unwrap(), pdf_bytes(100));
+        assert!(!in_place_temp_path(&input).unwrap().exists());
+
+        // a passing validator
+        options.post_process = None;
+        let runner = MockRunner::gs(10);
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[test]
+    fn test_pre_process() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let options = ShrinkOptions {
+            pre_process: Some(Hook::parse("qpdf --replace-input {input}").unwrap()),
+            ..Default::default()
+        };
+
+        // success continues with the shrinking
+        let runner = MockRunner::gs(10);
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert!(matches!(outcome, Ok(ShrinkOutcome::Shrunk(_))));
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(
+            calls[0],
+            [
+                "qpdf".as_ref(),
+                "--replace-input".as_ref(),
+                input.as_os_str()
+            ]
+        );
+        assert_eq!(calls[1][0], "gs");
+
+        // failure skips the file
+        std::fs::remove_file(&output).unwrap();
+        let runner = MockRunner::failing(3, "qpdf: damaged file");
+        let outcome = shrink_file_with(&runner, &input, &output, &options);
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::PreProcessFailed));
+        assert_eq!(runner.calls().len(), 1);
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_fatal_removes_output() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("out.pdf");
+        // Ghostscript fails after writing part of the output
+        let runner = MockRunner::new(|argv| {
+            fs::write(output_file(argv).unwrap(), b"%PDF-1.4\n")?;
+            Ok(CommandOutput {
+                code: Some(2),
+                stderr: b"Error: /undefined".to_vec(),
+                ..Default::default()
+            })
+        });
+        let outcome = shrink_file_with(&runner, &input, &output, &Default::default());
+        assert!(matches!(outcome, Err(ShrinkError::NonZeroExit { .. })));
+        assert!(!output.exists());
+
+        // success, but garbage written
+        let runner = MockRunner::new(|argv| {
+            fs::write(output_file(argv).unwrap(), b"")?;
+            Ok(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+        });
+        let outcome = shrink_file_with(&runner, &input, &output, &Default::default());
+        assert!(matches!(outcome, Err(ShrinkError::InvalidOutput { .. })));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_non_zero_exit() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let runner = MockRunner::failing(1, "Error: /undefined");
+        let outcome = shrink_file_with(
+            &runner,
+            &input,
+            dir.path().join("out.pdf"),
+            &Default::default(),
+        );
+        match outcome {
+            Err(ShrinkError::NonZeroExit { code, stderr }) => {
+                assert_eq!(code, Some(1));
+                assert_eq!(stderr, "Error: /undefined");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let runner = MockRunner::gs(10);
+
+        for &mode in &[0o664, 0o600] {
+            let options = ShrinkOptions {
+                output_mode: Some(mode),
+                ..Default::default()
+            };
+            shrink_file_with(&runner, &input, &output, &options).unwrap();
+            let permissions = fs::metadata(&output).unwrap().permissions();
+            assert_eq!(permissions.mode() & 0o7777, mode);
+        }
+    }
+
+    #[test]
+    fn test_link_unchanged() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let options = ShrinkOptions {
+            keep_smaller: true,
+            link_unchanged: true,
+            ..Default::default()
+        };
+        let outcome = shrink_file_with(&MockRunner::gs(200), &input, &output, &options);
+        assert_eq!(outcome.unwrap(), skipped(SkipReason::OutputLarger));
+        assert_eq!(fs::read(&output).unwrap(), fs::read(&input).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let (i, o) = (stat_input(&input).unwrap(), fs::metadata(&output).unwrap());
+            assert_eq!((i.dev(), i.ino()), (o.dev(), o.ino()));
+        }
+    }
+
+    #[test]
+    fn test_link_or_copy_fallback() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("doc.shrunk.pdf");
+        let cross_device = |_: &Path, _: &Path| Err(io::ErrorKind::CrossesDevices.into());
+        link_or_copy(&input, &output, cross_device).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), crate::testing::pdf_bytes(100));
+
+        // other errors are reported
+        let denied = |_: &Path, _: &Path| Err(io::ErrorKind::PermissionDenied.into());
+        let other = dir.path().join("other.pdf");
+        assert!(link_or_copy(&input, &other, denied).is_err());
+        assert!(!other.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_not_writable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let readonly = dir.path().join("readonly");
+        fs::create_dir(&readonly).unwrap();
+        fs::set_permissions(&readonly, fs::Permissions::from_mode(0o555)).unwrap();
+        if fs::write(readonly.join("probe"), b"").is_ok() {
+            eprintln!("skipping test_output_not_writable: permissions are not enforced");
+            return;
+        }
+
+        let runner = MockRunner::gs(10);
+        let output = readonly.join("doc.shrunk.pdf");
+        let outcome = shrink_file_with(&runner, &input, &output, &Default::default());
+        assert!(
+            matches!(&outcome, Err(ShrinkError::OutputNotWritable { path }) if *path == output),
+            "{:?}",
+            outcome
+        );
+
+        // an existing output without write permission
+        let output = dir.pdf("doc.shrunk.pdf", 10);
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o444)).unwrap();
+        let outcome = shrink_file_with(&runner, &input, &output, &Default::default());
+        assert!(matches!(
+            outcome,
+            Err(ShrinkError::OutputNotWritable { .. })
+        ));
+        assert!(runner.calls().is_empty());
+
+        // the probe leaves no file behind
+        let output = dir.path().join("new.shrunk.pdf");
+        check_writable(&output).unwrap();
+        assert!(!output.exists());
+        fs::set_permissions(&readonly, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_ensure_parent_dir() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("shrunk/deep/doc.pdf");
+
+        assert_eq!(
+            ensure_parent_dir(&output).unwrap(),
+            Some(dir.path().join("shrunk/deep"))
+        );
+        assert!(output.parent().unwrap().is_dir());
+        assert!(!output.exists());
+        assert_eq!(ensure_parent_dir(&output).unwrap(), None);
+        assert_eq!(ensure_parent_dir("doc.pdf").unwrap(), None);
+
+        // existing files are left alone
+        assert!(ensure_parent_dir(input.join("doc.pdf")).is_err());
+        assert_eq!(fs::read(&input).unwrap(), crate::testing::pdf_bytes(100));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_ensure_parent_dir_like() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new();
+        let source = dir.path().join("source");
+        fs::create_dir(&source).unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o750)).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+
+        let output = dir.path().join("out/deep/doc.pdf");
+        assert_eq!(
+            ensure_parent_dir_like(&output, &source).unwrap(),
+            Some(dir.path().join("out/deep"))
+        );
+        assert_eq!(mode(&dir.path().join("out")), 0o750);
+        assert_eq!(mode(&dir.path().join("out/deep")), 0o750);
+        // existing directories are left alone
+        assert_ne!(mode(dir.path()), 0o750);
+
+        let options = ShrinkOptions {
+            preserve_dir_mode: true,
+            ..Default::default()
+        };
+        let input = source.join("doc.pdf");
+        fs::write(&input, pdf_bytes(100)).unwrap();
+        let output = dir.path().join("shrunk/doc.pdf");
+        shrink_file_with(&MockRunner::gs(10), &input, &output, &options).unwrap();
+        assert_eq!(mode(&dir.path().join("shrunk")), 0o750);
+
+        // the source is only read when a directory is created
+        assert!(ensure_parent_dir_like(&output, dir.path().join("missing")).is_ok());
+        assert!(matches!(
+            ensure_parent_dir_like(dir.path().join("new/doc.pdf"), dir.path().join("missing")),
+            Err(ShrinkError::CreateOutputDir { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_parent_in_every_mode() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        // as if the outputs were relocated under a directory which does not exist yet
+        let relocated = dir.path().join("out/doc.pdf");
+        for outpath in [
+            crate::pdf_with_suffix(&relocated, "shrunk"),
+            crate::pdf_with_dash_suffix(&relocated, "shrunk"),
+            crate::pdf_into_subdir(&relocated, "shrunk"),
+        ] {
+            let outpath = outpath.unwrap();
+            let _ = fs::remove_dir_all(dir.path().join("out"));
+            let runner = MockRunner::gs(50);
+            let outcome =
+                shrink_file_with(&runner, &input, &outpath, &ShrinkOptions::default()).unwrap();
+            assert!(matches!(outcome, ShrinkOutcome::Shrunk(_)), "{:?}", outpath);
+            assert_eq!(fs::metadata(&outpath).unwrap().len(), 50);
+        }
+    }
+
+    #[test]
+    fn test_io_error_phases() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("out/doc.pdf");
+        let shrink = |runner: &MockRunner, input: &Path, output: &Path| {
+            shrink_file_with(runner, input, output, &Default::default())
+        };
+
+        let missing = dir.path().join("missing.pdf");
+        match shrink(&MockRunner::gs(10), &missing, &output) {
+            Err(ShrinkError::ReadInput { path, .. }) => assert_eq!(path, missing),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // a file is in the way of the output directory
+        let blocked = input.join("out.pdf");
+        match shrink(&MockRunner::gs(10), &input, &blocked) {
+            Err(ShrinkError::CreateOutputDir { path, .. }) => assert_eq!(path, input),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let runner = MockRunner::new(|_| Err(io::ErrorKind::NotFound.into()));
+        match shrink(&runner, &input, &output) {
+            Err(ShrinkError::SpawnFailed { program, .. }) => assert_eq!(program, Path::new("gs")),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(output.parent().unwrap().is_dir());
+
+        // a directory is in the way of the output
+        fs::create_dir(&output).unwrap();
+        match shrink(&MockRunner::succeeding(), &input, &output) {
+            Err(ShrinkError::FinalizeOutput { path, .. }) => assert_eq!(path, output),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_classify_exit() {
+        use ExitClass::*;
+        let table = [
+            (Some(0), true, Success),
+            (Some(0), false, Fatal),
+            (Some(1), true, Warning),
+            (Some(1), false, Fatal),
+            (Some(255), true, Fatal),
+            (Some(-1), true, Fatal),
+            (None, true, Fatal),
+        ];
+        for &(code, valid, class) in &table {
+            assert_eq!(classify_exit(code, valid), class, "{:?} {}", code, valid);
+        }
+    }
+
+    #[test]
+    fn test_exit_status() {
+        let dir = TempDir::new();
+        let input = dir.pdf("doc.pdf", 100);
+        let output = dir.path().join("out.pdf");
+
+        // an error on a page, but the output was written
+        let runner = MockRunner::new(|argv| {
+            fs::write(crate::testing::output_file(argv).unwrap(), b"%PDF-1.4\n")?;
+            Ok(CommandOutput {
+                code: Some(1),
+                ..Default::default()
+            })
+        });
+        match shrink_file_with(&runner, &input, &output, &Default::default()) {
+            Ok(ShrinkOutcome::Shrunk(report)) => assert_eq!(report.status, ExitClass::Warning),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // success, but garbage written
+        let runner = MockRunner::new(|argv| {
+            fs::write(crate::testing::output_file(argv).unwrap(), b"")?;
+            Ok(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+        });
+        assert!(matches!(
+            shrink_file_with(&runner, &input, &output, &Default::default()),
+            Err(ShrinkError::InvalidOutput { .. })
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+//! Per-file overrides of the options, read from a sidecar file.
+//!
+//! The sidecar of `name.pdf` is the TOML file `name.pdf.pdfshrink.toml` next to it.
+//! Its keys are a subset of the fields of [`ShrinkOptions`], for example
+//!
+//! ```toml
+//! color_resolution = 300
+//! gray_resolution = 300
+//! last_page = 10
+//! ```
+
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use serde::Deserialize;
+
+use crate::{ShrinkError, ShrinkOptions};
+
+/// Extension appended to the name of an input to obtain its sidecar.
+pub const SIDECAR_EXTENSION: &str = "pdfshrink.toml";
+
+/// Options which can be overridden by a sidecar, or set by a [preset](crate::Presets).
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Overrides {
+    first_page: Option<u32>,
+    last_page: Option<u32>,
+    color_resolution: Option<u32>,
+    gray_resolution: Option<u32>,
+    mono_resolution: Option<u32>,
+    device_resolution: Option<u32>,
+    flatten_forms: Option<bool>,
+    grayscale: Option<bool>,
+    fast_web_view: Option<bool>,
+    strip_metadata: Option<bool>,
+    preserve_bookmarks: Option<bool>,
+    keep_smaller: Option<bool>,
+}
+
+impl Overrides {
+    pub(crate) fn apply(self, options: &mut ShrinkOptions) {
+        macro_rules! set {
+            ($($field:ident),*) => {
+                $(if let Some(value) = self.$field {
+                    options.$field = value;
+                })*
+            };
+        }
+        options.first_page = self.first_page.or(options.first_page);
+        options.last_page = self.last_page.or(options.last_page);
+        options.device_resolution = self.device_resolution.or(options.device_resolution);
+        set!(
+            color_resolution,
+            gray_resolution,
+            mono_resolution,
+            flatten_forms,
+            grayscale,
+            fast_web_view,
+            strip_metadata,
+            preserve_bookmarks,
+            keep_smaller
+        );
+    }
+}
+
+/// Path of the sidecar of `inpath`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::sidecar_path;
+/// assert_eq!(sidecar_path("dir/name.pdf"), std::path::Path::new("dir/name.pdf.pdfshrink.toml"));
+/// ```
+pub fn sidecar_path<P>(inpath: P) -> PathBuf
+where
+    P: AsRef<Path>,
+{
+    let mut path = OsString::from(inpath.as_ref());
+    path.push(".");
+    path.push(SIDECAR_EXTENSION);
+    path.into()
+}
+
+/// The `options` for `inpath`, with the overrides of its sidecar applied, if it exists.
+pub fn with_sidecar<P>(inpath: P, options: &ShrinkOptions) -> Result<ShrinkOptions, ShrinkError>
+where
+    P: AsRef<Path>,
+{
+    let path = sidecar_path(inpath);
+    let mut options = options.clone();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(options),
+        Err(source) => return Err(ShrinkError::Io { path, source }),
+    };
+    let overrides: Overrides = match toml::from_str(&text) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            return Err(ShrinkError::InvalidSidecar {
+                path,
+                message: e.message().into(),
+            })
+        }
+    };
+    #[cfg(feature = "logging")]
+    trace!("with_sidecar: {:?} = {:?}", path, overrides);
+    overrides.apply(&mut options);
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_sidecar_overrides() {
+        let dir = TempDir::new();
+        let special = dir.pdf("special.pdf", 100);
+        let plain = dir.pdf("plain.pdf", 100);
+        std::fs::write(
+            sidecar_path(&special),
+            "color_resolution = 300\nlast_page = 2\n",
+        )
+        .unwrap();
+
+        let global = ShrinkOptions {
+            gray_resolution: 100,
+            ..Default::default()
+        };
+        let options = with_sidecar(&special, &global).unwrap();
+        assert_eq!(options.color_resolution, 300);
+        assert_eq!(options.gray_resolution, 100);
+        assert_eq!(options.last_page, Some(2));
+        assert_eq!(with_sidecar(&plain, &global).unwrap(), global);
+
+        std::fs::write(sidecar_path(&plain), "colour_resolution = 300\n").unwrap();
+        assert!(matches!(
+            with_sidecar(&plain, &global),
+            Err(ShrinkError::InvalidSidecar { .. })
+        ));
+    }
+}
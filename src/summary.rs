@@ -0,0 +1,518 @@
+//! Totals of a batch of files.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{ShrinkError, ShrinkOutcome, ShrinkReport, SkipReason};
+
+/// Formats a size in bytes with a binary multiple, like the sizes accepted by
+/// [`parse_size`](crate::parse_size).
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::format_bytes;
+/// assert_eq!(format_bytes(512), "512 B");
+/// assert_eq!(format_bytes(1536), "1.5 KiB");
+/// assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+/// ```
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Labels of the buckets of [`BatchSummary::histogram`], by the percentage of the input saved.
+pub const HISTOGRAM_LABELS: [&str; 7] = [
+    "grew", "0-10%", "10-25%", "25-50%", "50-75%", "75-90%", "90-100%",
+];
+
+/// Lower bounds of the buckets after the first, in percent saved.
+const HISTOGRAM_BOUNDS: [f64; 6] = [0.0, 10.0, 25.0, 50.0, 75.0, 90.0];
+
+/// Longest bar drawn by [`BatchSummary::histogram_bars`].
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Index in [`HISTOGRAM_LABELS`] of the bucket of an output `ratio` times the size of its input.
+///
+/// Each bucket includes its lower bound, so that saving exactly 10% falls in `10-25%`.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{histogram_bucket, HISTOGRAM_LABELS};
+/// assert_eq!(HISTOGRAM_LABELS[histogram_bucket(0.6)], "25-50%");
+/// assert_eq!(HISTOGRAM_LABELS[histogram_bucket(1.2)], "grew");
+/// ```
+pub fn histogram_bucket(ratio: f64) -> usize {
+    // tolerate the rounding of the ratio, so that 0.9 saves 10% and not 9.999...%
+    let saved = 100.0 * (1.0 - ratio) + 1e-9;
+    HISTOGRAM_BOUNDS
+        .iter()
+        .rposition(|&bound| saved >= bound)
+        .map_or(0, |i| i + 1)
+}
+
+/// Representation of the [`BatchSummary`] printed at the end of a run, see [`BatchSummary::render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SummaryFormat {
+    /// The [`Display`](fmt::Display) line, such as `2 shrunk, 0 failed`.
+    #[default]
+    Text,
+    /// A JSON object, see [`BatchSummary::to_json`].
+    #[cfg(feature = "json")]
+    Json,
+    /// A CSV header and a record, see [`BatchSummary::to_csv`].
+    Csv,
+}
+
+impl std::str::FromStr for SummaryFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SummaryFormat, String> {
+        match s {
+            "text" => Ok(SummaryFormat::Text),
+            #[cfg(feature = "json")]
+            "json" => Ok(SummaryFormat::Json),
+            "csv" => Ok(SummaryFormat::Csv),
+            _ => Err(format!("invalid summary format {:?}", s)),
+        }
+    }
+}
+
+/// Columns of [`BatchSummary::to_csv`].
+pub const SUMMARY_CSV_HEADER: &str = "shrunk,failed,skipped,input_bytes,output_bytes,grown";
+
+/// Compares the ratios of two reports exactly, without dividing.
+fn cmp_ratio(a: &ShrinkReport, b: &ShrinkReport) -> Ordering {
+    let lhs = u128::from(a.output_bytes) * u128::from(b.input_bytes);
+    let rhs = u128::from(b.output_bytes) * u128::from(a.input_bytes);
+    lhs.cmp(&rhs)
+}
+
+/// Tally of the outcomes of a batch, printed at the end of a run.
+///
+/// # Examples
+///
+/// ```
+/// # use pdfshrink::{BatchSummary, SkipReason};
+/// let mut summary = BatchSummary::default();
+/// summary.record_skip(SkipReason::TooSmall);
+/// summary.record_failure();
+/// assert_eq!(summary.to_string(), "0 shrunk, 1 failed, skipped: 1 too-small");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub shrunk: usize,
+    pub failed: usize,
+    /// Number of skipped files, for each reason.
+    pub skipped: BTreeMap<SkipReason, usize>,
+    /// Total size of the shrunk inputs.
+    pub input_bytes: u64,
+    /// Total size of their outputs.
+    pub output_bytes: u64,
+    /// Inputs whose output is larger.
+    pub grown: Vec<PathBuf>,
+    /// Report with the smallest [`ratio`](ShrinkReport::ratio), the first one in case of ties.
+    ///
+    /// Empty inputs have no meaningful ratio and are ignored.
+    pub best: Option<ShrinkReport>,
+    /// Report with the largest [`ratio`](ShrinkReport::ratio), see [`best`](Self::best).
+    pub worst: Option<ShrinkReport>,
+    /// Number of shrunk files in each bucket of [`HISTOGRAM_LABELS`], see [`histogram_bucket`].
+    ///
+    /// Empty inputs are ignored, like for [`best`](Self::best).
+    pub histogram: [usize; 7],
+}
+
+impl BatchSummary {
+    /// Counts the result of processing a file.
+    pub fn record(&mut self, result: &Result<ShrinkOutcome, ShrinkError>) {
+        match result {
+            Ok(ShrinkOutcome::Shrunk(report)) => {
+                self.shrunk += 1;
+                self.input_bytes += report.input_bytes;
+                self.output_bytes += report.output_bytes;
+                if report.output_bytes > report.input_bytes {
+                    self.grown.push(report.input.clone());
+                }
+                self.record_ratio(report);
+            }
+            Ok(ShrinkOutcome::Skipped { reason }) => self.record_skip(*reason),
+            Err(_) => self.record_failure(),
+        }
+    }
+
+    fn record_ratio(&mut self, report: &ShrinkReport) {
+        if report.input_bytes == 0 {
+            return;
+        }
+        self.histogram[histogram_bucket(report.ratio())] += 1;
+        if self
+            .best
+            .as_ref()
+            .is_none_or(|best| cmp_ratio(report, best) == Ordering::Less)
+        {
+            self.best = Some(report.clone());
+        }
+        if self
+            .worst
+            .as_ref()
+            .is_none_or(|worst| cmp_ratio(report, worst) == Ordering::Greater)
+        {
+            self.worst = Some(report.clone());
+        }
+    }
+
+    /// Line naming the inputs which shrank the most and the least, with the percentage saved.
+    ///
+    /// Returns `None` if no ratio was measured.
+    pub fn extremes(&self) -> Option<String> {
+        let saved =
+            |r: &ShrinkReport| format!("{} ({:.0}%)", r.input.display(), 100.0 * r.saved_ratio());
+        match (&self.best, &self.worst) {
+            (Some(best), Some(worst)) => {
+                Some(format!("best: {}, worst: {}", saved(best), saved(worst)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The [`histogram`](Self::histogram) as text, a line per bucket with a bar
+    /// proportional to its count.
+    ///
+    /// Returns `None` if no ratio was measured.
+    pub fn histogram_bars(&self) -> Option<String> {
+        let max = *self.histogram.iter().max()?;
+        if max == 0 {
+            return None;
+        }
+        let lines: Vec<String> = HISTOGRAM_LABELS
+            .iter()
+            .zip(&self.histogram)
+            .map(|(label, &count)| {
+                let width = (count * HISTOGRAM_WIDTH).div_ceil(max);
+                format!("{:>7} |{} {}", label, "#".repeat(width), count)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// The summary as a JSON object.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::{BatchSummary, SkipReason};
+    /// let mut summary = BatchSummary::default();
+    /// summary.record_skip(SkipReason::TooSmall);
+    /// let json = summary.to_json();
+    /// assert_eq!(json["shrunk"], 0);
+    /// assert_eq!(json["skipped"]["too-small"], 1);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        let skipped: serde_json::Map<String, serde_json::Value> = self
+            .skipped
+            .iter()
+            .map(|(reason, &count)| (reason.name().into(), count.into()))
+            .collect();
+        serde_json::json!({
+            "shrunk": self.shrunk,
+            "failed": self.failed,
+            "skipped": skipped,
+            "input_bytes": self.input_bytes,
+            "output_bytes": self.output_bytes,
+            "grown": self.grown,
+        })
+    }
+
+    /// The summary as CSV: the [`SUMMARY_CSV_HEADER`] and a record, each followed by a newline.
+    ///
+    /// The skipped files are counted together and the grown ones are only counted.
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{}\n{},{},{},{},{},{}\n",
+            SUMMARY_CSV_HEADER,
+            self.shrunk,
+            self.failed,
+            self.skipped_total(),
+            self.input_bytes,
+            self.output_bytes,
+            self.grown.len()
+        )
+    }
+
+    /// The summary in the given `format`, without a trailing newline for [`SummaryFormat::Text`]
+    /// and [`SummaryFormat::Json`].
+    pub fn render(&self, format: SummaryFormat) -> String {
+        match format {
+            SummaryFormat::Text => self.to_string(),
+            #[cfg(feature = "json")]
+            SummaryFormat::Json => self.to_json().to_string(),
+            SummaryFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// Counts a file skipped for `reason`.
+    pub fn record_skip(&mut self, reason: SkipReason) {
+        *self.skipped.entry(reason).or_insert(0) += 1;
+    }
+
+    /// Counts a file which could not be processed.
+    pub fn record_failure(&mut self) {
+        self.failed += 1;
+    }
+
+    /// Whether any output was larger than its input, including the ones
+    /// discarded by [`ShrinkOptions::keep_smaller`](crate::ShrinkOptions::keep_smaller).
+    pub fn grew(&self) -> bool {
+        !self.grown.is_empty() || self.skipped.contains_key(&SkipReason::OutputLarger)
+    }
+
+    /// Total number of skipped files.
+    pub fn skipped_total(&self) -> usize {
+        self.skipped.values().sum()
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} shrunk, {} failed", self.shrunk, self.failed)?;
+        if !self.skipped.is_empty() {
+            let reasons: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(reason, count)| format!("{} {}", count, reason.name()))
+                .collect();
+            write!(f, ", skipped: {}", reasons.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExitClass;
+    use std::path::Path;
+
+    #[test]
+    fn test_batch_summary() {
+        let shrunk = |input_bytes, output_bytes| {
+            Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+                input: "in.pdf".into(),
+                output: "out.pdf".into(),
+                input_bytes,
+                output_bytes,
+                status: ExitClass::Success,
+            }))
+        };
+        let skipped = |reason| Ok(ShrinkOutcome::Skipped { reason });
+
+        let mut summary = BatchSummary::default();
+        for result in [
+            shrunk(100, 40),
+            skipped(SkipReason::TooSmall),
+            skipped(SkipReason::AlreadyShrunk),
+            Err(ShrinkError::InvalidOutput {
+                path: "out.pdf".into(),
+            }),
+            skipped(SkipReason::TooSmall),
+            shrunk(50, 10),
+            skipped(SkipReason::TooSmall),
+        ] {
+            summary.record(&result);
+        }
+
+        assert_eq!(summary.shrunk, 2);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.skipped[&SkipReason::TooSmall], 3);
+        assert_eq!(summary.skipped[&SkipReason::AlreadyShrunk], 1);
+        assert_eq!(summary.skipped.get(&SkipReason::NotAPdf), None);
+        assert_eq!(summary.skipped_total(), 4);
+        assert_eq!((summary.input_bytes, summary.output_bytes), (150, 50));
+        assert!(!summary.grew());
+        assert_eq!(
+            summary.to_string(),
+            "2 shrunk, 1 failed, skipped: 3 too-small, 1 already-shrunk"
+        );
+    }
+
+    #[test]
+    fn test_extremes() {
+        let report = |input: &str, input_bytes, output_bytes| ShrinkReport {
+            input: input.into(),
+            output: "out.pdf".into(),
+            input_bytes,
+            output_bytes,
+            status: ExitClass::Success,
+        };
+        let mut summary = BatchSummary::default();
+        assert_eq!(summary.extremes(), None);
+        for r in [
+            report("a.pdf", 1000, 500),
+            report("empty.pdf", 0, 100),
+            report("b.pdf", 100, 8),
+            report("c.pdf", 1000, 970),
+            // ties keep the first
+            report("d.pdf", 200, 16),
+            report("e.pdf", 2000, 1940),
+        ] {
+            summary.record(&Ok(ShrinkOutcome::Shrunk(r)));
+        }
+        assert_eq!(summary.best.as_ref().unwrap().input, Path::new("b.pdf"));
+        assert_eq!(summary.worst.as_ref().unwrap().input, Path::new("c.pdf"));
+        assert_eq!(
+            summary.extremes().unwrap(),
+            "best: b.pdf (92%), worst: c.pdf (3%)"
+        );
+
+        summary.record(&Ok(ShrinkOutcome::Shrunk(report("f.pdf", 100, 120))));
+        assert_eq!(
+            summary.extremes().unwrap(),
+            "best: b.pdf (92%), worst: f.pdf (-20%)"
+        );
+
+        let mut summary = BatchSummary::default();
+        summary.record(&Ok(ShrinkOutcome::Shrunk(report("empty.pdf", 0, 0))));
+        assert_eq!(summary.extremes(), None);
+    }
+
+    #[test]
+    fn test_histogram_bucket() {
+        for &(ratio, label) in &[
+            (1.5, "grew"),
+            (1.001, "grew"),
+            (1.0, "0-10%"),
+            (0.95, "0-10%"),
+            (0.9, "10-25%"),
+            (0.8, "10-25%"),
+            (0.75, "25-50%"),
+            (0.5, "50-75%"),
+            (0.3, "50-75%"),
+            (0.25, "75-90%"),
+            (0.1, "90-100%"),
+            (0.0, "90-100%"),
+        ] {
+            assert_eq!(
+                HISTOGRAM_LABELS[histogram_bucket(ratio)],
+                label,
+                "{}",
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut summary = BatchSummary::default();
+        assert_eq!(summary.histogram_bars(), None);
+        for &(input_bytes, output_bytes) in &[(100, 95), (100, 40), (100, 45), (0, 10), (10, 20)] {
+            summary.record(&Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+                input: "in.pdf".into(),
+                output: "out.pdf".into(),
+                input_bytes,
+                output_bytes,
+                status: ExitClass::Success,
+            })));
+        }
+        assert_eq!(summary.histogram, [1, 1, 0, 0, 2, 0, 0]);
+        let bars = summary.histogram_bars().unwrap();
+        let lines: Vec<&str> = bars.lines().collect();
+        assert_eq!(lines.len(), HISTOGRAM_LABELS.len());
+        assert_eq!(lines[0], format!("   grew |{} 1", "#".repeat(20)));
+        assert_eq!(lines[2], " 10-25% | 0");
+        assert_eq!(lines[4], format!(" 50-75% |{} 2", "#".repeat(40)));
+    }
+
+    #[test]
+    fn test_summary_formats() {
+        let mut summary = BatchSummary::default();
+        for &(input, input_bytes, output_bytes) in &[("a.pdf", 100, 50), ("b.pdf", 100, 120)] {
+            summary.record(&Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+                input: input.into(),
+                output: "out.pdf".into(),
+                input_bytes,
+                output_bytes,
+                status: ExitClass::Success,
+            })));
+        }
+        summary.record_skip(SkipReason::TooSmall);
+        summary.record_skip(SkipReason::NotAPdf);
+        summary.record_failure();
+
+        assert_eq!(
+            summary.render(SummaryFormat::Text),
+            "2 shrunk, 1 failed, skipped: 1 not-a-pdf, 1 too-small"
+        );
+
+        let csv = summary.render(SummaryFormat::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines, [SUMMARY_CSV_HEADER, "2,1,2,200,170,1"]);
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+
+        #[cfg(feature = "json")]
+        {
+            let json: serde_json::Value =
+                serde_json::from_str(&summary.render(SummaryFormat::Json)).unwrap();
+            assert_eq!(
+                json,
+                serde_json::json!({
+                    "shrunk": 2,
+                    "failed": 1,
+                    "skipped": {"not-a-pdf": 1, "too-small": 1},
+                    "input_bytes": 200,
+                    "output_bytes": 170,
+                    "grown": ["b.pdf"],
+                })
+            );
+        }
+
+        for &(name, format) in &[("text", SummaryFormat::Text), ("csv", SummaryFormat::Csv)] {
+            assert_eq!(name.parse(), Ok(format));
+        }
+        assert!("xml".parse::<SummaryFormat>().is_err());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(10 * 1024 * 1024 + 512 * 1024), "10.5 MiB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GiB");
+        assert_eq!(format_bytes(2048 << 40), "2048.0 TiB");
+    }
+
+    #[test]
+    fn test_grown() {
+        let mut summary = BatchSummary::default();
+        for &(input, input_bytes, output_bytes) in &[("a.pdf", 100, 50), ("b.pdf", 100, 120)] {
+            summary.record(&Ok(ShrinkOutcome::Shrunk(ShrinkReport {
+                input: input.into(),
+                output: "out.pdf".into(),
+                input_bytes,
+                output_bytes,
+                status: ExitClass::Success,
+            })));
+        }
+        assert!(summary.grew());
+        assert_eq!(summary.grown, [PathBuf::from("b.pdf")]);
+
+        let mut summary = BatchSummary::default();
+        summary.record_skip(SkipReason::OutputLarger);
+        assert!(summary.grew());
+        assert!(summary.grown.is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! Helpers shared by the unit tests.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::{CommandOutput, Runner};
+
+/// Temporary directory removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> TempDir {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pdfshrink-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Creates the file `name` with `len` bytes of PDF-looking content.
+    pub fn pdf(&self, name: &str, len: usize) -> PathBuf {
+        let path = self.0.join(name);
+        fs::write(&path, pdf_bytes(len)).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// `len` bytes starting with a PDF header.
+pub fn pdf_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = b"%PDF-1.4\n".to_vec();
+    bytes.resize(len.max(bytes.len()), b'x');
+    bytes
+}
+
+/// Path written by Ghostscript according to the `-sOutputFile=` argument.
+pub fn output_file(argv: &[OsString]) -> Option<PathBuf> {
+    argv.iter().find_map(|arg| {
+        arg.to_str()?
+            .strip_prefix("-sOutputFile=")
+            .map(|path| PathBuf::from(path.replace("%%", "%")))
+    })
+}
+
+type Respond = dyn Fn(&[OsString]) -> io::Result<CommandOutput> + Send + Sync;
+
+/// [`Runner`] recording the invocations instead of spawning them.
+///
+/// Each recorded invocation contains the program followed by its arguments.
+pub struct MockRunner {
+    pub calls: Mutex<Vec<Vec<OsString>>>,
+    respond: Box<Respond>,
+}
+
+impl MockRunner {
+    pub fn new<F>(respond: F) -> MockRunner
+    where
+        F: Fn(&[OsString]) -> io::Result<CommandOutput> + Send + Sync + 'static,
+    {
+        MockRunner {
+            calls: Mutex::new(Vec::new()),
+            respond: Box::new(respond),
+        }
+    }
+
+    /// Simulates a successful Ghostscript writing `len` bytes to its output.
+    pub fn gs(len: usize) -> MockRunner {
+        MockRunner::new(move |argv| {
+            if let Some(out) = output_file(argv) {
+                fs::write(out, pdf_bytes(len))?;
+            }
+            Ok(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+        })
+    }
+
+    /// Simulates a program terminating successfully without output.
+    pub fn succeeding() -> MockRunner {
+        MockRunner::new(|_| {
+            Ok(CommandOutput {
+                code: Some(0),
+                ..Default::default()
+            })
+        })
+    }
+
+    /// Simulates a program exiting with `code` and printing `stderr`.
+    pub fn failing(code: i32, stderr: &'static str) -> MockRunner {
+        MockRunner::new(move |_| {
+            Ok(CommandOutput {
+                code: Some(code),
+                stderr: stderr.into(),
+                ..Default::default()
+            })
+        })
+    }
+
+    pub fn calls(&self) -> Vec<Vec<OsString>> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl Runner for MockRunner {
+    fn run(&self, cmd: &mut Command) -> io::Result<CommandOutput> {
+        let mut argv = vec![cmd.get_program().to_os_string()];
+        argv.extend(cmd.get_args().map(|a| a.to_os_string()));
+        let result = (self.respond)(&argv);
+        self.calls.lock().unwrap().push(argv);
+        result
+    }
+}
@@ -0,0 +1,118 @@
+//! Collection of the PDF files in a directory tree.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "logging")]
+use log::trace;
+
+use crate::ShrinkError;
+
+/// Files with a `.pdf` extension in the directory `dir` and its subdirectories, sorted.
+///
+/// The files directly in `dir` are at depth 1, the ones in its subdirectories at depth 2
+/// and so on; if `max_depth` is set, deeper files are not collected. Symbolic links to
+/// directories are not followed, so that a link to an ancestor cannot make the walk endless.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use pdfshrink::collect_pdfs;
+/// for path in collect_pdfs("scans", Some(2))? {
+///     println!("{}", path.display());
+/// }
+/// # Ok::<(), pdfshrink::ShrinkError>(())
+/// ```
+pub fn collect_pdfs<P>(dir: P, max_depth: Option<usize>) -> Result<Vec<PathBuf>, ShrinkError>
+where
+    P: AsRef<Path>,
+{
+    let mut pdfs = Vec::new();
+    collect_into(dir.as_ref(), 1, max_depth, &mut pdfs)?;
+    pdfs.sort();
+    #[cfg(feature = "logging")]
+    trace!(
+        "collect_pdfs({:?}, {:?}): {} files",
+        dir.as_ref(),
+        max_depth,
+        pdfs.len()
+    );
+    Ok(pdfs)
+}
+
+fn collect_into(
+    dir: &Path,
+    depth: usize,
+    max_depth: Option<usize>,
+    pdfs: &mut Vec<PathBuf>,
+) -> Result<(), ShrinkError> {
+    if max_depth.is_some_and(|max| depth > max) {
+        return Ok(());
+    }
+    let io_error = |source| ShrinkError::Io {
+        path: dir.into(),
+        source,
+    };
+    for entry in fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(io_error)?;
+        if file_type.is_dir() {
+            collect_into(&path, depth + 1, max_depth, pdfs)?;
+        } else if path.extension() == Some("pdf".as_ref()) && path.is_file() {
+            pdfs.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_collect_pdfs() {
+        let dir = TempDir::new();
+        fs::create_dir_all(dir.path().join("b/c")).unwrap();
+        for name in &["z.pdf", "a.txt", "b/y.pdf", "b/c/x.pdf", "b/c/x.PDF"] {
+            dir.pdf(name, 10);
+        }
+        fs::create_dir(dir.path().join("empty.pdf")).unwrap();
+
+        let pdfs = |max_depth| {
+            collect_pdfs(dir.path(), max_depth)
+                .unwrap()
+                .into_iter()
+                .map(|p| p.strip_prefix(dir.path()).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let all: Vec<PathBuf> = ["b/c/x.pdf", "b/y.pdf", "z.pdf"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(pdfs(None), all);
+        assert_eq!(pdfs(Some(3)), all);
+        assert_eq!(pdfs(Some(2)), all[1..]);
+        assert_eq!(pdfs(Some(1)), all[2..]);
+        assert!(pdfs(Some(0)).is_empty());
+
+        assert!(matches!(
+            collect_pdfs(dir.path().join("missing"), None),
+            Err(ShrinkError::Io { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_pdfs_symlink_loop() {
+        let dir = TempDir::new();
+        dir.pdf("a.pdf", 10);
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("a.pdf"), dir.path().join("b.pdf")).unwrap();
+        assert_eq!(
+            collect_pdfs(dir.path(), None).unwrap(),
+            [dir.path().join("a.pdf"), dir.path().join("b.pdf")]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Text drawn diagonally across every page of the output.
+
+use crate::ps_string;
+
+/// Text drawn diagonally across the center of every page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watermark {
+    pub text: String,
+    /// Font size, in points.
+    pub font_size: u32,
+    /// Opacity of the text, in percent.
+    pub opacity: u8,
+}
+
+impl Watermark {
+    /// Watermark with the default size (72 points) and opacity (30%).
+    pub fn new<S>(text: S) -> Watermark
+    where
+        S: Into<String>,
+    {
+        Watermark {
+            text: text.into(),
+            font_size: 72,
+            opacity: 30,
+        }
+    }
+
+    /// PostScript code installing an `EndPage` procedure which draws the watermark.
+    ///
+    /// The text is centered on the page and rotated along its diagonal.
+    /// The opacity requires Ghostscript 9.53 or later, older versions draw the text opaque.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pdfshrink::Watermark;
+    /// let code = Watermark::new("DRAFT").postscript();
+    /// assert!(code.contains("(DRAFT)"));
+    /// assert!(code.contains("/EndPage"));
+    /// ```
+    pub fn postscript(&self) -> String {
+        let opacity = f64::from(self.opacity.min(100)) / 100.0;
+        format!(
+            "<< /EndPage {{ 2 eq {{ pop false }} {{ pop gsave \
+             currentpagedevice /PageSize get aload pop \
+             2 copy 2 div exch 2 div exch translate exch atan rotate \
+             /Helvetica-Bold {size} selectfont 0.5 setgray \
+             /.setfillconstantalpha where {{ pop {opacity} .setfillconstantalpha }} if \
+             {text} dup stringwidth pop -2 div {size} -3 div moveto show \
+             grestore true }} ifelse }} bind >> setpagedevice",
+            size = self.font_size,
+            opacity = opacity,
+            text = ps_string(&self.text),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_postscript() {
+        let watermark = Watermark {
+            text: "DRAFT (v2)".into(),
+            font_size: 48,
+            opacity: 50,
+        };
+        let code = watermark.postscript();
+        assert!(code.starts_with("<< /EndPage {"));
+        assert!(code.ends_with(">> setpagedevice"));
+        assert!(code.contains("/Helvetica-Bold 48 selectfont"));
+        assert!(code.contains("0.5 .setfillconstantalpha"));
+        assert!(code.contains("(DRAFT \\(v2\\)) dup stringwidth"));
+        assert_eq!(code.matches('{').count(), code.matches('}').count());
+    }
+}
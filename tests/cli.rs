@@ -0,0 +1,802 @@
+//! Runs the `pdfshrink` binary with a fake Ghostscript.
+#![cfg(all(unix, feature = "build-binary"))]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory containing a `gs` script which writes a minimal PDF to its `-sOutputFile`.
+///
+/// The script appends its arguments, one per line, to `gs-args.log` in the directory.
+fn fake_gs_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("bin")).unwrap();
+    let gs = dir.join("bin/gs");
+    fs::write(
+        &gs,
+        "#!/bin/sh\n\
+         printf '%s\\n' \"$@\" >> \"${0%/*}/../gs-args.log\"\n\
+         for arg in \"$@\"; do\n\
+         case \"$arg\" in -sOutputFile=*) printf '%%PDF-1.4\\n' > \"${arg#-sOutputFile=}\";; esac\n\
+         done\n",
+    )
+    .unwrap();
+    fs::set_permissions(&gs, fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+fn pdfshrink(dir: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_pdfshrink"));
+    cmd.current_dir(dir).env("PATH", dir.join("bin"));
+    cmd
+}
+
+#[test]
+fn test_print_output_paths() {
+    let dir = fake_gs_dir("print-output-paths");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+    fs::write(dir.join("b c.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--print-output-paths", "a.pdf", "b c.pdf", "d.txt"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a.shrunk.pdf\nb c.shrunk.pdf\n");
+    assert!(!output.stderr.is_empty());
+
+    let output = pdfshrink(&dir)
+        .args(["--print-output-paths", "--null", "a.pdf", "b c.pdf"])
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, b"a.shrunk.pdf\0b c.shrunk.pdf\0");
+
+    // nothing else may be written to stdout
+    for flags in [
+        &["--summary-format", "text"][..],
+        &["--log", "stderr"],
+        &["--histogram"],
+    ] {
+        let output = pdfshrink(&dir)
+            .arg("--print-output-paths")
+            .args(flags)
+            .arg("a.pdf")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", flags);
+        assert_eq!(output.stdout, b"a.shrunk.pdf\n", "{:?}", flags);
+    }
+    for flags in [
+        &["--json"][..],
+        &["--summary-format", "json"],
+        &["--summary-format", "csv"],
+        &["--log", "stdout"],
+    ] {
+        let output = pdfshrink(&dir)
+            .arg("--print-output-paths")
+            .args(flags)
+            .arg("a.pdf")
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", flags);
+        assert!(output.stdout.is_empty(), "{:?}", flags);
+    }
+}
+
+#[test]
+fn test_compare() {
+    let dir = fake_gs_dir("compare");
+    fs::write(dir.join("a.pdf"), vec![b'x'; 4096]).unwrap();
+    fs::write(dir.join("a.shrunk.pdf"), vec![b'x'; 1024]).unwrap();
+    fs::write(dir.join("b.pdf"), vec![b'x'; 2048]).unwrap();
+    // the fake gs must not run
+    fs::remove_file(dir.join("bin/gs")).unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--compare", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "a.pdf -> a.shrunk.pdf: 4.0 KiB -> 1.0 KiB (25.0%)\n"
+    );
+
+    let output = pdfshrink(&dir)
+        .args([
+            "--compare",
+            "--pairs",
+            "b.pdf",
+            "a.shrunk.pdf",
+            "a.pdf",
+            "b.pdf",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "b.pdf -> a.shrunk.pdf: 2.0 KiB -> 1.0 KiB (50.0%)\n\
+         a.pdf -> b.pdf: 4.0 KiB -> 2.0 KiB (50.0%)\n"
+    );
+
+    // the output of b.pdf does not exist
+    let output = pdfshrink(&dir)
+        .args(["--compare", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(output.stdout.split(|&b| b == b'\n').count(), 2);
+}
+
+#[test]
+fn test_input_list_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let dir = fake_gs_dir("input-list-from-stdin");
+    for name in ["a.pdf", "b.pdf", "c.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    for (mode, progress) in [("buffer", "[3/3]"), ("stream", "[3]")] {
+        let mut child = pdfshrink(&dir)
+            .args([
+                "--input-list-from-stdin",
+                "--input-list-mode",
+                mode,
+                "a.pdf",
+            ])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"b.pdf\n\nc.pdf\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains(progress), "{}", stderr);
+        assert!(dir.join("c.shrunk.pdf").exists());
+        fs::remove_file(dir.join("c.shrunk.pdf")).unwrap();
+    }
+}
+
+#[test]
+fn test_inplace_never_writes_to_the_input() {
+    let dir = fake_gs_dir("inplace");
+    let mut original = b"%PDF-1.4\n".to_vec();
+    original.resize(1000, b'x');
+    fs::write(dir.join("a.pdf"), &original).unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--inplace", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let log = fs::read_to_string(dir.join("gs-args.log")).unwrap();
+    let written: Vec<&str> = log
+        .lines()
+        .filter_map(|arg| arg.strip_prefix("-sOutputFile="))
+        .collect();
+    assert_eq!(written.len(), 1);
+    assert_ne!(
+        dir.join(written[0]).canonicalize().ok(),
+        dir.join("a.pdf").canonicalize().ok()
+    );
+    // the input was replaced and the temporary file renamed
+    assert_eq!(fs::read(dir.join("a.pdf")).unwrap(), b"%PDF-1.4\n");
+    assert!(!dir.join(written[0]).exists());
+}
+
+#[test]
+fn test_always_replace() {
+    // the fake Ghostscript writes 9 bytes, more than the input
+    let dir = fake_gs_dir("always-replace");
+    fs::write(dir.join("a.pdf"), b"%PDF-").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--inplace", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("a.pdf")).unwrap(), b"%PDF-");
+
+    let output = pdfshrink(&dir)
+        .args(["--inplace", "--always-replace", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("a.pdf")).unwrap(), b"%PDF-1.4\n");
+
+    let output = pdfshrink(&dir)
+        .args(["--always-replace", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_pdf_version() {
+    let dir = fake_gs_dir("pdf-version");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--pdf-version", "1.7", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let log = fs::read_to_string(dir.join("gs-args.log")).unwrap();
+    assert!(log.lines().any(|arg| arg == "-dCompatibilityLevel=1.7"));
+
+    let output = pdfshrink(&dir)
+        .args(["--pdf-version", "1.8", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_checksum() {
+    let dir = fake_gs_dir("checksum");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--checksum", "sha256", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // digest of the 9 bytes written by the fake Ghostscript
+    assert_eq!(
+        fs::read_to_string(dir.join("a.shrunk.pdf.sha256")).unwrap(),
+        "e5c62df5dab5c87b6a015ef3d43597074d1eec433b15f51aec63b8582d0e4ab4  a.shrunk.pdf\n"
+    );
+    assert!(!dir.join("a.shrunk.pdf.md5").exists());
+}
+
+#[test]
+fn test_suffix() {
+    let dir = fake_gs_dir("suffix");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--suffix", "small", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("a.small.pdf").exists());
+    assert!(!dir.join("a.shrunk.pdf").exists());
+
+    for suffix in &["", "web/x"] {
+        let output = pdfshrink(&dir)
+            .args(["--suffix", suffix, "a.pdf"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", suffix);
+    }
+}
+
+#[test]
+fn test_json_errors() {
+    let dir = fake_gs_dir("json-errors");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+    fs::write(dir.join("b.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir).args(["--json", "a.pdf"]).output().unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["output"], "a.shrunk.pdf");
+    let command = report["command"].as_str().unwrap();
+    assert!(command.starts_with(dir.join("bin/gs").to_str().unwrap()));
+    assert!(command.ends_with(" -sOutputFile=a.shrunk.pdf a.pdf"));
+
+    // Ghostscript fails without writing the output
+    fs::write(dir.join("bin/gs"), "#!/bin/sh\nexit 1\n").unwrap();
+    let output = pdfshrink(&dir).args(["--json", "b.pdf"]).output().unwrap();
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let error: serde_json::Value = stderr
+        .lines()
+        .find_map(|line| serde_json::from_str(line).ok())
+        .unwrap_or_else(|| panic!("no JSON error in {:?}", stderr));
+    assert_eq!(error["kind"], "non-zero-exit");
+    assert_eq!(error["input"], "b.pdf");
+    assert_eq!(error["error"], "Ghostscript exited with code 1");
+}
+
+#[test]
+fn test_expand_input_patterns() {
+    let dir = fake_gs_dir("expand-input-patterns");
+    fs::create_dir(dir.join("2022")).unwrap();
+    fs::create_dir(dir.join("2023")).unwrap();
+    for name in ["2022/a.pdf", "2022/b.pdf", "2023/c.pdf", "{x}.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    let output = pdfshrink(&dir)
+        .args(["--print-output-paths", "{2022,2023}/*.pdf", "{x}.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "2022/a.shrunk.pdf\n2022/b.shrunk.pdf\n2023/c.shrunk.pdf\n{x}.shrunk.pdf\n"
+    );
+}
+
+#[test]
+fn test_glob_strict() {
+    let dir = fake_gs_dir("glob-strict");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--print-output-paths", "missing/*.pdf", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a.shrunk.pdf\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No file matches \"missing/*.pdf\""));
+    fs::remove_file(dir.join("a.shrunk.pdf")).unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--glob-strict", "missing/*.pdf", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no file matches \"missing/*.pdf\""));
+    assert!(!dir.join("a.shrunk.pdf").exists());
+}
+
+#[test]
+fn test_no_glob() {
+    let dir = fake_gs_dir("no-glob");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--no-glob", "--print-output-paths", "*.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\"*.pdf\""));
+    assert!(!dir.join("a.shrunk.pdf").exists());
+}
+
+#[test]
+fn test_recursive() {
+    let dir = fake_gs_dir("recursive");
+    fs::create_dir_all(dir.join("docs/2024")).unwrap();
+    for name in ["docs/a.pdf", "docs/notes.txt", "docs/2024/b.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    let output = pdfshrink(&dir)
+        .args([
+            "--recursive",
+            "--max-depth",
+            "1",
+            "--print-output-paths",
+            "docs",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"docs/a.shrunk.pdf\n");
+
+    let output = pdfshrink(&dir)
+        .args(["-R", "--print-output-paths", "--skip-shrunk", "docs"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        output.stdout,
+        b"docs/2024/b.shrunk.pdf\ndocs/a.shrunk.pdf\n"
+    );
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("notes.txt"));
+}
+
+#[test]
+fn test_jobs() {
+    let dir = fake_gs_dir("jobs");
+    let names: Vec<String> = (0..8).map(|i| format!("{}.pdf", i)).collect();
+    for name in &names {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    let output = pdfshrink(&dir)
+        .args(["--jobs", "4", "--print-output-paths"])
+        .args(&names)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let expected: String = (0..8).map(|i| format!("{}.shrunk.pdf\n", i)).collect();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+    let log = fs::read_to_string(dir.join("gs-args.log")).unwrap();
+    assert_eq!(log.lines().filter(|arg| *arg == "-dSAFER").count(), 8);
+
+    let output = pdfshrink(&dir).args(["-j", "0", "0.pdf"]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_content_address() {
+    let dir = fake_gs_dir("content-address");
+    for name in ["a.pdf", "b.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    let output = pdfshrink(&dir)
+        .args(["--content-address", "store", "--print-output-paths"])
+        .args(["a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // both outputs are identical, only the first one is stored
+    let stored: Vec<_> = fs::read_dir(dir.join("store"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(stored.len(), 1);
+    let name = &stored[0];
+    assert!(name.ends_with(".pdf") && name.len() == 64 + 4, "{}", name);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("store/{}\n", name)
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Skipping \"b.pdf\""), "{}", stderr);
+
+    // later runs find the same name
+    let output = pdfshrink(&dir)
+        .args(["--content-address", "store", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Skipping \"a.pdf\""));
+    assert_eq!(fs::read_dir(dir.join("store")).unwrap().count(), 1);
+}
+
+#[test]
+fn test_max_runtime() {
+    let dir = fake_gs_dir("max-runtime");
+    for name in ["a.pdf", "b.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+
+    let output = pdfshrink(&dir)
+        .args(["--max-runtime", "0", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("2 files were left unprocessed"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("gs-args.log").exists());
+
+    let output = pdfshrink(&dir)
+        .args(["--max-runtime", "1h", "--kill-at-max-runtime", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("a.shrunk.pdf").exists());
+    let output = pdfshrink(&dir)
+        .args(["--max-runtime", "1x", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_gs_arg() {
+    let dir = fake_gs_dir("gs-arg");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args([
+            "--gs-arg",
+            "-dNOINTERPOLATE",
+            "--gs-arg=-dDownsampleColorImages=false",
+        ])
+        .arg("a.pdf")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let log = fs::read_to_string(dir.join("gs-args.log")).unwrap();
+    let args: Vec<&str> = log.lines().collect();
+    let n = args.len();
+    assert_eq!(
+        args[n - 4..],
+        [
+            "-dNOINTERPOLATE",
+            "-dDownsampleColorImages=false",
+            "-sOutputFile=a.shrunk.pdf",
+            "a.pdf"
+        ]
+    );
+}
+
+#[test]
+fn test_upgrade() {
+    let dir = fake_gs_dir("upgrade");
+    let unmarked = b"%PDF-1.4\n%%EOF\n";
+    fs::write(dir.join("a.pdf"), unmarked).unwrap();
+    fs::write(
+        dir.join("b.pdf"),
+        "%PDF-1.4\n<< /PdfShrink (-r72) /PdfShrinkHash (0000000000000000) >>\n%%EOF\n",
+    )
+    .unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--inplace", "--upgrade", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let log = fs::read_to_string(dir.join("gs-args.log")).unwrap();
+    let args: Vec<&str> = log.lines().collect();
+    // only the file shrunk with other settings is shrunk again, recording the new ones
+    assert!(!args.contains(&"a.pdf"));
+    assert!(args.contains(&"b.pdf"));
+    assert!(args.iter().any(|arg| arg.contains("/PdfShrinkHash (")));
+    assert_eq!(fs::read(dir.join("a.pdf")).unwrap(), unmarked);
+}
+
+#[test]
+fn test_malformed_sidecar() {
+    let dir = fake_gs_dir("malformed-sidecar");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+    fs::write(dir.join("b.pdf"), b"%PDF-1.4\n").unwrap();
+    fs::write(
+        dir.join("a.pdf.pdfshrink.toml"),
+        "color_resolution = \"high\"\n",
+    )
+    .unwrap();
+
+    let output = pdfshrink(&dir).args(["a.pdf", "b.pdf"]).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid sidecar"), "{}", stderr);
+    assert!(stderr.contains("1 shrunk, 1 failed"), "{}", stderr);
+    // the other files are still shrunk
+    assert!(!dir.join("a.shrunk.pdf").exists());
+    assert!(dir.join("b.shrunk.pdf").exists());
+}
+
+#[test]
+fn test_explain_inplace() {
+    let dir = fake_gs_dir("explain-inplace");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    // the input would be replaced before its images could be compared
+    let output = pdfshrink(&dir)
+        .args(["--explain", "--inplace", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    assert!(!dir.join("gs-args.log").exists());
+}
+
+#[test]
+fn test_run_plan_failure() {
+    let dir = fake_gs_dir("run-plan-failure");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--save-plan", "plan.json", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let output = pdfshrink(&dir)
+        .args(["--run-plan", "plan.json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("a.shrunk.pdf").exists());
+
+    // Ghostscript fails when the plan is executed
+    fs::write(dir.join("bin/gs"), "#!/bin/sh\nexit 2\n").unwrap();
+    let output = pdfshrink(&dir)
+        .args(["--run-plan", "plan.json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("1 of 1 jobs of the plan failed"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    let dir = fake_gs_dir("checkpoint");
+    for name in ["a.pdf", "b.pdf", "c.pdf"] {
+        fs::write(dir.join(name), b"%PDF-1.4\n").unwrap();
+    }
+    // a previous run was interrupted after shrinking a.pdf
+    fs::write(dir.join("progress.txt"), "a.pdf\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--checkpoint", "progress.txt", "a.pdf", "b.pdf", "c.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!dir.join("a.shrunk.pdf").exists());
+    assert!(dir.join("b.shrunk.pdf").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join("progress.txt")).unwrap(),
+        "a.pdf\nb.pdf\nc.pdf\n"
+    );
+
+    // everything is done
+    fs::remove_file(dir.join("gs-args.log")).unwrap();
+    let output = pdfshrink(&dir)
+        .args(["--checkpoint", "progress.txt", "a.pdf", "b.pdf", "c.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!dir.join("gs-args.log").exists());
+}
+
+#[test]
+fn test_gs_override() {
+    let dir = fake_gs_dir("gs-override");
+    fs::create_dir(dir.join("opt")).unwrap();
+    fs::rename(dir.join("bin/gs"), dir.join("opt/gs-10")).unwrap();
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir).arg("a.pdf").output().unwrap();
+    assert!(!output.status.success());
+
+    let output = pdfshrink(&dir)
+        .args(["--gs", "opt/gs-10", "a.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(dir.join("a.shrunk.pdf").exists());
+}
+
+#[test]
+fn test_histogram() {
+    let dir = fake_gs_dir("histogram");
+    let mut large = b"%PDF-1.4\n".to_vec();
+    large.resize(100, b'x');
+    fs::write(dir.join("a.pdf"), &large).unwrap();
+    fs::write(dir.join("b.pdf"), &large).unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--histogram", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // the fake Ghostscript writes 9 bytes
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("90-100% |"), "{}", stderr);
+    assert!(stderr.ends_with(" 2\n"), "{}", stderr);
+
+    // the histogram goes through the logger
+    let output = pdfshrink(&dir)
+        .args(["--histogram", "--log", "stdout", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Space saved:"));
+    assert!(output.stderr.is_empty());
+
+    let output = pdfshrink(&dir)
+        .args(["--histogram", "--json", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Space saved:"));
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Space saved:"));
+}
+
+#[test]
+fn test_lossy_input() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = fake_gs_dir("lossy-input");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+    let lossy = OsStr::from_bytes(b"caf\xe9.pdf");
+    // some file systems reject names which are not UTF-8
+    let _ = fs::write(dir.join(lossy), b"%PDF-1.4\n");
+
+    let output = pdfshrink(&dir).arg(lossy).arg("a.pdf").output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not valid Unicode"), "{}", stderr);
+    assert!(stderr.contains("1 shrunk, 1 failed"), "{}", stderr);
+    assert!(dir.join("a.shrunk.pdf").exists());
+}
+
+#[test]
+fn test_summary_format() {
+    let dir = fake_gs_dir("summary-format");
+    let mut large = b"%PDF-1.4\n".to_vec();
+    large.resize(100, b'x');
+    fs::write(dir.join("a.pdf"), &large).unwrap();
+    fs::write(dir.join("b.txt"), b"text").unwrap();
+
+    let run = |format: &str| {
+        let output = pdfshrink(&dir)
+            .args(["--summary-format", format, "a.pdf", "b.txt"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    let (stdout, stderr) = run("text");
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("Summary: 1 shrunk, 1 failed"), "{}", stderr);
+
+    let (stdout, _) = run("csv");
+    assert_eq!(
+        stdout,
+        "shrunk,failed,skipped,input_bytes,output_bytes,grown\n1,1,0,100,9,0\n"
+    );
+
+    let (stdout, _) = run("json");
+    let summary: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(summary["shrunk"], 1);
+    assert_eq!(summary["failed"], 1);
+    assert_eq!(summary["output_bytes"], 9);
+}
+
+#[test]
+fn test_dry_run_without_args_program() {
+    let dir = fake_gs_dir("dry-run-without-args");
+    fs::write(dir.join("a.pdf"), b"%PDF-1.4\n").unwrap();
+    fs::write(dir.join("b.pdf"), b"%PDF-1.4\n").unwrap();
+
+    let output = pdfshrink(&dir)
+        .args(["--dry-run", "a.pdf", "b.pdf"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(
+        stderr.matches("Cannot run \"args\"").count(),
+        2,
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("gs-args.log").exists());
+}
+
+#[test]
+fn test_version() {
+    let dir = fake_gs_dir("version");
+    let crate_line = format!("pdfshrink {}\n", env!("CARGO_PKG_VERSION"));
+
+    fs::write(dir.join("bin/gs"), "#!/bin/sh\necho 10.02.1\n").unwrap();
+    let output = pdfshrink(&dir).arg("--version").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}gs: 10.02\n", crate_line)
+    );
+
+    fs::remove_file(dir.join("bin/gs")).unwrap();
+    let output = pdfshrink(&dir).arg("-V").output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}gs: not found\n", crate_line)
+    );
+}
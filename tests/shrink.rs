@@ -0,0 +1,83 @@
+//! Shrinks a real PDF with Ghostscript, if it is installed.
+
+use std::path::{Path, PathBuf};
+
+use pdfshrink::{
+    resolve_ghostscript, shrink_file, shrink_open_file, ExitClass, ShrinkOptions, ShrinkOutcome,
+};
+
+/// A one-page PDF with a 128x128 grayscale image drawn at 512 dpi.
+fn fixture() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tiny.pdf")
+}
+
+#[test]
+fn test_shrink_fixture() {
+    let ghostscript = match resolve_ghostscript() {
+        Ok(gs) => gs,
+        Err(e) => {
+            eprintln!("skipping test_shrink_fixture: {}", e);
+            return;
+        }
+    };
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("tiny.shrunk.pdf");
+    let _ = std::fs::remove_file(&output);
+    let options = ShrinkOptions {
+        ghostscript: Some(ghostscript),
+        ..Default::default()
+    };
+    let report = match shrink_file(fixture(), &output, &options) {
+        Ok(ShrinkOutcome::Shrunk(report)) => report,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert_eq!(report.status, ExitClass::Success);
+    assert!(report.output_bytes <= report.input_bytes, "{:?}", report);
+
+    let bytes = std::fs::read(&output).unwrap();
+    assert_eq!(bytes.len() as u64, report.output_bytes);
+    assert!(bytes.starts_with(b"%PDF-"));
+    let tail = &bytes[bytes.len().saturating_sub(32)..];
+    assert!(tail.windows(5).any(|w| w == b"%%EOF"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_shrink_open_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("open-file");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    // copies the input, the last argument, to the output
+    let gs = dir.join("gs");
+    std::fs::write(
+        &gs,
+        "#!/bin/sh\n\
+         for arg in \"$@\"; do\n\
+         case \"$arg\" in -sOutputFile=*) output=\"${arg#-sOutputFile=}\";; esac\n\
+         input=\"$arg\"\n\
+         done\n\
+         cat \"$input\" > \"$output\"\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&gs, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let input = dir.join("secret.pdf");
+    std::fs::write(&input, b"%PDF-1.4\nopened once\n").unwrap();
+    let file = std::fs::File::open(&input).unwrap();
+    // the path is not used after opening the file
+    std::fs::remove_file(&input).unwrap();
+
+    let output = dir.join("secret.shrunk.pdf");
+    let options = ShrinkOptions {
+        ghostscript: Some(gs),
+        ..Default::default()
+    };
+    let report = match shrink_open_file(&file, &output, &options) {
+        Ok(ShrinkOutcome::Shrunk(report)) => report,
+        other => panic!("unexpected {:?}", other),
+    };
+    assert!(report.input.starts_with("/dev/fd"), "{:?}", report);
+    assert_eq!(std::fs::read(&output).unwrap(), b"%PDF-1.4\nopened once\n");
+}